@@ -30,6 +30,9 @@ ROLE_TO_TOKEN = {
     "error": Generic.Error,
     "command": Name.Function,
     "keyword": Name.Function,
+    "builtin": Name.Function,
+    "function": Name.Function,
+    "abbreviation": Name.Function,
     "statement_terminator": Punctuation,
     "param": Name.Constant,
     "option": Name.Constant,
@@ -37,11 +40,13 @@ ROLE_TO_TOKEN = {
     "match": DEFAULT,
     "search_match": DEFAULT,
     "operat": Operator,
+    "unset_variable": Operator,
     "escape": String.Escape,
     "quote": String.Single,  # note, may be changed to double dynamically
     "redirection": Punctuation,  # ?
     "autosuggestion": Other,  # in practice won't be generated
     "selection": DEFAULT,
+    "matching_pair": DEFAULT,
     "pager_progress": DEFAULT,
     "pager_background": DEFAULT,
     "pager_prefix": DEFAULT,
@@ -56,23 +56,30 @@ complete -c bind -s L -l list-modes -d 'Display a list of defined bind modes'
 complete -c bind -s s -l silent -d 'Operate silently'
 complete -c bind -l preset -d 'Operate on preset bindings'
 complete -c bind -l user -d 'Operate on user bindings'
+complete -c bind -l macro -d 'Bind keys to a recorded macro'
+complete -c bind -l timeout -x -d 'Milliseconds to wait for each key of the sequence'
+complete -c bind -l on-timeout -x -a 'prefix\t"Handle the keys typed so far" insert\t"Insert the keys typed so far"' -d 'What to do when the timeout expires'
+complete -c bind -l description -x -d 'Description to show in sequence hints'
 
 complete -c bind -n __fish_bind_test2 -a '(bind --function-names)' -d 'Function name' -x
 
 function __fish_bind_complete
     argparse M/mode= m/sets-mode= preset user s/silent \
-        a/all function-names list-modes e/erase -- (commandline -xpc)[2..] 2>/dev/null
+        a/all function-names list-modes e/erase macro=? timeout= on-timeout= description= -- (commandline -xpc)[2..] 2>/dev/null
     or return 1
     set -l token (commandline -ct)
-    if test (count $argv) = 0 && set -l prefix (string match -r -- '(.*,)?(ctrl-|alt-|shift-)*' $token)
+    if test (count $argv) = 0 && set -l prefix (string match -r -- '(.*,)?(ctrl-|alt-|shift-|keypad-|release-|repeat-)*' $token)
         printf '%sctrl-\tCtrl modifier…\n' $prefix
         printf '%sc-\tCtrl modifier…\n' $prefix
         printf '%salt-\tAlt modifier…\n' $prefix
         printf '%sa-\tAlt modifier…\n' $prefix
         printf '%sshift-\tShift modifier…\n' $prefix
+        printf '%skeypad-\tKey on the numeric keypad…\n' $prefix
+        printf '%srelease-\tKey release…\n' $prefix
+        printf '%srepeat-\tKey repeat…\n' $prefix
         set -l key_names minus comma backspace delete escape \
             enter up down left right pageup pagedown home end insert tab \
-            space f(seq 12)
+            space f(seq 12) mouseleft mousemiddle mouseright wheelup wheeldown
         printf '%s\tNamed key\n' $prefix$key_names
     end
 end
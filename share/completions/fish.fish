@@ -11,6 +11,7 @@ complete -c fish -l profile-startup -d "Output startup profiling information to
 complete -c fish -s d -l debug -d "Specify debug categories" -x -a "(fish --print-debug-categories | string replace ' ' \t)"
 complete -c fish -s o -l debug-output -d "Where to direct debug output to" -rF
 complete -c fish -s P -l private -d "Do not persist history"
+complete -c fish -l replay-keys -d "Replay a key recording as interactive input" -r
 
 function __fish_complete_features
     set -l arg_comma (commandline -tc | string replace -rf '(.*,)[^,]*' '$1' | string replace -r -- '--.*=' '')
@@ -6,7 +6,12 @@ complete -c fish_indent -l only-unindent -d 'Do not reformat, only unindent line
 complete -c fish_indent -l ansi -d 'Colorize the output using ANSI escape sequences'
 complete -c fish_indent -l html -d 'Output in HTML format'
 complete -c fish_indent -s w -l write -d 'Write to file'
+complete -c fish_indent -l diff -d 'Print the changes indenting would make as a diff'
+complete -c fish_indent -l max-width -x -d 'Break lines wider than this many columns'
+complete -c fish_indent -l no-config -d 'Ignore .fish_indent config files'
+complete -c fish_indent -l fix -x -a "all (status features | string replace -rf '^([\w-]+).*\t(.*)\$' '\$1\t\$2')" -d 'Rewrite syntax changed by feature flags'
 complete -c fish_indent -s d -l debug -x -d 'Enable debug at specified verbosity level'
 complete -c fish_indent -s o -l debug-output -d "Where to direct debug output to" -rF
 complete -c fish_indent -s D -l debug-stack-frames -x -d 'Specify how many stack frames to display in debug messages'
 complete -c fish_indent -l dump-parse-tree -d 'Dump information about parsed statements to stderr'
+complete -c fish_indent -l dump-ast -x -a json -d 'Print the syntax tree in the given format'
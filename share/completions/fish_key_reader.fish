@@ -2,3 +2,5 @@ complete -c fish_key_reader -s h -l help -d 'Display help and exit'
 complete -c fish_key_reader -s v -l version -d 'Display version and exit'
 complete -c fish_key_reader -s c -l continuous -d 'Start a continuous session'
 complete -c fish_key_reader -s V -l verbose -d 'Output timing and explain sequence'
+complete -c fish_key_reader -s j -l json -d 'Print keys as JSON'
+complete -c fish_key_reader -l record -r -d 'Record keys to a file for fish --replay-keys'
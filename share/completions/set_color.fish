@@ -4,6 +4,10 @@ complete -c set_color -s o -l bold -d 'Make font bold'
 complete -c set_color -s i -l italics -d Italicise
 complete -c set_color -s d -l dim -d 'Dim text'
 complete -c set_color -s r -l reverse -d 'Reverse color text'
-complete -c set_color -s u -l underline -d 'Underline text'
+complete -c set_color -s u -l underline -a 'single double curly dotted dashed' -d 'Underline text'
+complete -c set_color -l underline-color -x -a '(set_color --print-colors)' -d 'Change underline color'
+complete -c set_color -s s -l strikethrough -d 'Strike text through'
+complete -c set_color -l overline -d 'Overline text'
 complete -c set_color -s h -l help -d 'Display help and exit'
 complete -c set_color -s c -l print-colors -d 'Print a list of all accepted color names'
+complete -c set_color -l hyperlink -x -d 'Make the following text a hyperlink'
@@ -0,0 +1,10 @@
+# Note that when a completion file is sourced a new block scope is created so `set -l` works.
+set -l __fish_theme_all_commands list show choose
+
+complete -c theme -s h -l help -d "Display help and exit"
+complete -f -c theme -n "not __fish_seen_subcommand_from $__fish_theme_all_commands" -a list -d "List the available themes"
+complete -f -c theme -n "not __fish_seen_subcommand_from $__fish_theme_all_commands" -a show -d "Preview the current colors and themes"
+complete -f -c theme -n "not __fish_seen_subcommand_from $__fish_theme_all_commands" -a choose -d "Apply a theme"
+complete -f -c theme -n "__fish_seen_subcommand_from show choose" -a "(theme list)"
+complete -f -c theme -n "__fish_seen_subcommand_from show choose" -l variant -x -a "light dark" -d "Use the light or dark colors"
+complete -f -c theme -n "__fish_seen_subcommand_from choose" -s U -l universal -d "Set the colors as universal variables"
@@ -29,6 +29,11 @@ function __fish_shared_key_bindings -d "Bindings shared between emacs and vi mod
     $legacy_bind --preset $argv -k ppage beginning-of-history
     $legacy_bind --preset $argv -k npage end-of-history
 
+    # Mouse support, only reported if $fish_mouse is set.
+    bind --preset $argv mouseleft mouse-click
+    bind --preset $argv wheelup scroll-up
+    bind --preset $argv wheeldown scroll-down
+
     # Interaction with the system clipboard.
     bind --preset $argv ctrl-x fish_clipboard_copy
     bind --preset $argv ctrl-v fish_clipboard_paste
@@ -82,6 +87,9 @@ function __fish_shared_key_bindings -d "Bindings shared between emacs and vi mod
 
     bind --preset $argv alt-s 'for cmd in sudo doas please; if command -q $cmd; fish_commandline_prepend $cmd; break; end; end'
 
+    # Fix a mistyped command, like "gti" for "git".
+    bind --preset $argv alt-x correct-command
+
     # Allow reading manpages by pressing f1 (many GUI applications) or Alt+h (like in zsh).
     bind --preset $argv f1 __fish_man_page
     $legacy_bind --preset $argv -k f1 __fish_man_page
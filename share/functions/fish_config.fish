@@ -156,11 +156,9 @@ function fish_config --description "Launch fish's web based configuration"
                 return 1
             end
 
-            set -l dirs $__fish_config_dir/themes $__fish_data_dir/tools/web_config/themes
-
             switch $cmd
                 case list ''
-                    string replace -r '.*/([^/]*).theme$' '$1' $dirs/*.theme
+                    theme list
                     return
                 case demo
                     echo -ns (set_color $fish_color_command || set_color $fish_color_normal) /bright/vixens
@@ -187,33 +185,7 @@ function fish_config --description "Launch fish's web based configuration"
                     echo is an autosuggestion
                     echo
                 case show
-                    set -l fish (status fish-path)
-                    set -l themes $dirs/$argv.theme
-                    set -q themes[1]; or set themes $dirs/*.theme
-                    set -l used_themes
-
-                    echo -s (set_color normal; set_color --underline) Current (set_color normal)
-                    fish_config theme demo
-
-                    for t in $themes
-                        not test -e "$t"
-                        and continue
-
-                        set -l themename (string replace -r '.*/([^/]*).theme$' '$1' $t)
-                        contains -- $themename $used_themes
-                        and continue
-                        set -a used_themes $themename
-
-                        echo -s (set_color normal; set_color --underline) $themename (set_color normal)
-
-                        # Use a new, --no-config, fish to display the theme.
-                        # So we can use this function, explicitly source it before anything else!
-                        functions fish_config | $fish -C "source -" --no-config -c '
-                        fish_config theme choose $argv
-                        fish_config theme demo $argv
-                        ' $themename
-                    end
-
+                    theme show $argv
                 case choose save
                     if set -q argv[2]
                         echo "Too many arguments" >&2
@@ -226,7 +198,6 @@ function fish_config --description "Launch fish's web based configuration"
                     end
 
                     set -l scope -g
-                    set -l have_colors
 
                     if contains -- $cmd save
                         read -P"Overwrite your current theme? [y/N] " -l yesno
@@ -247,48 +218,12 @@ function fish_config --description "Launch fish's web based configuration"
                     # If we are choosing a theme or saving from a named theme, load the theme now.
                     # Otherwise, we'll persist the currently loaded/themed variables (in case of `theme save`).
                     if set -q argv[1]
-                        set -l files $dirs/$argv[1].theme
-                        set -l file
-
-                        for f in $files
-                            if test -e "$f"
-                                set file $f
-                                break
-                            end
-                        end
-
-                        if not set -q file[1]
-                            echo "No such theme: $argv[1]" >&2
-                            echo "Searched directories: $dirs" >&2
-                            return 1
-                        end
-
-                        while read -lat toks
-                            # The whitelist allows only color variables.
-                            # Not the specific list, but something named *like* a color variable.
-                            # This also takes care of empty lines and comment lines.
-                            string match -rq -- $theme_var_filter $toks[1]
-                            or continue
-
-                            # If we're supposed to set universally, remove any shadowing globals
-                            # so the change takes effect immediately (and there's no warning).
-                            if test x"$scope" = x-U; and set -qg $toks[1]
-                                set -eg $toks[1]
-                            end
-                            set $scope $toks
-                            set -a have_colors $toks[1]
-                        end <$file
-
-                        # Set all colors that aren't mentioned to empty
-                        for c in $known_colors
-                            contains -- $c $have_colors
-                            and continue
-
-                            # Erase conflicting global variables so we don't get a warning and
-                            # so changes are observed immediately.
-                            set -eg $c
-                            set $scope $c
+                        if test x"$scope" = x-U
+                            theme choose --universal $argv[1]
+                        else
+                            theme choose $argv[1]
                         end
+                        or return 1
                     else
                         # We're persisting whatever current colors are loaded (maybe in the global scope)
                         # to the universal scope, without overriding them from a theme file.
@@ -1482,6 +1482,9 @@ class FishConfigHTTPRequestHandler(SimpleHTTPServer.SimpleHTTPRequestHandler):
                     "fish_color_normal",
                     "fish_color_command",
                     "fish_color_keyword",
+                    "fish_color_builtin",
+                    "fish_color_function",
+                    "fish_color_abbreviation",
                     "fish_color_quote",
                     "fish_color_redirection",
                     "fish_color_end",
@@ -1490,7 +1493,10 @@ class FishConfigHTTPRequestHandler(SimpleHTTPServer.SimpleHTTPRequestHandler):
                     "fish_color_option",
                     "fish_color_comment",
                     "fish_color_selection",
+                    "fish_color_paste",
                     "fish_color_operator",
+                    "fish_color_unset_variable",
+                    "fish_color_match",
                     "fish_color_escape",
                     "fish_color_autosuggestion",
                     "fish_color_cwd",
@@ -4,6 +4,7 @@ use std::{
     sync::{Mutex, MutexGuard},
 };
 
+use crate::highlight::highlight_invalidate_cache;
 use crate::wchar::prelude::*;
 use once_cell::sync::Lazy;
 
@@ -21,10 +22,12 @@ pub fn with_abbrs<R>(cb: impl FnOnce(&AbbreviationSet) -> R) -> R {
 
 pub fn with_abbrs_mut<R>(cb: impl FnOnce(&mut AbbreviationSet) -> R) -> R {
     let mut abbrs_g = ABBRS.lock().unwrap();
+    highlight_invalidate_cache();
     cb(&mut abbrs_g)
 }
 
 pub fn abbrs_get_set() -> MutexGuard<'static, AbbreviationSet> {
+    highlight_invalidate_cache();
     ABBRS.lock().unwrap()
 }
 
@@ -41,6 +41,7 @@ use fish::{
     fprintf, function, future_feature_flags as features,
     history::{self, start_private_mode},
     io::IoChain,
+    key_recording::replay_keys,
     nix::{getpid, isatty},
     panic::panic_handler,
     parse_constants::{ParseErrorList, ParseTreeFlags},
@@ -100,6 +101,8 @@ struct FishCmdOpts {
     is_interactive_session: bool,
     /// Whether to enable private mode.
     enable_private_mode: bool,
+    /// A key recording to use as input.
+    replay_keys: Option<WString>,
 }
 
 /// Return a timeval converted to milliseconds.
@@ -318,6 +321,7 @@ fn fish_parse_opt(args: &mut [WString], opts: &mut FishCmdOpts) -> ControlFlow<i
     const RUSAGE_ARG: char = 1 as char;
     const PRINT_DEBUG_CATEGORIES_ARG: char = 2 as char;
     const PROFILE_STARTUP_ARG: char = 3 as char;
+    const REPLAY_KEYS_ARG: char = 4 as char;
 
     const SHORT_OPTS: &wstr = L!("+hPilNnvc:C:p:d:f:D:o:");
     const LONG_OPTS: &[WOption<'static>] = &[
@@ -340,6 +344,7 @@ fn fish_parse_opt(args: &mut [WString], opts: &mut FishCmdOpts) -> ControlFlow<i
         wopt(L!("profile"), RequiredArgument, 'p'),
         wopt(L!("profile-startup"), RequiredArgument, PROFILE_STARTUP_ARG),
         wopt(L!("private"), NoArgument, 'P'),
+        wopt(L!("replay-keys"), RequiredArgument, REPLAY_KEYS_ARG),
         wopt(L!("help"), NoArgument, 'h'),
         wopt(L!("version"), NoArgument, 'v'),
     ];
@@ -401,6 +406,11 @@ fn fish_parse_opt(args: &mut [WString], opts: &mut FishCmdOpts) -> ControlFlow<i
                 PROFILING_ACTIVE.store(true);
             }
             'P' => opts.enable_private_mode = true,
+            REPLAY_KEYS_ARG => {
+                // The keys are meant for the interactive reader.
+                opts.replay_keys = Some(w.woptarg.unwrap().to_owned());
+                opts.is_interactive_session = true;
+            }
             'v' => {
                 printf!(
                     "%s",
@@ -527,6 +537,13 @@ fn throwing_main() -> i32 {
         };
     }
 
+    if let Some(path) = &opts.replay_keys {
+        if let Err(err) = replay_keys(path) {
+            eprintf!("%ls\n", err);
+            return 1;
+        }
+    }
+
     // No-exec is prohibited when in interactive mode.
     if opts.is_interactive_session && opts.no_exec {
         FLOG!(
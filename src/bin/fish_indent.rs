@@ -8,45 +8,57 @@
 
 use std::ffi::{CString, OsStr};
 use std::io::{stdin, Read, Write};
+use std::ops::Range;
 use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 use std::sync::atomic::Ordering;
 
 use fish::panic::panic_handler;
 use libc::{LC_ALL, STDOUT_FILENO};
 
 use fish::ast::{
-    self, Ast, Category, Leaf, List, Node, NodeVisitor, SourceRangeList, Traversal, Type,
+    self, ast_type_to_string, Ast, Category, Leaf, List, Node, NodeVisitor, SourceRangeList,
+    Traversal, Type,
 };
 use fish::builtins::shared::{STATUS_CMD_ERROR, STATUS_CMD_OK};
 use fish::common::{
-    str2wcstring, unescape_string, wcs2string, wcs2zstring, UnescapeFlags, UnescapeStringStyle,
-    PROGRAM_NAME,
+    escape_string, read_unquoted_escape, str2wcstring, unescape_string, wcs2string, wcs2zstring,
+    EscapeStringStyle, UnescapeFlags, UnescapeStringStyle, PROGRAM_NAME, RESERVED_CHAR_BASE,
+    RESERVED_CHAR_END,
 };
 use fish::env::env_init;
 use fish::env::environment::Environment;
 use fish::env::EnvStack;
 use fish::eprintf;
-use fish::expand::INTERNAL_SEPARATOR;
+use fish::expand::{INTERNAL_SEPARATOR, PROCESS_EXPAND_SELF_STR};
+use fish::fallback::fish_wcwidth;
 use fish::fds::set_cloexec;
 use fish::fprintf;
 #[allow(unused_imports)]
 use fish::future::{IsSomeAnd, IsSorted};
+use fish::future_feature_flags::{FeatureFlag, METADATA};
 use fish::global_safety::RelaxedAtomicBool;
 use fish::highlight::{colorize, highlight_shell, HighlightRole, HighlightSpec};
 use fish::libc::setlinebuf;
 use fish::operation_context::OperationContext;
-use fish::parse_constants::{ParseTokenType, ParseTreeFlags, SourceRange};
-use fish::parse_util::{apply_indents, parse_util_compute_indents, SPACES_PER_INDENT};
+use fish::parse_constants::{ParseErrorCode, ParseTokenType, ParseTreeFlags, SourceRange};
+use fish::parse_util::{
+    apply_indents, parse_util_compute_indents, parse_util_locate_cmdsubst_range, MaybeParentheses,
+    Parentheses, SPACES_PER_INDENT,
+};
 use fish::print_help::print_help;
 use fish::printf;
 use fish::threads;
-use fish::tokenizer::{TokenType, Tokenizer, TOK_SHOW_BLANK_LINES, TOK_SHOW_COMMENTS};
+use fish::tokenizer::{
+    tok_is_string_character, TokenType, Tokenizer, TOK_AMPERSAND_ENDS_TOKEN, TOK_SHOW_BLANK_LINES,
+    TOK_SHOW_COMMENTS,
+};
 use fish::topic_monitor::topic_monitor_init;
 use fish::wchar::prelude::*;
-use fish::wcstringutil::count_preceding_backslashes;
+use fish::wcstringutil::{count_preceding_backslashes, trim};
 use fish::wgetopt::{wopt, ArgType, WGetopter, WOption};
 use fish::wutil::perror;
-use fish::wutil::{fish_iswalnum, write_to_fd};
+use fish::wutil::{fish_iswalnum, fish_wcstoi, write_to_fd};
 use fish::{
     flog::{self, activate_flog_categories_by_pattern, set_flog_file_fd},
     future_feature_flags,
@@ -733,6 +745,122 @@ fn char_is_escaped(text: &wstr, idx: usize) -> bool {
     count_preceding_backslashes(text, idx) % 2 == 1
 }
 
+/// Return the number of columns taken by `text`.
+fn display_width(text: &wstr) -> usize {
+    text.chars()
+        .map(|c| usize::try_from(fish_wcwidth(c)).unwrap_or(0))
+        .sum()
+}
+
+/// Insert a line break into each line of the prettified `src` that is wider than `max_width`
+/// columns and can be broken. Return the new source, which needs to be prettified again, or None
+/// if no line can be broken.
+///
+/// We prefer to break a line at its loosest binding: first between the jobs of "and" and "or"
+/// chains, then after the pipes of a pipeline, and only then between arguments, using escaped
+/// newlines. Arguments fill each line as far as they fit.
+fn break_long_lines(src: &wstr, max_width: usize) -> Option<WString> {
+    let ast = Ast::parse(src, parse_flags(), None);
+    // Don't make a mess of broken code.
+    if ast.errored() {
+        return None;
+    }
+
+    let mut all_edits = vec![];
+    let mut line_start = 0;
+    for line in src.split('\n') {
+        let line_end = line_start + line.len();
+        let this_line_start = line_start;
+        line_start = line_end + 1;
+        if display_width(line) <= max_width {
+            continue;
+        }
+        let content_start = this_line_start + line.chars().take_while(|&c| c == ' ').count();
+        let in_line = |pos: usize| (content_start..line_end).contains(&pos);
+        let column = |pos: usize| display_width(&src[this_line_start..pos]);
+
+        // Text to insert or replace, in source order.
+        let mut conjunction_breaks = vec![];
+        let mut pipe_breaks = vec![];
+        let mut items = vec![];
+        for node in Traversal::new(ast.top()) {
+            if let Some(conj) = node.as_job_conjunction() {
+                // Put "; and cmd" on its own line.
+                let Some(decorator) = conj.decorator.as_ref() else {
+                    continue;
+                };
+                let decorator_start = decorator.source_range().start();
+                if !in_line(decorator_start) {
+                    continue;
+                }
+                let mut semi = decorator_start;
+                while semi > content_start && src.as_char_slice()[semi - 1] == ' ' {
+                    semi -= 1;
+                }
+                if semi > content_start && src.as_char_slice()[semi - 1] == ';' {
+                    conjunction_breaks.push((semi - 1..decorator_start, L!("\n")));
+                }
+            } else if let Some(cont) = node.as_job_conjunction_continuation() {
+                // Break after "&&" and "||" if the next job is on the same line.
+                let end = cont.conjunction.source_range().end();
+                if in_line(end - 1)
+                    && cont
+                        .job
+                        .try_source_range()
+                        .is_some_and(|r| r.start() < line_end)
+                {
+                    conjunction_breaks.push((end..end, L!("\n")));
+                }
+            } else if let Some(cont) = node.as_job_continuation() {
+                let end = cont.pipe.source_range().end();
+                if in_line(end - 1)
+                    && cont
+                        .statement
+                        .try_source_range()
+                        .is_some_and(|r| r.start() < line_end)
+                {
+                    pipe_breaks.push((end..end, L!("\n")));
+                }
+            } else if matches!(node.typ(), Type::argument | Type::redirection) {
+                let range = node.source_range();
+                // Never break before the first token of a line.
+                if range.start() > content_start && in_line(range.start()) {
+                    items.push(range);
+                }
+            }
+        }
+
+        let edits = if !conjunction_breaks.is_empty() {
+            conjunction_breaks
+        } else if !pipe_breaks.is_empty() {
+            pipe_breaks
+        } else {
+            items.sort_by_key(|r| r.start());
+            // Break before the first argument that does not fit, leaving room for " \".
+            let last = items.len().saturating_sub(1);
+            let overflow = items.iter().enumerate().find(|&(i, r)| {
+                let end = column(r.end().min(line_end));
+                end > max_width || (i != last && end + 2 > max_width)
+            });
+            let Some((_, r)) = overflow else {
+                continue;
+            };
+            vec![(r.start()..r.start(), L!("\\\n"))]
+        };
+        all_edits.extend(edits);
+    }
+    if all_edits.is_empty() {
+        return None;
+    }
+
+    let mut wrapped = src.to_owned();
+    all_edits.sort_by_key(|(range, _)| range.start);
+    for (range, text) in all_edits.into_iter().rev() {
+        wrapped.replace_range(range, text);
+    }
+    Some(wrapped)
+}
+
 fn main() {
     PROGRAM_NAME.set(L!("fish_indent")).unwrap();
     panic_handler(throwing_main)
@@ -766,7 +894,9 @@ fn throwing_main() -> i32 {
         File,
         Ansi,
         PygmentsCsv,
+        AstJson,
         Check,
+        Diff,
         Html,
     }
 
@@ -775,6 +905,12 @@ fn throwing_main() -> i32 {
     let mut do_indent = true;
     let mut only_indent = false;
     let mut only_unindent = false;
+    // The maximum line width from the command line, which overrides the config file.
+    let mut max_width_arg = None;
+    // Whether to look for a config file.
+    let mut use_config = true;
+    // The features whose deprecated syntax to rewrite.
+    let mut fixes = vec![];
     // File path for debug output.
     let mut debug_output = None;
 
@@ -784,6 +920,7 @@ fn throwing_main() -> i32 {
         wopt(L!("debug-output"), ArgType::RequiredArgument, 'o'),
         wopt(L!("debug-stack-frames"), ArgType::RequiredArgument, 'D'),
         wopt(L!("dump-parse-tree"), ArgType::NoArgument, 'P'),
+        wopt(L!("dump-ast"), ArgType::RequiredArgument, '\x08'),
         wopt(L!("no-indent"), ArgType::NoArgument, 'i'),
         wopt(L!("only-indent"), ArgType::NoArgument, '\x04'),
         wopt(L!("only-unindent"), ArgType::NoArgument, '\x05'),
@@ -794,6 +931,10 @@ fn throwing_main() -> i32 {
         wopt(L!("ansi"), ArgType::NoArgument, '\x02'),
         wopt(L!("pygments"), ArgType::NoArgument, '\x03'),
         wopt(L!("check"), ArgType::NoArgument, 'c'),
+        wopt(L!("diff"), ArgType::NoArgument, '\x07'),
+        wopt(L!("max-width"), ArgType::RequiredArgument, '\x06'),
+        wopt(L!("no-config"), ArgType::NoArgument, '\x0a'),
+        wopt(L!("fix"), ArgType::RequiredArgument, '\x09'),
     ];
 
     let args: Vec<WString> = std::env::args_os()
@@ -805,6 +946,17 @@ fn throwing_main() -> i32 {
     while let Some(c) = w.next_opt() {
         match c {
             'P' => DUMP_PARSE_TREE.store(true),
+            '\x08' => {
+                let format = w.woptarg.unwrap();
+                if format != "json" {
+                    eprintf!(
+                        "%s",
+                        wgettext_fmt!("Invalid AST format '%s', expected 'json'\n", format)
+                    );
+                    return STATUS_CMD_ERROR.unwrap();
+                }
+                output_type = OutputType::AstJson;
+            }
             'h' => {
                 print_help("fish_indent");
                 return STATUS_CMD_OK.unwrap();
@@ -828,6 +980,23 @@ fn throwing_main() -> i32 {
             '\x02' => output_type = OutputType::Ansi,
             '\x03' => output_type = OutputType::PygmentsCsv,
             'c' => output_type = OutputType::Check,
+            '\x07' => output_type = OutputType::Diff,
+            '\x06' => {
+                let arg = w.woptarg.unwrap();
+                let Some(width) = parse_max_width(arg) else {
+                    eprintf!("%s", wgettext_fmt!("Invalid maximum width: %s\n", arg));
+                    return STATUS_CMD_ERROR.unwrap();
+                };
+                max_width_arg = Some(width);
+            }
+            '\x0a' => use_config = false,
+            '\x09' => match parse_fixes(w.woptarg.unwrap()) {
+                Ok(flags) => fixes = flags,
+                Err(err) => {
+                    eprintf!("%s", err);
+                    return STATUS_CMD_ERROR.unwrap();
+                }
+            },
             'd' => {
                 activate_flog_categories_by_pattern(w.woptarg.unwrap());
                 for cat in flog::categories::all_categories() {
@@ -914,20 +1083,61 @@ fn throwing_main() -> i32 {
             continue;
         }
 
+        if output_type == OutputType::AstJson {
+            let output = make_ast_json(args.get(i).copied(), &src);
+            let _ = write_to_fd(&wcs2string(&output), STDOUT_FILENO);
+            i += 1;
+            continue;
+        }
+
+        // Look for a config file next to the file, or in the working directory for stdin.
+        let max_width = match max_width_arg {
+            Some(width) => Some(width),
+            None if !use_config => None,
+            None => {
+                let config_dir = if args.is_empty() {
+                    std::env::current_dir().ok()
+                } else {
+                    std::fs::canonicalize(OsStr::from_bytes(&wcs2string(output_location)))
+                        .ok()
+                        .and_then(|path| path.parent().map(Path::to_owned))
+                };
+                match config_dir.map_or(Ok(Config::default()), |dir| find_config(&dir)) {
+                    Ok(config) => config.max_width,
+                    Err(err) => {
+                        eprintf!("%s", err);
+                        return STATUS_CMD_ERROR.unwrap();
+                    }
+                }
+            }
+        }
+        .filter(|&width| width != 0);
+
+        let name = args.get(i).copied().unwrap_or(L!("-"));
+        let input = fix_deprecated_syntax(&src, &fixes, name).unwrap_or_else(|| {
+            eprintf!(
+                "%s",
+                wgettext_fmt!(
+                    "%s: not fixing deprecated syntax because of syntax errors\n",
+                    name
+                )
+            );
+            src.clone()
+        });
         let output_wtext = if only_indent || only_unindent {
-            let indents = parse_util_compute_indents(&src);
+            let indents = parse_util_compute_indents(&input);
             if only_indent {
-                apply_indents(&src, &indents)
+                apply_indents(&input, &indents)
             } else {
                 // Only unindent.
                 let mut indented_everywhere = true;
-                for (i, c) in src.chars().enumerate() {
-                    if c != '\n' || i + 1 == src.len() {
+                for (i, c) in input.chars().enumerate() {
+                    if c != '\n' || i + 1 == input.len() {
                         continue;
                     }
                     let num_spaces = SPACES_PER_INDENT * usize::try_from(indents[i + 1]).unwrap();
-                    if src.len() < i + 1 + num_spaces
-                        || !src[i + 1..].chars().take(num_spaces).all(|c| c == ' ')
+                    if input.len() < i + 1 + num_spaces
+                        || !input[i + 1..].chars().take(num_spaces).all(|c| c == ' ')
                     {
                         indented_everywhere = false;
                         break;
@@ -936,22 +1146,22 @@ fn throwing_main() -> i32 {
                 if indented_everywhere {
                     let mut out = WString::new();
                     let mut i = 0;
-                    while i < src.len() {
-                        let c = src.as_char_slice()[i];
+                    while i < input.len() {
+                        let c = input.as_char_slice()[i];
                         out.push(c);
                         i += 1;
-                        if c != '\n' || i == src.len() {
+                        if c != '\n' || i == input.len() {
                             continue;
                         }
                         i += SPACES_PER_INDENT * usize::try_from(indents[i]).unwrap();
                     }
                     out
                 } else {
-                    src.clone()
+                    input
                 }
             }
         } else {
-            prettify(&src, do_indent)
+            prettify(&input, do_indent, max_width)
         };
 
         // Maybe colorize.
@@ -995,7 +1205,7 @@ fn throwing_main() -> i32 {
             OutputType::Html => {
                 colored_output = html_colorize(&output_wtext, &colors);
             }
-            OutputType::PygmentsCsv => {
+            OutputType::PygmentsCsv | OutputType::AstJson => {
                 unreachable!()
             }
             OutputType::Check => {
@@ -1006,16 +1216,93 @@ fn throwing_main() -> i32 {
                     retval += 1;
                 }
             }
+            OutputType::Diff => {
+                if output_wtext != src {
+                    let name = args.get(i).copied().unwrap_or(L!("-"));
+                    colored_output = wcs2string(&unified_diff(name, &src, &output_wtext));
+                    retval += 1;
+                }
+            }
         }
 
         let _ = write_to_fd(&colored_output, STDOUT_FILENO);
         i += 1;
     }
+
+    // Sum up, unless the diff says it all. This goes to stderr, so the diffs can be applied.
+    if output_type == OutputType::Diff && args.len() > 1 {
+        eprintf!(
+            "%s",
+            wgettext_fmt!("%d of %d files would be reformatted\n", retval, args.len())
+        );
+    }
     retval
 }
 
 static DUMP_PARSE_TREE: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
 
+/// The file with the settings for a project. We look for it in the directory of the file we
+/// format, or the working directory when reading standard input, and then in their parents.
+const CONFIG_FILE_NAME: &str = ".fish_indent";
+
+/// The settings from a config file.
+#[derive(Default)]
+struct Config {
+    /// The maximum line width, where 0 means no maximum.
+    max_width: Option<usize>,
+}
+
+/// Find the config file in `dir` or its closest parent and read it. Return an error message if
+/// it cannot be read or has an invalid setting. Unknown settings are ignored with a warning, so
+/// files written for newer versions still work.
+fn find_config(dir: &Path) -> Result<Config, WString> {
+    let mut config = Config::default();
+    let Some(path) = dir
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+    else {
+        return Ok(config);
+    };
+    let path_str = str2wcstring(path.as_os_str().as_bytes());
+    let contents = std::fs::read(&path)
+        .map_err(|err| wgettext_fmt!("Opening \"%s\" failed: %s\n", path_str, err.to_string()))?;
+    // Each line is a "key = value" setting, or a comment.
+    for (i, line) in str2wcstring(&contents).split('\n').enumerate() {
+        let line = line.split('#').next().unwrap();
+        let invalid = || wgettext_fmt!("%s:%d: invalid setting '%s'\n", &path_str, i + 1, line);
+        if line.chars().all(|c| c.is_whitespace()) {
+            continue;
+        }
+        let Some(eq) = line.find_char('=') else {
+            return Err(invalid());
+        };
+        let key = trim(line[..eq].to_owned(), None);
+        let value = trim(line[eq + 1..].to_owned(), None);
+        if key == L!("max-width") {
+            config.max_width = Some(parse_max_width(&value).ok_or_else(invalid)?);
+        } else {
+            eprintf!(
+                "%s",
+                wgettext_fmt!(
+                    "%s:%d: warning: ignoring unknown setting '%s'\n",
+                    &path_str,
+                    i + 1,
+                    key
+                )
+            );
+        }
+    }
+    Ok(config)
+}
+
+/// Parse a maximum line width, which may be 0 for no maximum.
+fn parse_max_width(s: &wstr) -> Option<usize> {
+    fish_wcstoi(s)
+        .ok()
+        .and_then(|width| usize::try_from(width).ok())
+}
+
 // Read the entire contents of a file into the specified string.
 fn read_file(mut f: impl Read) -> Result<WString, ()> {
     let mut buf = vec![];
@@ -1040,6 +1327,12 @@ fn highlight_role_to_string(role: HighlightRole) -> &'static wstr {
         HighlightRole::redirection => L!("redirection"),
         HighlightRole::autosuggestion => L!("autosuggestion"),
         HighlightRole::selection => L!("selection"),
+        HighlightRole::paste => L!("paste"),
+        HighlightRole::builtin => L!("builtin"),
+        HighlightRole::function => L!("function"),
+        HighlightRole::abbreviation => L!("abbreviation"),
+        HighlightRole::unset_variable => L!("unset_variable"),
+        HighlightRole::matching_pair => L!("matching_pair"),
         HighlightRole::pager_progress => L!("pager_progress"),
         HighlightRole::pager_background => L!("pager_background"),
         HighlightRole::pager_prefix => L!("pager_prefix"),
@@ -1108,8 +1401,208 @@ fn make_pygments_csv(src: &wstr) -> Vec<u8> {
     result.into_bytes()
 }
 
+/// Return a JSON string literal for `s`.
+fn json_string(s: &wstr) -> WString {
+    escape_string(s, EscapeStringStyle::Json)
+}
+
+/// Return the name of a token type in the JSON output.
+fn token_type_json_name(token_type: ParseTokenType) -> &'static wstr {
+    match token_type {
+        ParseTokenType::invalid => L!("invalid"),
+        ParseTokenType::string => L!("string"),
+        ParseTokenType::pipe => L!("pipe"),
+        ParseTokenType::redirection => L!("redirection"),
+        ParseTokenType::background => L!("background"),
+        ParseTokenType::andand => L!("andand"),
+        ParseTokenType::oror => L!("oror"),
+        ParseTokenType::end => L!("end"),
+        ParseTokenType::terminate => L!("terminate"),
+        ParseTokenType::error => L!("error"),
+        ParseTokenType::tokenizer_error => L!("tokenizer_error"),
+        ParseTokenType::comment => L!("comment"),
+    }
+}
+
+/// Return the name of a syntax error code in the JSON output.
+fn error_code_json_name(code: ParseErrorCode) -> &'static wstr {
+    match code {
+        ParseErrorCode::none => L!("none"),
+        ParseErrorCode::syntax => L!("syntax"),
+        ParseErrorCode::cmdsubst => L!("cmdsubst"),
+        ParseErrorCode::generic => L!("generic"),
+        ParseErrorCode::tokenizer_unterminated_quote => L!("tokenizer_unterminated_quote"),
+        ParseErrorCode::tokenizer_unterminated_subshell => L!("tokenizer_unterminated_subshell"),
+        ParseErrorCode::tokenizer_unterminated_slice => L!("tokenizer_unterminated_slice"),
+        ParseErrorCode::tokenizer_unterminated_escape => L!("tokenizer_unterminated_escape"),
+        ParseErrorCode::tokenizer_other => L!("tokenizer_other"),
+        ParseErrorCode::unbalancing_end => L!("unbalancing_end"),
+        ParseErrorCode::unbalancing_else => L!("unbalancing_else"),
+        ParseErrorCode::unbalancing_case => L!("unbalancing_case"),
+        ParseErrorCode::bare_variable_assignment => L!("bare_variable_assignment"),
+        ParseErrorCode::andor_in_pipeline => L!("andor_in_pipeline"),
+    }
+}
+
+/// Converts source offsets to lines and columns, which both start at 1. Columns count characters.
+struct LineIndex {
+    /// The offset where each line starts.
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(src: &wstr) -> Self {
+        let newlines = src
+            .chars()
+            .enumerate()
+            .filter(|&(_, c)| c == '\n')
+            .map(|(i, _)| i + 1);
+        Self {
+            line_starts: std::iter::once(0).chain(newlines).collect(),
+        }
+    }
+
+    /// Return a JSON object for the position at `offset`.
+    fn position_json(&self, offset: usize) -> WString {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let column = offset - self.line_starts[line - 1] + 1;
+        sprintf!(
+            "{\"offset\":%d,\"line\":%d,\"column\":%d}",
+            offset,
+            line,
+            column
+        )
+    }
+
+    /// Append the "start" and "end" members of a JSON object for `range`, which are null for a
+    /// node without source. The end is exclusive.
+    fn append_range_json(&self, out: &mut WString, range: Option<SourceRange>) {
+        match range {
+            Some(range) => out.push_utfstr(&sprintf!(
+                "\"start\":%ls,\"end\":%ls",
+                self.position_json(range.start()),
+                self.position_json(range.end())
+            )),
+            None => out.push_str("\"start\":null,\"end\":null"),
+        }
+    }
+}
+
+/// Collects the children of a node.
+struct ChildCollector<'a> {
+    children: Vec<&'a dyn Node>,
+}
+
+impl<'a> NodeVisitor<'a> for ChildCollector<'a> {
+    fn visit(&mut self, node: &'a dyn Node) {
+        self.children.push(node);
+    }
+}
+
+/// Append `node` and its children to `out` as a JSON object.
+fn append_node_json(out: &mut WString, node: &dyn Node, src: &wstr, lines: &LineIndex) {
+    let category = match node.category() {
+        Category::branch => L!("branch"),
+        Category::leaf => L!("leaf"),
+        Category::list => L!("list"),
+    };
+    out.push_utfstr(&sprintf!(
+        "{\"type\":\"%ls\",\"category\":\"%ls\",",
+        ast_type_to_string(node.typ()),
+        category
+    ));
+    let range = node.try_source_range();
+    lines.append_range_json(out, range);
+    if let Some(keyword) = node.as_keyword() {
+        out.push_utfstr(&sprintf!(
+            ",\"keyword\":%ls",
+            json_string(keyword.keyword().to_wstr())
+        ));
+    } else if let Some(token) = node.as_token() {
+        out.push_utfstr(&sprintf!(
+            ",\"token\":\"%ls\"",
+            token_type_json_name(token.token_type())
+        ));
+    }
+    if node.category() == Category::leaf {
+        if let Some(range) = range {
+            out.push_utfstr(&sprintf!(
+                ",\"text\":%ls",
+                json_string(&src[range.start()..range.end()])
+            ));
+        }
+    }
+
+    let mut collector = ChildCollector { children: vec![] };
+    node.accept(&mut collector, false);
+    out.push_str(",\"children\":[");
+    for (i, child) in collector.children.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        append_node_json(out, child, src, lines);
+    }
+    out.push_str("]}");
+}
+
+// Entry point for --dump-ast=json.
+// Our output is one line per file, with a JSON object with these members:
+// "file": the file name, or null for standard input.
+// "ast": the nodes of the abstract syntax tree, nested through their "children".
+// "comments": the source ranges of comments, which are not part of the tree.
+// "errors": the syntax errors, each with its "code", "message" and source range.
+// Source positions have an "offset", "line" and "column"; the latter two start at 1.
+fn make_ast_json(name: Option<&wstr>, src: &wstr) -> WString {
+    // Unlike for formatting, we want to report unterminated quotes and the like.
+    let mut errors = vec![];
+    let ast = Ast::parse(
+        src,
+        ParseTreeFlags::CONTINUE_AFTER_ERROR | ParseTreeFlags::INCLUDE_COMMENTS,
+        Some(&mut errors),
+    );
+    let lines = LineIndex::new(src);
+
+    let mut out = WString::from_str("{\"file\":");
+    match name {
+        Some(name) => out.push_utfstr(&json_string(name)),
+        None => out.push_str("null"),
+    }
+    out.push_str(",\"ast\":");
+    append_node_json(&mut out, ast.top(), src, &lines);
+
+    out.push_str(",\"comments\":[");
+    for (i, comment) in ast.extras.comments.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('{');
+        lines.append_range_json(&mut out, Some(*comment));
+        out.push('}');
+    }
+
+    out.push_str("],\"errors\":[");
+    for (i, error) in errors.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        // Errors at the end of the source may extend past it.
+        let start = error.source_start.min(src.len());
+        let length = error.source_length.min(src.len() - start);
+        out.push_utfstr(&sprintf!(
+            "{\"code\":\"%ls\",\"message\":%ls,",
+            error_code_json_name(error.code),
+            json_string(&error.text)
+        ));
+        lines.append_range_json(&mut out, Some(SourceRange::new(start, length)));
+        out.push('}');
+    }
+    out.push_str("]}\n");
+    out
+}
+
 // Entry point for prettification.
-fn prettify(src: &wstr, do_indent: bool) -> WString {
+// If `max_width` is given, lines wider than that are broken where possible.
+fn prettify(src: &wstr, do_indent: bool, max_width: Option<usize>) -> WString {
     if DUMP_PARSE_TREE.load() {
         let ast = Ast::parse(
             src,
@@ -1122,7 +1615,668 @@ fn prettify(src: &wstr, do_indent: bool) -> WString {
         eprintf!("%s\n", ast_dump);
     }
     let mut printer = PrettyPrinter::new(src, do_indent);
-    printer.prettify()
+    let mut output = printer.prettify();
+    let Some(max_width) = max_width else {
+        return output;
+    };
+    // Each pass breaks every long line once, so the number of passes depends on how often a
+    // single line needs breaking rather than on the length of the file.
+    while let Some(wrapped) = break_long_lines(&output, max_width) {
+        let mut printer = PrettyPrinter::new(&wrapped, do_indent);
+        let rewrapped = printer.prettify();
+        // If prettifying undid all our line breaks, the remaining lines cannot be broken.
+        if rewrapped == output {
+            break;
+        }
+        output = rewrapped;
+    }
+    output
+}
+
+/// A replacement for part of the source.
+type Edit = (Range<usize>, WString);
+
+/// Parse the comma-separated list of features given to --fix, where "all" means every feature
+/// that is not enabled yet. Fixing syntax for an enabled feature would change what a script does,
+/// so we warn when such a feature is named.
+/// The result is in the order of the features, so fixes are applied in a fixed order.
+fn parse_fixes(arg: &wstr) -> Result<Vec<FeatureFlag>, WString> {
+    let mut selected = [false; METADATA.len()];
+    for name in arg.split(',') {
+        if name.is_empty() {
+            continue;
+        }
+        if name == "all" {
+            for (idx, md) in METADATA.iter().enumerate() {
+                selected[idx] |= !future_feature_flags::test(md.flag);
+            }
+            continue;
+        }
+        let Some(idx) = METADATA.iter().position(|md| md.name == name) else {
+            return Err(wgettext_fmt!("Unknown feature '%s'\n", name));
+        };
+        if future_feature_flags::test(METADATA[idx].flag) {
+            eprintf!(
+                "%s",
+                wgettext_fmt!(
+                    "Warning: feature '%s' is already enabled, fixing its syntax may change what scripts do\n",
+                    name
+                )
+            );
+        }
+        selected[idx] = true;
+    }
+    Ok(METADATA
+        .iter()
+        .zip(selected)
+        .filter(|&(_, enabled)| enabled)
+        .map(|(md, _)| md.flag)
+        .collect())
+}
+
+/// Rewrite `src` so that it does the same with each of the given features enabled as it did
+/// without them. Each fix parses the script again, so it sees the changes of the previous one.
+/// Return none if the script has syntax errors, as we can't tell what it means. Syntax that may or
+/// may not need fixing is left alone and reported on stderr, for the file called `name`.
+fn fix_deprecated_syntax(src: &wstr, fixes: &[FeatureFlag], name: &wstr) -> Option<WString> {
+    if fixes.is_empty() {
+        return Some(src.to_owned());
+    }
+    if Ast::parse(src, parse_flags(), None).errored() {
+        return None;
+    }
+    let mut text = src.to_owned();
+    for &flag in fixes {
+        let fix: fn(&wstr) -> Vec<Edit> = match flag {
+            FeatureFlag::stderr_nocaret => fix_stderr_caret,
+            FeatureFlag::qmark_noglob => fix_qmark_glob,
+            FeatureFlag::string_replace_backslash => fix_replace_backslash,
+            FeatureFlag::ampersand_nobg_in_token => fix_ampersand_in_token,
+            FeatureFlag::remove_percent_self => fix_percent_self,
+            FeatureFlag::test_require_arg => fix_test_arguments,
+        };
+        if matches!(flag, FeatureFlag::stderr_nocaret) {
+            for (range, arg) in fix_recursively(&text, ambiguous_stderr_carets) {
+                let line = text[..range.start].chars().filter(|&c| c == '\n').count() + 1;
+                eprintf!(
+                    "%s",
+                    wgettext_fmt!(
+                        "%s:%d: warning: not fixing '%s', use '2>' instead if it redirects stderr\n",
+                        name,
+                        line,
+                        arg
+                    )
+                );
+            }
+        }
+        text = apply_edits(&text, fix_recursively(&text, fix));
+    }
+    Some(text)
+}
+
+/// Return the edits `fix` makes to `src`, and to the commands of its command substitutions.
+fn fix_recursively(src: &wstr, fix: fn(&wstr) -> Vec<Edit>) -> Vec<Edit> {
+    let mut edits = fix(src);
+    let mut tokenizer = Tokenizer::new(src, TOK_SHOW_COMMENTS);
+    while let Some(tok) = tokenizer.next() {
+        if tok.type_ != TokenType::string {
+            continue;
+        }
+        for cmdsub in command_substitutions(tok.get_source(src)) {
+            let command = cmdsub.command();
+            let offset = tok.offset() + command.start;
+            let inner = fix_recursively(&src[offset..tok.offset() + command.end], fix);
+            edits.extend(
+                inner
+                    .into_iter()
+                    .map(|(range, text)| (range.start + offset..range.end + offset, text)),
+            );
+        }
+    }
+    edits
+}
+
+/// Return the command substitutions in the token `text`, including those in double quotes.
+fn command_substitutions(text: &wstr) -> Vec<Parentheses> {
+    let mut cmdsubs = vec![];
+    let mut cursor = 0;
+    let mut is_quoted = false;
+    while let MaybeParentheses::CommandSubstitution(parens) =
+        parse_util_locate_cmdsubst_range(text, &mut cursor, false, Some(&mut is_quoted), None)
+    {
+        cmdsubs.push(parens);
+    }
+    cmdsubs
+}
+
+/// Apply non-overlapping edits to `src`.
+fn apply_edits(src: &wstr, mut edits: Vec<Edit>) -> WString {
+    let mut out = src.to_owned();
+    edits.sort_by_key(|(range, _)| range.start);
+    for (range, text) in edits.into_iter().rev() {
+        out.replace_range(range, text.as_utfstr());
+    }
+    out
+}
+
+fn range_text(src: &wstr, range: SourceRange) -> &wstr {
+    &src[range.start()..range.end()]
+}
+
+/// Return the source ranges of all arguments in `src`, or none if it does not parse.
+fn argument_ranges(src: &wstr) -> Vec<SourceRange> {
+    let ast = Ast::parse(src, parse_flags(), None);
+    if ast.errored() {
+        return vec![];
+    }
+    Traversal::new(ast.top())
+        .filter(|node| node.typ() == Type::argument)
+        .map(|node| node.source_range())
+        .collect()
+}
+
+/// Return the source ranges of the command and its arguments for each plain command in `src`, or
+/// none if it does not parse.
+fn commands_with_arguments(src: &wstr) -> Vec<(SourceRange, Vec<SourceRange>)> {
+    let ast = Ast::parse(src, parse_flags(), None);
+    if ast.errored() {
+        return vec![];
+    }
+    let mut commands = vec![];
+    for node in Traversal::new(ast.top()) {
+        let Some(stmt) = node.as_decorated_statement() else {
+            continue;
+        };
+        let mut args = vec![];
+        for v in &stmt.args_or_redirs {
+            if v.is_argument() {
+                args.push(v.argument().source_range());
+            }
+        }
+        commands.push((stmt.command.source_range(), args));
+    }
+    commands
+}
+
+/// Commands that commonly take regular expressions, where a leading "^" is an anchor.
+const REGEX_COMMANDS: &[&str] = &["awk", "egrep", "grep", "rg", "sed", "string"];
+
+/// Return the argument `text` of `command` with a leading "^" if it can only have been a redirection
+/// of stderr, along with how many characters to replace with which redirection. Anything else
+/// might just as well be an argument like a regex, which it is with the feature enabled.
+fn stderr_caret_redirection(command: &wstr, text: &wstr) -> Option<(usize, &'static wstr)> {
+    let is_regex_command = REGEX_COMMANDS.iter().any(|&name| command == name);
+    if text.starts_with(L!("^^")) {
+        // A lone "^^" had no target.
+        return (text.len() > 2 && !is_regex_command).then_some((2, L!("2>>")));
+    }
+    let target = text.strip_prefix(L!("^"))?;
+    let is_fd = |target: &wstr| {
+        target.len() == 2
+            && target.char_at(0) == '&'
+            && (target.char_at(1).is_ascii_digit() || target.char_at(1) == '-')
+    };
+    (target == "/dev/null" || is_fd(target)).then_some((1, L!("2>")))
+}
+
+/// "^" used to redirect stderr, use "2>" instead. Only the unambiguous cases are rewritten, see
+/// [`ambiguous_stderr_carets`] for the rest.
+fn fix_stderr_caret(src: &wstr) -> Vec<Edit> {
+    let mut edits = vec![];
+    for (command, args) in commands_with_arguments(src) {
+        let command = range_text(src, command);
+        for arg in args {
+            if let Some((caret_len, redirection)) =
+                stderr_caret_redirection(command, range_text(src, arg))
+            {
+                edits.push((arg.start()..arg.start() + caret_len, redirection.to_owned()));
+            }
+        }
+    }
+    edits
+}
+
+/// Return the arguments starting with "^" that may or may not have been meant to redirect stderr,
+/// so the user can check them. Each is returned as an edit that leaves it alone.
+fn ambiguous_stderr_carets(src: &wstr) -> Vec<Edit> {
+    let mut carets = vec![];
+    for (command, args) in commands_with_arguments(src) {
+        let command = range_text(src, command);
+        if REGEX_COMMANDS.iter().any(|&name| command == name) {
+            continue;
+        }
+        for arg in args {
+            let text = range_text(src, arg);
+            if text.starts_with(L!("^"))
+                && text != "^"
+                && text != "^^"
+                && stderr_caret_redirection(command, text).is_none()
+            {
+                carets.push((arg.start()..arg.end(), text.to_owned()));
+            }
+        }
+    }
+    carets
+}
+
+/// "?" used to be a wildcard, but mostly by accident. Escape unquoted question marks, so they are
+/// literal either way.
+fn fix_qmark_glob(src: &wstr) -> Vec<Edit> {
+    let mut edits = vec![];
+    for arg in argument_ranges(src) {
+        let text = range_text(src, arg);
+        // Command substitutions are fixed on their own, skip over them.
+        let cmdsubs = command_substitutions(text);
+        let mut quote = None;
+        let mut escaped = false;
+        let mut i = 0;
+        while i < text.len() {
+            if let Some(cmdsub) = cmdsubs.iter().find(|cmdsub| cmdsub.start() == i) {
+                i = cmdsub.end();
+                continue;
+            }
+            let c = text.char_at(i);
+            i += 1;
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match c {
+                '\\' => escaped = true,
+                '\'' | '"' if quote.is_none() => quote = Some(c),
+                _ if quote == Some(c) => quote = None,
+                '?' if quote.is_none() => {
+                    let pos = arg.start() + i - 1;
+                    edits.push((pos..pos, L!("\\").to_owned()));
+                }
+                _ => (),
+            }
+        }
+    }
+    edits
+}
+
+/// "string replace -r" used to unescape its replacement a second time. Do that here instead.
+fn fix_replace_backslash(src: &wstr) -> Vec<Edit> {
+    let mut edits = vec![];
+    for (command, args) in commands_with_arguments(src) {
+        if range_text(src, command) != "string"
+            || args.first().map(|&arg| range_text(src, arg)) != Some(L!("replace"))
+        {
+            continue;
+        }
+        // Options may come anywhere before "--", like the builtin allows.
+        let mut regex = false;
+        let mut positionals = vec![];
+        let mut options_done = false;
+        for &arg in &args[1..] {
+            let text = range_text(src, arg);
+            if options_done || text.len() < 2 || !text.starts_with(L!("-")) {
+                positionals.push(arg);
+            } else if text == "--" {
+                options_done = true;
+            } else if text.starts_with(L!("--")) {
+                regex |= L!("regex").starts_with(text.slice_from(2));
+            } else {
+                regex |= text.chars().any(|c| c == 'r');
+            }
+        }
+        if !regex {
+            continue;
+        }
+        // The replacement follows the pattern.
+        let Some(&replacement) = positionals.get(1) else {
+            continue;
+        };
+        if let Some(fixed) = unescape_replacement(range_text(src, replacement)) {
+            edits.push((replacement.start()..replacement.end(), fixed));
+        }
+    }
+    edits
+}
+
+/// Return the replacement argument `text` of "string replace -r" with the second round of
+/// unescaping applied, or none if that does not change it or depends on expansions.
+fn unescape_replacement(text: &wstr) -> Option<WString> {
+    let mut value = unescape_string(text, UnescapeStringStyle::Script(UnescapeFlags::SPECIAL))?;
+    value.retain(|c| c != INTERNAL_SEPARATOR);
+    if value
+        .chars()
+        .any(|c| (RESERVED_CHAR_BASE..RESERVED_CHAR_END).contains(&c))
+    {
+        return None;
+    }
+    let mut result = WString::with_capacity(value.len());
+    let mut cursor: &wstr = &value;
+    while !cursor.is_empty() {
+        if cursor.char_at(0) == '\\' {
+            let escape_len = read_unquoted_escape(cursor, &mut result, true, false)?;
+            cursor = cursor.slice_from(escape_len);
+        } else {
+            result.push(cursor.char_at(0));
+            cursor = cursor.slice_from(1);
+        }
+    }
+    if result == value {
+        return None;
+    }
+    Some(escape_string(&result, EscapeStringStyle::default()))
+}
+
+/// "&" used to background a job even if a word followed. Add a space after it in that case.
+fn fix_ampersand_in_token(src: &wstr) -> Vec<Edit> {
+    // Tokenize the way we used to.
+    let mut edits = vec![];
+    let tokenizer = Tokenizer::new(src, TOK_SHOW_COMMENTS | TOK_AMPERSAND_ENDS_TOKEN);
+    for tok in tokenizer {
+        if tok.type_ != TokenType::background {
+            continue;
+        }
+        // With the feature, a string character after the "&" would pull it into the token.
+        let end = tok.end();
+        if src
+            .as_char_slice()
+            .get(end)
+            .is_some_and(|&c| tok_is_string_character(c, None))
+        {
+            edits.push((end..end, L!(" ").to_owned()));
+        }
+    }
+    edits
+}
+
+/// "%self" used to expand to fish's PID, like $fish_pid does.
+fn fix_percent_self(src: &wstr) -> Vec<Edit> {
+    argument_ranges(src)
+        .into_iter()
+        .filter(|&arg| range_text(src, arg) == PROCESS_EXPAND_SELF_STR)
+        .map(|arg| (arg.start()..arg.end(), L!("$fish_pid").to_owned()))
+        .collect()
+}
+
+/// "test" used to check if a single argument is non-empty, which made "test -n $foo" true for an
+/// empty $foo. Spell out the "-n", and quote variables and command substitutions after "-n" and
+/// "-z" so they always make an argument.
+fn fix_test_arguments(src: &wstr) -> Vec<Edit> {
+    let mut edits = vec![];
+    for (command, mut args) in commands_with_arguments(src) {
+        let command = range_text(src, command);
+        if command == "[" {
+            if args.last().is_some_and(|&arg| range_text(src, arg) == "]") {
+                args.pop();
+            }
+        } else if command != "test" {
+            continue;
+        }
+
+        // Only insert text around the operand, the fixes in command substitutions go inside.
+        let mut quote_operand = |arg: SourceRange, prefix: &wstr| {
+            let (open, close) = test_operand_quotes(range_text(src, arg)).unwrap_or_default();
+            if !prefix.is_empty() || !open.is_empty() {
+                let mut text = prefix.to_owned();
+                text.push_utfstr(open);
+                edits.push((arg.start()..arg.start(), text));
+            }
+            if !close.is_empty() {
+                edits.push((arg.end()..arg.end(), close.to_owned()));
+            }
+        };
+        if args.len() == 1 {
+            quote_operand(args[0], L!("-n "));
+            continue;
+        }
+        for pair in args.windows(2) {
+            let op = range_text(src, pair[0]);
+            if op == "-n" || op == "-z" {
+                quote_operand(pair[1], L!(""));
+            }
+        }
+    }
+    edits
+}
+
+/// Return the quotes to put around the unquoted test operand `text`, if it is a variable expansion
+/// or a command substitution, which may expand to nothing.
+fn test_operand_quotes(text: &wstr) -> Option<(&'static wstr, &'static wstr)> {
+    let mut cursor = 0;
+    if let MaybeParentheses::CommandSubstitution(cmdsub) =
+        parse_util_locate_cmdsubst_range(text, &mut cursor, false, None, None)
+    {
+        if cmdsub.end() != text.len() {
+            return None;
+        }
+        return match cmdsub.start() {
+            // Command substitutions need a "$" in double quotes.
+            0 => Some((L!("\"$"), L!("\""))),
+            1 if text.starts_with(L!("$")) => Some((L!("\""), L!("\""))),
+            _ => None,
+        };
+    }
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_$[].:/@%+=,-".contains(c);
+    if !text.chars().any(|c| c == '$') || !text.chars().all(is_plain) {
+        return None;
+    }
+    Some((L!("\""), L!("\"")))
+}
+
+/// The number of unchanged lines shown around the changes in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum DiffOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Return the lines of `text`, each with its newline, if it has one.
+fn split_lines(text: &wstr) -> Vec<&wstr> {
+    let mut lines = vec![];
+    let mut start = 0;
+    for (i, c) in text.chars().enumerate() {
+        if c == '\n' {
+            lines.push(&text[start..i + 1]);
+            start = i + 1;
+        }
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+/// Return a shortest list of operations that turn the lines `old` into `new`, using the linear
+/// space variant of Myers' algorithm.
+fn diff_lines(old: &[&wstr], new: &[&wstr]) -> Vec<DiffOp> {
+    let mut ops = vec![];
+    append_diff_ops(old, new, &mut ops);
+    ops
+}
+
+/// Append the operations that turn `a` into `b` to `ops`.
+fn append_diff_ops(a: &[&wstr], b: &[&wstr], ops: &mut Vec<DiffOp>) {
+    // Most of a file is usually unchanged, so skip the common prefix and suffix first.
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    ops.extend(std::iter::repeat(DiffOp::Equal).take(prefix));
+    match diff_middle(a, b) {
+        Some((x, y)) => {
+            append_diff_ops(&a[..x], &b[..y], ops);
+            append_diff_ops(&a[x..], &b[y..], ops);
+        }
+        None => {
+            ops.extend(std::iter::repeat(DiffOp::Delete).take(a.len()));
+            ops.extend(std::iter::repeat(DiffOp::Insert).take(b.len()));
+        }
+    }
+    ops.extend(std::iter::repeat(DiffOp::Equal).take(suffix));
+}
+
+/// Search for a shortest edit path from `a` to `b` from both ends at once, and return a point in
+/// the middle where the searches meet, splitting the diff in two smaller ones. Return none if
+/// either side is empty, so the diff is trivial.
+///
+/// Only the furthest point on each diagonal is kept, so this takes linear space.
+fn diff_middle(a: &[&wstr], b: &[&wstr]) -> Option<(usize, usize)> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (n, m) = (
+        isize::try_from(a.len()).unwrap(),
+        isize::try_from(b.len()).unwrap(),
+    );
+    let max_d = (n + m + 1) / 2;
+    // forward[k] is the furthest x reached on diagonal k = x - y from the start, backward[k] the
+    // same from the end. They are offset to be valid indices, and -1 if not reached yet.
+    let offset = max_d + 1;
+    let idx = |k: isize| usize::try_from(k + offset).unwrap();
+    let pos = |x: isize| usize::try_from(x).unwrap();
+    let mut forward = vec![-1; idx(max_d + 1) + 1];
+    let mut backward = forward.clone();
+    forward[idx(1)] = 0;
+    backward[idx(1)] = 0;
+    // The diagonal of the end point. If its parity is odd, the searches meet going forward.
+    let delta = n - m;
+    let meet_forward = delta % 2 != 0;
+    // Diagonals that ran off the edit graph don't need to be searched any more.
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+    let reached = |v: &[isize], k: isize| {
+        usize::try_from(k + offset)
+            .ok()
+            .and_then(|i| v.get(i).copied())
+            .filter(|&x| x != -1)
+    };
+    // Whether the point x on the forward diagonal k is inside the graph and at or past the point
+    // x2 reached backward on the same diagonal.
+    let meets = |x: isize, k: isize, x2: isize| x <= n && (0..=m).contains(&(x - k)) && x >= n - x2;
+
+    for d in 0..max_d {
+        for k in (-d + k1_start..=d - k1_end).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[idx(k - 1)] < forward[idx(k + 1)]) {
+                forward[idx(k + 1)]
+            } else {
+                forward[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[pos(x)] == b[pos(y)] {
+                x += 1;
+                y += 1;
+            }
+            forward[idx(k)] = x;
+            if x > n {
+                k1_end += 2;
+            } else if y > m {
+                k1_start += 2;
+            } else if meet_forward {
+                if let Some(x2) = reached(&backward, delta - k) {
+                    if meets(x, k, x2) {
+                        return Some((pos(x), pos(y)));
+                    }
+                }
+            }
+        }
+
+        for k in (-d + k2_start..=d - k2_end).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[idx(k - 1)] < backward[idx(k + 1)]) {
+                backward[idx(k + 1)]
+            } else {
+                backward[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[pos(n - x - 1)] == b[pos(m - y - 1)] {
+                x += 1;
+                y += 1;
+            }
+            backward[idx(k)] = x;
+            if x > n {
+                k2_end += 2;
+            } else if y > m {
+                k2_start += 2;
+            } else if !meet_forward {
+                let k1 = delta - k;
+                if let Some(x1) = reached(&forward, k1) {
+                    if meets(x1, k1, x) {
+                        return Some((pos(x1), pos(x1 - k1)));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Return a unified diff from `old` to `new`, which are the original and prettified contents of
+/// the file called `name`, or the empty string if they are equal.
+fn unified_diff(name: &wstr, old: &wstr, new: &wstr) -> WString {
+    let (old, new) = (split_lines(old), split_lines(new));
+    let ops = diff_lines(&old, &new);
+
+    // Group the changes into hunks, merging those whose context would overlap.
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (i, op) in ops.iter().enumerate() {
+        if *op == DiffOp::Equal {
+            continue;
+        }
+        let start = i.saturating_sub(DIFF_CONTEXT);
+        let end = (i + 1 + DIFF_CONTEXT).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    if hunks.is_empty() {
+        return WString::new();
+    }
+
+    // Like diff, a range is "start,count", but a single line is just its number, and an empty
+    // range starts at the line before it.
+    let format_range = |start: usize, count: usize| match count {
+        0 => sprintf!("%d,0", start),
+        1 => sprintf!("%d", start + 1),
+        _ => sprintf!("%d,%d", start + 1, count),
+    };
+
+    let mut result = sprintf!("--- %ls\t(original)\n+++ %ls\t(formatted)\n", name, name);
+    // The line in the old and new file at the start of the current operation.
+    let (mut old_line, mut new_line) = (0, 0);
+    let mut op_idx = 0;
+    for (start, end) in hunks {
+        for op in &ops[op_idx..start] {
+            old_line += usize::from(*op != DiffOp::Insert);
+            new_line += usize::from(*op != DiffOp::Delete);
+        }
+        let hunk = &ops[start..end];
+        let old_count = hunk.iter().filter(|op| **op != DiffOp::Insert).count();
+        let new_count = hunk.iter().filter(|op| **op != DiffOp::Delete).count();
+        result.push_utfstr(&sprintf!(
+            "@@ -%ls +%ls @@\n",
+            format_range(old_line, old_count),
+            format_range(new_line, new_count)
+        ));
+        for op in hunk {
+            let (prefix, line) = match op {
+                DiffOp::Equal => (' ', old[old_line]),
+                DiffOp::Delete => ('-', old[old_line]),
+                DiffOp::Insert => ('+', new[new_line]),
+            };
+            result.push(prefix);
+            result.push_utfstr(line);
+            if !line.ends_with('\n') {
+                result.push_str("\n\\ No newline at end of file\n");
+            }
+            old_line += usize::from(*op != DiffOp::Insert);
+            new_line += usize::from(*op != DiffOp::Delete);
+        }
+        op_idx = end;
+    }
+    result
 }
 
 /// Given a string and list of colors of the same size, return the string with HTML span elements
@@ -1132,6 +2286,10 @@ fn html_class_name_for_color(spec: HighlightSpec) -> &'static wstr {
         HighlightRole::normal => L!("fish_color_normal"),
         HighlightRole::error => L!("fish_color_error"),
         HighlightRole::command => L!("fish_color_command"),
+        HighlightRole::builtin => L!("fish_color_builtin"),
+        HighlightRole::function => L!("fish_color_function"),
+        HighlightRole::abbreviation => L!("fish_color_abbreviation"),
+        HighlightRole::unset_variable => L!("fish_color_unset_variable"),
         HighlightRole::statement_terminator => L!("fish_color_statement_terminator"),
         HighlightRole::param => L!("fish_color_param"),
         HighlightRole::option => L!("fish_color_option"),
@@ -7,7 +7,16 @@
 //!
 //! Type "exit" or "quit" to terminate the program.
 
-use std::{ops::ControlFlow, os::unix::prelude::OsStrExt};
+use std::{
+    ffi::{CString, OsStr},
+    fs::File,
+    io::Write,
+    ops::ControlFlow,
+    os::unix::{ffi::OsStringExt, prelude::OsStrExt},
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
 
 use libc::{STDIN_FILENO, TCSANOW, VEOF, VINTR};
 
@@ -15,13 +24,21 @@ use libc::{STDIN_FILENO, TCSANOW, VEOF, VINTR};
 use fish::future::IsSomeAnd;
 use fish::{
     builtins::shared::BUILTIN_ERR_UNKNOWN,
-    common::{shell_modes, str2wcstring, PROGRAM_NAME},
+    common::{
+        escape_string, shell_modes, str2wcstring, wcs2osstring, wcs2string, EscapeStringStyle,
+        PROGRAM_NAME,
+    },
     env::env_init,
-    eprintf, fprintf,
-    input::input_terminfo_get_name,
-    input_common::{CharEvent, InputEventQueue, InputEventQueuer},
-    key::{self, Key},
+    eprintf,
+    fallback::fish_mkstemp_cloexec,
+    fprintf,
+    io::IoChain,
+    input::{input_binding_commands, input_terminfo_get_name},
+    input_common::{CharEvent, InputEventQueue, InputEventQueuer, KeyEvent},
+    key::{self, Key, KeyEventType},
+    key_recording::{key_recording_line, KEY_RECORDING_HEADER},
     panic::panic_handler,
+    parser::Parser,
     print_help::print_help,
     printf,
     proc::set_interactive_session,
@@ -30,9 +47,18 @@ use fish::{
     threads,
     topic_monitor::topic_monitor_init,
     wchar::prelude::*,
+    wcstringutil::join_strings,
     wgetopt::{wopt, ArgType, WGetopter, WOption},
 };
 
+struct Options {
+    continuous_mode: bool,
+    /// Print JSON instead of bind commands.
+    json: bool,
+    /// Where to record the keys, if anywhere.
+    record: Option<WString>,
+}
+
 /// Return true if the recent sequence of characters indicates the user wants to exit the program.
 fn should_exit(recent_keys: &mut Vec<Key>, key: Key) -> bool {
     recent_keys.push(key);
@@ -82,15 +108,111 @@ fn sequence_name(recent_chars: &mut Vec<u8>, c: char) -> Option<WString> {
     input_terminfo_get_name(&str2wcstring(recent_chars))
 }
 
+/// The script that makes fish write the key bindings of an interactive session as bind commands,
+/// and the bind mode they start in, to the file given as its argument. Anything else the
+/// configuration prints goes to stdout, which we ignore.
+const LIST_BINDINGS_SCRIPT: &str = "
+set -q fish_key_bindings[1]
+or set -g fish_key_bindings fish_default_key_bindings
+$fish_key_bindings 2>/dev/null
+functions -q fish_user_key_bindings
+and fish_user_key_bindings 2>/dev/null
+begin
+    bind --preset --user
+    echo set -g fish_bind_mode (string escape -- $fish_bind_mode)
+end >$argv[1]
+";
+
+/// Load the key bindings of an interactive fish, so we can tell what a key is bound to. We don't
+/// read the configuration ourselves, so ask the fish next to us to list them. It runs as an
+/// interactive shell, so it sees the bindings made in "status is-interactive" blocks too.
+fn load_bindings() {
+    let fish = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("fish")))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("fish"));
+    let template = std::env::temp_dir().join("fish_key_reader.XXXXXX");
+    let Ok((_, path)) =
+        fish_mkstemp_cloexec(CString::new(template.into_os_string().into_vec()).unwrap())
+    else {
+        return;
+    };
+    let path = PathBuf::from(OsStr::from_bytes(path.as_bytes()));
+    let status = Command::new(fish)
+        .arg("--interactive")
+        .args(["--command", LIST_BINDINGS_SCRIPT])
+        .arg(&path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let bindings = std::fs::read(&path);
+    let _ = std::fs::remove_file(&path);
+    let (Ok(status), Ok(bindings)) = (status, bindings) else {
+        return;
+    };
+    if !status.success() {
+        return;
+    }
+    // Bindings for keys this terminal doesn't have would complain.
+    let script = sprintf!("begin\n%ls\nend 2>/dev/null", str2wcstring(&bindings));
+    Parser::principal_parser().eval(&script, &IoChain::new());
+}
+
+/// Return a JSON object describing a key event, on one line.
+fn key_event_json(
+    kevt: &KeyEvent,
+    terminfo_name: Option<&wstr>,
+    binding: Option<&[WString]>,
+) -> WString {
+    let json = |s: &wstr| escape_string(s, EscapeStringStyle::Json);
+    let key = kevt.key;
+    let modifiers: Vec<WString> = [
+        (key.modifiers.ctrl, L!("ctrl")),
+        (key.modifiers.alt, L!("alt")),
+        (key.modifiers.shift, L!("shift")),
+        (key.modifiers.keypad, L!("keypad")),
+    ]
+    .into_iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| json(name))
+    .collect();
+    let event = match key.modifiers.event {
+        KeyEventType::Press => L!("press"),
+        KeyEventType::Repeat => L!("repeat"),
+        KeyEventType::Release => L!("release"),
+    };
+    let bytes: Vec<WString> = wcs2string(&kevt.seq)
+        .into_iter()
+        .map(|b| b.to_wstring())
+        .collect();
+    let binding = binding.map_or(L!("null").to_owned(), |commands| {
+        let commands: Vec<WString> = commands.iter().map(|cmd| json(cmd)).collect();
+        sprintf!("[%ls]", join_strings(&commands, ','))
+    });
+    sprintf!(
+        "{\"key\": %ls, \"codepoint\": %u, \"modifiers\": [%ls], \"event\": %ls, \"bytes\": [%ls], \"terminfo\": %ls, \"binding\": %ls}\n",
+        json(&WString::from(key)),
+        u32::from(key.codepoint),
+        join_strings(&modifiers, ','),
+        json(event),
+        join_strings(&bytes, ','),
+        terminfo_name.map_or(L!("null").to_owned(), json),
+        binding
+    )
+}
+
 /// Process the characters we receive as the user presses keys.
-fn process_input(continuous_mode: bool) -> i32 {
+fn process_input(opts: &Options, mut recording: Option<File>) -> i32 {
     let mut first_char_seen = false;
     let mut queue = InputEventQueue::new(STDIN_FILENO);
     let mut recent_chars1 = vec![];
     let mut recent_chars2 = vec![];
+    let mut last_key_time: Option<Instant> = None;
     eprintf!("Press a key:\n");
 
-    while (!first_char_seen || continuous_mode) && !check_exit_loop_maybe_warning(None) {
+    while (!first_char_seen || opts.continuous_mode) && !check_exit_loop_maybe_warning(None) {
         let evt = queue.readch();
 
         let CharEvent::Key(kevt) = evt else {
@@ -100,12 +222,33 @@ fn process_input(continuous_mode: bool) -> i32 {
         if c == key::Invalid {
             continue;
         }
-        printf!("bind %s 'do something'\n", kevt.key);
-        if let Some(name) = sequence_name(&mut recent_chars1, c) {
-            printf!("bind -k %ls 'do something'\n", name);
+        let terminfo_name = sequence_name(&mut recent_chars1, c);
+        if opts.json {
+            let binding =
+                input_binding_commands(Parser::principal_parser().vars(), kevt.key, &kevt.seq);
+            printf!(
+                "%ls",
+                key_event_json(&kevt, terminfo_name.as_deref(), binding.as_deref())
+            );
+        } else {
+            printf!("bind %s 'do something'\n", kevt.key);
+            if let Some(name) = terminfo_name {
+                printf!("bind -k %ls 'do something'\n", name);
+            }
         }
 
-        if continuous_mode && should_exit(&mut recent_chars2, kevt.key) {
+        if let Some(file) = recording.as_mut() {
+            let now = Instant::now();
+            let delay = last_key_time.map_or(Duration::ZERO, |last| now - last);
+            last_key_time = Some(now);
+            let line = key_recording_line(delay, &kevt.seq, kevt.key);
+            if !kevt.seq.is_empty() && file.write_all(&wcs2string(&line)).is_err() {
+                eprintf!("Could not write key recording\n");
+                recording = None;
+            }
+        }
+
+        if opts.continuous_mode && should_exit(&mut recent_chars2, kevt.key) {
             eprintf!("\nExiting at your request.\n");
             break;
         }
@@ -116,19 +259,36 @@ fn process_input(continuous_mode: bool) -> i32 {
 }
 
 /// Setup our environment (e.g., tty modes), process key strokes, then reset the environment.
-fn setup_and_process_keys(continuous_mode: bool) -> i32 {
+fn setup_and_process_keys(opts: &Options) -> i32 {
+    let recording = match &opts.record {
+        Some(path) => match File::create(wcs2osstring(path)) {
+            Ok(mut file) => {
+                let _ = file.write_all(KEY_RECORDING_HEADER.as_bytes());
+                Some(file)
+            }
+            Err(err) => {
+                eprintf!("Could not open '%ls': %s\n", path, err.to_string());
+                return 1;
+            }
+        },
+        None => None,
+    };
+
     set_interactive_session(true);
     topic_monitor_init();
     threads::init();
     env_init(None, true, false);
     let _restore_term = reader_init();
+    if opts.json {
+        load_bindings();
+    }
 
     signal_set_handlers(true);
     // We need to set the shell-modes for ICRNL,
     // in fish-proper this is done once a command is run.
     unsafe { libc::tcsetattr(STDIN_FILENO, TCSANOW, &*shell_modes()) };
 
-    if continuous_mode {
+    if opts.continuous_mode {
         eprintf!("\n");
         eprintf!("To terminate this program type \"exit\" or \"quit\" in this window,\n");
         let modes = shell_modes();
@@ -140,14 +300,17 @@ fn setup_and_process_keys(continuous_mode: bool) -> i32 {
         eprintf!("\n");
     }
 
-    process_input(continuous_mode)
+    process_input(opts, recording)
 }
 
-fn parse_flags(continuous_mode: &mut bool) -> ControlFlow<i32> {
-    let short_opts: &wstr = L!("+chvV");
+fn parse_flags(opts: &mut Options) -> ControlFlow<i32> {
+    const RECORD_ARG: char = 1 as char;
+    let short_opts: &wstr = L!("+chjvV");
     let long_opts: &[WOption] = &[
         wopt(L!("continuous"), ArgType::NoArgument, 'c'),
         wopt(L!("help"), ArgType::NoArgument, 'h'),
+        wopt(L!("json"), ArgType::NoArgument, 'j'),
+        wopt(L!("record"), ArgType::RequiredArgument, RECORD_ARG),
         wopt(L!("version"), ArgType::NoArgument, 'v'),
         wopt(L!("verbose"), ArgType::NoArgument, 'V'), // Removed
     ];
@@ -160,7 +323,13 @@ fn parse_flags(continuous_mode: &mut bool) -> ControlFlow<i32> {
     while let Some(opt) = w.next_opt() {
         match opt {
             'c' => {
-                *continuous_mode = true;
+                opts.continuous_mode = true;
+            }
+            'j' => {
+                opts.json = true;
+            }
+            RECORD_ARG => {
+                opts.record = Some(w.woptarg.unwrap().to_owned());
             }
             'h' => {
                 print_help("fish_key_reader");
@@ -208,9 +377,13 @@ fn main() {
 }
 
 fn throwing_main() -> i32 {
-    let mut continuous_mode = false;
+    let mut opts = Options {
+        continuous_mode: false,
+        json: false,
+        record: None,
+    };
 
-    if let ControlFlow::Break(i) = parse_flags(&mut continuous_mode) {
+    if let ControlFlow::Break(i) = parse_flags(&mut opts) {
         return i;
     }
 
@@ -219,5 +392,5 @@ fn throwing_main() -> i32 {
         return 1;
     }
 
-    setup_and_process_keys(continuous_mode)
+    setup_and_process_keys(&opts)
 }
@@ -6,8 +6,9 @@ use crate::common::{
 };
 use crate::highlight::{colorize, highlight_shell};
 use crate::input::{
-    input_function_get_names, input_mappings, input_terminfo_get_names,
-    input_terminfo_get_sequence, GetSequenceError, InputMappingSet, KeyNameStyle,
+    input_function_get_names, input_macro_commands, input_mappings, input_terminfo_get_names,
+    input_terminfo_get_sequence, GetSequenceError, InputMappingOptions, InputMappingSet,
+    KeyNameStyle, MacroCommandsError, SequenceTimeout, TimeoutFallback, LAST_MACRO_REGISTER,
 };
 use crate::key::{self, canonicalize_raw_escapes, char_to_symbol, parse_keys, Key, Modifiers};
 use crate::nix::isatty;
@@ -20,6 +21,11 @@ const BIND_ERASE: c_int = 1;
 const BIND_KEY_NAMES: c_int = 2;
 const BIND_FUNCTION_NAMES: c_int = 3;
 
+const MACRO_SHORT: char = 1 as char;
+const TIMEOUT_SHORT: char = 2 as char;
+const ON_TIMEOUT_SHORT: char = 3 as char;
+const DESCRIPTION_SHORT: char = 4 as char;
+
 struct Options {
     all: bool,
     bind_mode_given: bool,
@@ -27,6 +33,7 @@ struct Options {
     print_help: bool,
     silent: bool,
     use_terminfo: bool,
+    macro_register: Option<char>,
     have_user: bool,
     user: bool,
     have_preset: bool,
@@ -34,6 +41,9 @@ struct Options {
     mode: c_int,
     bind_mode: WString,
     sets_bind_mode: Option<WString>,
+    timeout_ms: Option<usize>,
+    timeout_fallback: Option<TimeoutFallback>,
+    description: Option<WString>,
 }
 
 impl Options {
@@ -45,6 +55,7 @@ impl Options {
             print_help: false,
             silent: false,
             use_terminfo: false,
+            macro_register: None,
             have_user: false,
             user: false,
             have_preset: false,
@@ -52,6 +63,9 @@ impl Options {
             mode: BIND_INSERT,
             bind_mode: DEFAULT_BIND_MODE.to_owned(),
             sets_bind_mode: None,
+            timeout_ms: None,
+            timeout_fallback: None,
+            description: None,
         }
     }
 }
@@ -83,7 +97,7 @@ impl BuiltinBind {
         streams: &mut IoStreams,
     ) -> bool {
         let mut ecmds: &[_] = &[];
-        let mut sets_mode = None;
+        let mut options = None;
         let mut key_name_style = KeyNameStyle::Plain;
         let mut out = WString::new();
         if !self.input_mappings.get(
@@ -91,11 +105,12 @@ impl BuiltinBind {
             bind_mode,
             &mut ecmds,
             user,
-            &mut sets_mode,
+            &mut options,
             &mut key_name_style,
         ) {
             return false;
         }
+        let options = options.unwrap();
 
         out.push_str("bind");
 
@@ -108,13 +123,22 @@ impl BuiltinBind {
             out.push_utfstr(&escape(bind_mode));
         }
 
-        if let Some(sets_mode) = sets_mode {
+        if let Some(sets_mode) = &options.sets_mode {
             if sets_mode != bind_mode {
                 out.push_str(" -m ");
                 out.push_utfstr(&escape(sets_mode));
             }
         }
 
+        if let Some(timeout) = options.timeout {
+            append_timeout(&mut out, timeout);
+        }
+
+        if let Some(description) = &options.description {
+            out.push_str(" --description ");
+            out.push_utfstr(&escape(description));
+        }
+
         out.push(' ');
         match key_name_style {
             KeyNameStyle::Plain => {
@@ -158,15 +182,7 @@ impl BuiltinBind {
         }
         out.push('\n');
 
-        if !streams.out_is_redirected && isatty(libc::STDOUT_FILENO) {
-            let mut colors = Vec::new();
-            highlight_shell(&out, &mut colors, &parser.context(), false, None);
-            let colored = colorize(&out, &colors, parser.vars());
-            streams.out.append(str2wcstring(&colored));
-        } else {
-            streams.out.append(out);
-        }
-
+        print_listing(out, parser, streams);
         true
     }
 
@@ -191,8 +207,26 @@ impl BuiltinBind {
         retval
     }
 
-    /// List all current key bindings.
+    /// List all current key bindings, after the sequence timeouts of their modes.
     fn list(&self, bind_mode: Option<&wstr>, user: bool, parser: &Parser, streams: &mut IoStreams) {
+        for (mode, timeout) in self.input_mappings.get_mode_timeouts(user) {
+            if bind_mode.is_some_and(|m| m != mode) {
+                continue;
+            }
+
+            let mut out = L!("bind").to_owned();
+            if !user {
+                out.push_str(" --preset");
+            }
+            if mode != DEFAULT_BIND_MODE {
+                out.push_str(" -M ");
+                out.push_utfstr(&escape(&mode));
+            }
+            append_timeout(&mut out, timeout);
+            out.push('\n');
+            print_listing(out, parser, streams);
+        }
+
         let lst = self.input_mappings.get_names(user);
         for binding in lst {
             if bind_mode.is_some_and(|m| m != binding.mode) {
@@ -269,11 +303,24 @@ impl BuiltinBind {
         } else {
             KeyNameStyle::Plain
         };
+        let options = InputMappingOptions {
+            sets_mode,
+            timeout: self.timeout(),
+            description: self.opts.description.clone(),
+        };
         self.input_mappings
-            .add(key_seq, key_name_style, cmds, mode, sets_mode, user);
+            .add(key_seq, key_name_style, cmds, mode, options, user);
         false
     }
 
+    /// The sequence timeout given by the options, if any.
+    fn timeout(&self) -> Option<SequenceTimeout> {
+        self.opts.timeout_ms.map(|ms| SequenceTimeout {
+            ms,
+            fallback: self.opts.timeout_fallback.unwrap_or_default(),
+        })
+    }
+
     fn compute_seq(&self, streams: &mut IoStreams, seq: &wstr) -> Option<Vec<Key>> {
         if self.opts.use_terminfo {
             let Some(tinfo_seq) = self.get_terminfo_sequence(seq, streams) else {
@@ -334,6 +381,25 @@ impl BuiltinBind {
         let argc = argv.len();
         let cmd = argv[0];
         let arg_count = argc - optind;
+        if arg_count == 0 && self.opts.timeout_ms.is_some() {
+            // Set the timeout for the whole mode.
+            if self.opts.have_preset && self.opts.have_user {
+                streams.err.append(wgettext_fmt!(
+                    BUILTIN_ERR_COMBO2_EXCLUSIVE,
+                    cmd,
+                    "--preset",
+                    "--user"
+                ));
+                return true;
+            }
+            let timeout = self.timeout().unwrap();
+            self.input_mappings.set_mode_timeout(
+                self.opts.bind_mode.clone(),
+                timeout,
+                self.opts.user,
+            );
+            return false;
+        }
         if arg_count < 2 {
             // If we get both or neither preset/user, we list both.
             if !self.opts.have_preset && !self.opts.have_user {
@@ -425,6 +491,49 @@ impl BuiltinBind {
         false
     }
 
+    /// Bind a key sequence to the keyboard macro in `register`.
+    fn insert_macro(
+        &mut self,
+        register: char,
+        optind: usize,
+        argv: &[&wstr],
+        streams: &mut IoStreams,
+    ) -> bool {
+        let cmd = argv[0];
+        let arg_count = argv.len() - optind;
+        if arg_count != 1 {
+            streams
+                .err
+                .append(wgettext_fmt!(BUILTIN_ERR_ARG_COUNT1, cmd, 1, arg_count));
+            return true;
+        }
+        let commands = match input_macro_commands(register) {
+            Ok(commands) if !commands.is_empty() => commands,
+            Ok(_) | Err(MacroCommandsError::NoMacro) => {
+                streams
+                    .err
+                    .append(wgettext_fmt!("%ls: No macro has been recorded\n", cmd));
+                return true;
+            }
+            Err(MacroCommandsError::HasArguments) => {
+                streams.err.append(wgettext_fmt!(
+                    "%ls: The recorded macro contains a jump, which cannot be bound\n",
+                    cmd
+                ));
+                return true;
+            }
+        };
+        let commands: Vec<&wstr> = commands.iter().map(|c| c.as_utfstr()).collect();
+        self.add(
+            argv[optind],
+            &commands,
+            self.opts.bind_mode.to_owned(),
+            self.opts.sets_bind_mode.to_owned(),
+            self.opts.user,
+            streams,
+        )
+    }
+
     /// List all current bind modes.
     fn list_modes(&mut self, streams: &mut IoStreams) {
         // List all known modes, even if they are only in preset bindings.
@@ -453,16 +562,20 @@ fn parse_cmd_opts(
     let short_options = L!(":aehkKfM:Lm:s");
     const long_options: &[WOption] = &[
         wopt(L!("all"), NoArgument, 'a'),
+        wopt(L!("description"), RequiredArgument, DESCRIPTION_SHORT),
         wopt(L!("erase"), NoArgument, 'e'),
         wopt(L!("function-names"), NoArgument, 'f'),
         wopt(L!("help"), NoArgument, 'h'),
         wopt(L!("key"), NoArgument, 'k'),
         wopt(L!("key-names"), NoArgument, 'K'),
         wopt(L!("list-modes"), NoArgument, 'L'),
+        wopt(L!("macro"), OptionalArgument, MACRO_SHORT),
         wopt(L!("mode"), RequiredArgument, 'M'),
+        wopt(L!("on-timeout"), RequiredArgument, ON_TIMEOUT_SHORT),
         wopt(L!("preset"), NoArgument, 'p'),
         wopt(L!("sets-mode"), RequiredArgument, 'm'),
         wopt(L!("silent"), NoArgument, 's'),
+        wopt(L!("timeout"), RequiredArgument, TIMEOUT_SHORT),
         wopt(L!("user"), NoArgument, 'u'),
     ];
 
@@ -470,11 +583,26 @@ fn parse_cmd_opts(
     while let Some(c) = w.next_opt() {
         match c {
             'a' => opts.all = true,
+            DESCRIPTION_SHORT => opts.description = Some(w.woptarg.unwrap().to_owned()),
             'e' => opts.mode = BIND_ERASE,
             'f' => opts.mode = BIND_FUNCTION_NAMES,
             'h' => opts.print_help = true,
             'k' => opts.use_terminfo = true,
             'K' => opts.mode = BIND_KEY_NAMES,
+            MACRO_SHORT => {
+                opts.macro_register = Some(match w.woptarg {
+                    None => LAST_MACRO_REGISTER,
+                    Some(arg) if arg.len() == 1 => arg.char_at(0),
+                    Some(arg) => {
+                        streams.err.append(wgettext_fmt!(
+                            "%ls: Invalid macro register '%ls'\n",
+                            cmd,
+                            arg
+                        ));
+                        return STATUS_INVALID_ARGS;
+                    }
+                });
+            }
             'L' => {
                 opts.list_modes = true;
                 return STATUS_CMD_OK;
@@ -506,6 +634,34 @@ fn parse_cmd_opts(
                 opts.preset = true;
             }
             's' => opts.silent = true,
+            TIMEOUT_SHORT => {
+                let arg = w.woptarg.unwrap();
+                let Some(ms) = fish_wcstoi(arg)
+                    .ok()
+                    .and_then(|ms| usize::try_from(ms).ok())
+                else {
+                    streams
+                        .err
+                        .append(wgettext_fmt!(BUILTIN_ERR_NOT_NUMBER, cmd, arg));
+                    return STATUS_INVALID_ARGS;
+                };
+                opts.timeout_ms = Some(ms);
+            }
+            ON_TIMEOUT_SHORT => {
+                let arg = w.woptarg.unwrap();
+                opts.timeout_fallback = Some(match arg {
+                    _ if arg == "prefix" => TimeoutFallback::Prefix,
+                    _ if arg == "insert" => TimeoutFallback::Insert,
+                    _ => {
+                        streams.err.append(wgettext_fmt!(
+                            "%ls: Invalid timeout action '%ls'\n",
+                            cmd,
+                            arg
+                        ));
+                        return STATUS_INVALID_ARGS;
+                    }
+                });
+            }
             'u' => {
                 opts.have_user = true;
                 opts.user = true;
@@ -523,6 +679,14 @@ fn parse_cmd_opts(
             }
         }
     }
+    if opts.timeout_fallback.is_some() && opts.timeout_ms.is_none() {
+        streams.err.append(wgettext_fmt!(
+            BUILTIN_ERR_COMBO2,
+            cmd,
+            wgettext!("--on-timeout requires --timeout")
+        ));
+        return STATUS_INVALID_ARGS;
+    }
     *optind = w.wopt_index;
     return STATUS_CMD_OK;
 }
@@ -581,6 +745,12 @@ impl BuiltinBind {
                     }
                 }
             }
+            BIND_INSERT if self.opts.macro_register.is_some() => {
+                let register = self.opts.macro_register.unwrap();
+                if self.insert_macro(register, optind, argv, streams) {
+                    return STATUS_CMD_ERROR;
+                }
+            }
             BIND_INSERT => {
                 if self.insert(optind, argv, parser, streams) {
                     return STATUS_CMD_ERROR;
@@ -599,6 +769,26 @@ impl BuiltinBind {
     }
 }
 
+/// Append the options that recreate `timeout` to a listing.
+fn append_timeout(out: &mut WString, timeout: SequenceTimeout) {
+    out.push_utfstr(&sprintf!(" --timeout %lu", timeout.ms));
+    if timeout.fallback == TimeoutFallback::Insert {
+        out.push_str(" --on-timeout insert");
+    }
+}
+
+/// Print a line of the listing, highlighted if it goes to a terminal.
+fn print_listing(out: WString, parser: &Parser, streams: &mut IoStreams) {
+    if !streams.out_is_redirected && isatty(libc::STDOUT_FILENO) {
+        let mut colors = Vec::new();
+        highlight_shell(&out, &mut colors, &parser.context(), false, None);
+        let colored = colorize(&out, &colors, parser.vars());
+        streams.out.append(str2wcstring(&colored));
+    } else {
+        streams.out.append(out);
+    }
+}
+
 pub fn bind(parser: &Parser, streams: &mut IoStreams, args: &mut [&wstr]) -> Option<c_int> {
     BuiltinBind::new().bind(parser, streams, args)
 }
@@ -36,6 +36,7 @@ pub mod source;
 pub mod status;
 pub mod string;
 pub mod test;
+pub mod theme;
 pub mod r#type;
 pub mod ulimit;
 pub mod wait;
@@ -1,22 +1,14 @@
 // Implementation of the set_color builtin.
 
 use super::prelude::*;
-use crate::color::RgbColor;
+use crate::color::{RgbColor, UnderlineStyle};
 use crate::common::str2wcstring;
 use crate::curses::{self, Term};
 use crate::output::{self, Outputter};
+use crate::terminal::{hyperlink_start, terminal_supports, TerminalFeature};
 
-#[allow(clippy::too_many_arguments)]
-fn print_modifiers(
-    outp: &mut Outputter,
-    term: &Term,
-    bold: bool,
-    underline: bool,
-    italics: bool,
-    dim: bool,
-    reverse: bool,
-    bg: RgbColor,
-) {
+/// Print the modifiers set in `modifiers`, whose color is ignored.
+fn print_modifiers(outp: &mut Outputter, term: &Term, modifiers: RgbColor, bg: RgbColor) {
     let Term {
         enter_bold_mode,
         enter_underline_mode,
@@ -27,44 +19,54 @@ fn print_modifiers(
         exit_attribute_mode,
         ..
     } = term;
-    if bold {
+    if modifiers.is_bold() {
         outp.tputs_if_some(enter_bold_mode);
     }
 
-    if underline {
-        outp.tputs_if_some(enter_underline_mode);
+    // Other terminals may misread the colon-separated parameters, so only underline plainly.
+    let styled_underlines = terminal_supports(TerminalFeature::StyledUnderlines);
+    match modifiers.underline_style() {
+        None => (),
+        Some(style) if style != UnderlineStyle::Single && styled_underlines => {
+            outp.write_underline_style(style)
+        }
+        Some(_) => {
+            outp.tputs_if_some(enter_underline_mode);
+        }
     }
 
-    if italics {
+    if let Some(color) = modifiers.underline_color().filter(|_| styled_underlines) {
+        outp.write_underline_color(Some(color));
+    }
+
+    if modifiers.is_italics() {
         outp.tputs_if_some(enter_italics_mode);
     }
 
-    if dim {
+    if modifiers.is_dim() {
         outp.tputs_if_some(enter_dim_mode);
     }
 
     #[allow(clippy::collapsible_if)]
-    if reverse {
+    if modifiers.is_reverse() {
         if !outp.tputs_if_some(enter_reverse_mode) {
             outp.tputs_if_some(enter_standout_mode);
         }
     }
+
+    if modifiers.is_strikethrough() {
+        outp.write_strikethrough(true);
+    }
+
+    if modifiers.is_overline() {
+        outp.write_overline(true);
+    }
     if !bg.is_none() && bg.is_normal() {
         outp.tputs_if_some(exit_attribute_mode);
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn print_colors(
-    streams: &mut IoStreams,
-    args: &[&wstr],
-    bold: bool,
-    underline: bool,
-    italics: bool,
-    dim: bool,
-    reverse: bool,
-    bg: RgbColor,
-) {
+fn print_colors(streams: &mut IoStreams, args: &[&wstr], modifiers: RgbColor, bg: RgbColor) {
     let outp = &mut output::Outputter::new_buffering();
 
     // Rebind args to named_colors if there are no args.
@@ -80,7 +82,7 @@ fn print_colors(
     for color_name in args {
         if streams.out_is_terminal() {
             if let Some(term) = term.as_ref() {
-                print_modifiers(outp, term, bold, underline, italics, dim, reverse, bg);
+                print_modifiers(outp, term, modifiers, bg);
             }
             let color = RgbColor::from_wstr(color_name).unwrap_or(RgbColor::NONE);
             outp.set_color(color, RgbColor::NONE);
@@ -103,16 +105,28 @@ fn print_colors(
     streams.out.append(str2wcstring(contents));
 }
 
-const SHORT_OPTIONS: &wstr = L!(":b:hoidrcu");
+const HYPERLINK_ARG: char = '\x01';
+const UNDERLINE_COLOR_ARG: char = '\x02';
+const OVERLINE_ARG: char = '\x03';
+
+const SHORT_OPTIONS: &wstr = L!(":b:hoidrcu::s");
 const LONG_OPTIONS: &[WOption] = &[
     wopt(L!("background"), ArgType::RequiredArgument, 'b'),
     wopt(L!("help"), ArgType::NoArgument, 'h'),
     wopt(L!("bold"), ArgType::NoArgument, 'o'),
-    wopt(L!("underline"), ArgType::NoArgument, 'u'),
+    wopt(L!("underline"), ArgType::OptionalArgument, 'u'),
+    wopt(
+        L!("underline-color"),
+        ArgType::RequiredArgument,
+        UNDERLINE_COLOR_ARG,
+    ),
+    wopt(L!("strikethrough"), ArgType::NoArgument, 's'),
+    wopt(L!("overline"), ArgType::NoArgument, OVERLINE_ARG),
     wopt(L!("italics"), ArgType::NoArgument, 'i'),
     wopt(L!("dim"), ArgType::NoArgument, 'd'),
     wopt(L!("reverse"), ArgType::NoArgument, 'r'),
     wopt(L!("print-colors"), ArgType::NoArgument, 'c'),
+    wopt(L!("hyperlink"), ArgType::RequiredArgument, HYPERLINK_ARG),
 ];
 
 /// set_color builtin.
@@ -127,12 +141,9 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
     }
 
     let mut bgcolor = None;
-    let mut bold = false;
-    let mut underline = false;
-    let mut italics = false;
-    let mut dim = false;
-    let mut reverse = false;
+    let mut modifiers = RgbColor::NONE;
     let mut print = false;
+    let mut hyperlink = None;
 
     let mut w = WGetopter::new(SHORT_OPTIONS, LONG_OPTIONS, argv);
     while let Some(c) = w.next_opt() {
@@ -145,15 +156,44 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
                 builtin_print_help(parser, streams, argv[0]);
                 return STATUS_CMD_OK;
             }
-            'o' => bold = true,
-            'i' => italics = true,
-            'd' => dim = true,
-            'r' => reverse = true,
-            'u' => underline = true,
+            'o' => modifiers.set_bold(true),
+            'i' => modifiers.set_italics(true),
+            'd' => modifiers.set_dim(true),
+            'r' => modifiers.set_reverse(true),
+            'u' => {
+                let style = match w.woptarg {
+                    None => UnderlineStyle::Single,
+                    Some(name) => match UnderlineStyle::from_name(name) {
+                        Some(style) => style,
+                        None => {
+                            streams.err.append(wgettext_fmt!(
+                                "%ls: Unknown underline style '%ls'\n",
+                                argv[0],
+                                name
+                            ));
+                            return STATUS_INVALID_ARGS;
+                        }
+                    },
+                };
+                modifiers.set_underline_style(Some(style));
+            }
+            UNDERLINE_COLOR_ARG => {
+                let name = w.woptarg.unwrap();
+                let Some(color) = RgbColor::from_wstr(name).filter(|c| !c.is_special()) else {
+                    streams
+                        .err
+                        .append(wgettext_fmt!("%ls: Unknown color '%ls'\n", argv[0], name));
+                    return STATUS_INVALID_ARGS;
+                };
+                modifiers.set_underline_color(color);
+            }
+            's' => modifiers.set_strikethrough(true),
+            OVERLINE_ARG => modifiers.set_overline(true),
             'c' => print = true,
+            HYPERLINK_ARG => hyperlink = w.woptarg,
             ':' => {
-                // We don't error here because "-b" is the only option that requires an argument,
-                // and we don't error for missing colors.
+                // We don't error here because "-b", "--underline-color" and "--hyperlink" are the
+                // only options that require an argument, and we don't error for missing colors.
                 return STATUS_INVALID_ARGS;
             }
             '?' => {
@@ -182,6 +222,18 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
         return STATUS_INVALID_ARGS;
     }
 
+    if let Some(url) = hyperlink {
+        // The URL ends up in an escape sequence, so it must not end that early.
+        if url.chars().any(|c| c.is_control()) {
+            streams.err.append(wgettext_fmt!(
+                "%ls: Invalid hyperlink '%ls'\n",
+                argv[0],
+                url
+            ));
+            return STATUS_INVALID_ARGS;
+        }
+    }
+
     if print {
         // Hack: Explicitly setting a background of "normal" crashes
         // for --print-colors. Because it's not interesting in terms of display,
@@ -190,7 +242,7 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
             bg = RgbColor::from_wstr(L!("")).unwrap_or(RgbColor::NONE);
         }
         let args = &argv[wopt_index..argc];
-        print_colors(streams, args, bold, underline, italics, dim, reverse, bg);
+        print_colors(streams, args, modifiers, bg);
         return STATUS_CMD_OK;
     }
 
@@ -217,6 +269,11 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
 
     // Test if we have at least basic support for setting fonts, colors and related bits - otherwise
     // just give up...
+    if let Some(url) = hyperlink {
+        // This does not depend on terminfo.
+        streams.out.append(hyperlink_start(url));
+    }
+
     let Some(term) = curses::term() else {
         return STATUS_CMD_ERROR;
     };
@@ -225,7 +282,7 @@ pub fn set_color(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -
     };
 
     let outp = &mut output::Outputter::new_buffering();
-    print_modifiers(outp, &term, bold, underline, italics, dim, reverse, bg);
+    print_modifiers(outp, &term, modifiers, bg);
     if bgcolor.is_some() && bg.is_normal() {
         outp.tputs(exit_attribute_mode);
     }
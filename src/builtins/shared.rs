@@ -333,6 +333,10 @@ const BUILTIN_DATAS: &[BuiltinData] = &[
         name: L!("test"),
         func: test::test,
     },
+    BuiltinData {
+        name: L!("theme"),
+        func: theme::theme,
+    },
     BuiltinData {
         name: L!("time"),
         func: builtin_generic,
@@ -518,6 +522,7 @@ pub fn builtin_get_desc(name: &wstr) -> Option<&'static wstr> {
         _ if name == "string" => wgettext!("Manipulate strings"),
         _ if name == "switch" => wgettext!("Conditionally run blocks of code"),
         _ if name == "test" => wgettext!("Test a condition"),
+        _ if name == "theme" => wgettext!("List, preview and choose color themes"),
         _ if name == "time" => wgettext!("Measure how long a command or block takes"),
         _ if name == "true" => wgettext!("Return a successful result"),
         _ if name == "type" => wgettext!("Check if a thing is a thing"),
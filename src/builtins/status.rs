@@ -6,6 +6,7 @@ use crate::future_feature_flags::{self as features, feature_test};
 use crate::proc::{
     get_job_control_mode, get_login, is_interactive_session, set_job_control_mode, JobControl,
 };
+use crate::terminal::{terminal_info, terminal_supports, TerminalFeature};
 use crate::wutil::{waccess, wbasename, wdirname, wrealpath, Error};
 use libc::F_OK;
 use nix::errno::Errno;
@@ -57,6 +58,7 @@ enum StatusCmd {
     STATUS_STACK_TRACE,
     STATUS_TEST_FEATURE,
     STATUS_CURRENT_COMMANDLINE,
+    STATUS_TERMINAL,
 }
 
 str_enum!(
@@ -86,6 +88,7 @@ str_enum!(
     (STATUS_LINE_NUMBER, "line-number"),
     (STATUS_STACK_TRACE, "print-stack-trace"),
     (STATUS_STACK_TRACE, "stack-trace"),
+    (STATUS_TERMINAL, "terminal"),
     (STATUS_TEST_FEATURE, "test-feature"),
 );
 
@@ -193,6 +196,15 @@ fn print_features(streams: &mut IoStreams) {
     }
 }
 
+/// Print what we know about the terminal. Return false if we have not asked it.
+fn print_terminal(streams: &mut IoStreams) -> bool {
+    let Some(description) = terminal_info().describe() else {
+        return false;
+    };
+    streams.out.append(description);
+    true
+}
+
 fn parse_cmd_opts(
     opts: &mut StatusCmdOpts,
     optind: &mut usize,
@@ -397,6 +409,36 @@ pub fn status(parser: &Parser, streams: &mut IoStreams, args: &mut [&wstr]) -> O
             set_job_control_mode(job_control_mode);
         }
         STATUS_FEATURES => print_features(streams),
+        c @ STATUS_TERMINAL => match args.len() {
+            0 => {
+                if !print_terminal(streams) {
+                    return STATUS_CMD_ERROR;
+                }
+            }
+            1 => {
+                let Some(feature) = TerminalFeature::from_name(args[0]) else {
+                    streams.err.append(wgettext_fmt!(
+                        "%ls: Unknown terminal feature '%ls'\n",
+                        cmd,
+                        args[0]
+                    ));
+                    return STATUS_INVALID_ARGS;
+                };
+                if !terminal_supports(feature) {
+                    return STATUS_CMD_ERROR;
+                }
+            }
+            _ => {
+                streams.err.append(wgettext_fmt!(
+                    BUILTIN_ERR_ARG_COUNT2,
+                    cmd,
+                    c.to_wstr(),
+                    1,
+                    args.len()
+                ));
+                return STATUS_INVALID_ARGS;
+            }
+        },
         c @ STATUS_TEST_FEATURE => {
             if args.len() != 1 {
                 streams.err.append(wgettext_fmt!(
@@ -0,0 +1,216 @@
+//! Implementation of the theme builtin.
+
+use super::prelude::*;
+use crate::color::RgbColor;
+use crate::common::str2wcstring;
+use crate::env::{EnvMode, Environment};
+use crate::event::{self, Event};
+use crate::output::Outputter;
+use crate::theme::{theme_choose, theme_demo, theme_load, theme_names, ThemeVariant};
+
+#[derive(Default)]
+struct Options {
+    print_help: bool,
+    universal: bool,
+    variant: Option<ThemeVariant>,
+}
+
+const VARIANT_ARG: char = '\x01';
+
+const SHORT_OPTIONS: &wstr = L!(":hU");
+const LONG_OPTIONS: &[WOption] = &[
+    wopt(L!("help"), NoArgument, 'h'),
+    wopt(L!("universal"), NoArgument, 'U'),
+    wopt(L!("variant"), RequiredArgument, VARIANT_ARG),
+];
+
+/// Return `text` underlined, if `colorize` is set, followed by a newline.
+fn header(text: &wstr, colorize: bool) -> WString {
+    let mut outp = Outputter::new_buffering();
+    if colorize {
+        let mut underline = RgbColor::NORMAL;
+        underline.set_underline(true);
+        outp.set_color(underline, RgbColor::NORMAL);
+    }
+    outp.write_wstr(text);
+    if colorize {
+        outp.set_color(RgbColor::NORMAL, RgbColor::NORMAL);
+    }
+    outp.writech('\n');
+    str2wcstring(outp.contents())
+}
+
+/// Print a preview of the current colors, if no names are given, and of the given themes, or all
+/// themes.
+fn show(
+    parser: &Parser,
+    streams: &mut IoStreams,
+    cmd: &wstr,
+    opts: &Options,
+    args: &[&wstr],
+) -> Option<c_int> {
+    let vars = parser.vars();
+    let colorize = streams.out_is_terminal();
+    let names = if args.is_empty() {
+        streams.out.append(header(L!("Current"), colorize));
+        let current = |name: &wstr| vars.get(name).map(|var| var.as_list().to_vec());
+        streams.out.append(theme_demo(&current, colorize));
+        streams.out.append_char('\n');
+        theme_names(vars)
+    } else {
+        args.iter().map(|&name| name.to_owned()).collect()
+    };
+
+    let mut retval = STATUS_CMD_OK;
+    for name in names {
+        let theme = match theme_load(vars, &name) {
+            Ok((theme, warnings)) => {
+                for warning in warnings {
+                    streams.err.append(sprintf!("%ls: %ls\n", cmd, warning));
+                }
+                theme
+            }
+            Err(err) => {
+                streams.err.append(sprintf!("%ls: %ls\n", cmd, err));
+                retval = STATUS_CMD_ERROR;
+                continue;
+            }
+        };
+        let variants = match opts.variant {
+            Some(variant) => vec![variant],
+            None if theme.has_variants() => ThemeVariant::ALL.to_vec(),
+            None => vec![ThemeVariant::Dark],
+        };
+        for variant in variants {
+            let title = if theme.has_variants() {
+                sprintf!("%ls (%ls)", theme.name, variant.name())
+            } else {
+                theme.name.clone()
+            };
+            streams.out.append(header(&title, colorize));
+            let colors = theme.colors(variant);
+            let lookup = |name: &wstr| {
+                colors
+                    .iter()
+                    .find(|(n, _)| &**n == name)
+                    .map(|(_, value)| value.clone())
+            };
+            streams.out.append(theme_demo(&lookup, colorize));
+            streams.out.append_char('\n');
+        }
+    }
+    retval
+}
+
+/// The theme builtin, for listing, previewing and choosing color themes.
+pub fn theme(parser: &Parser, streams: &mut IoStreams, argv: &mut [&wstr]) -> Option<c_int> {
+    let cmd = argv[0];
+    let mut opts = Options::default();
+
+    let mut w = WGetopter::new(SHORT_OPTIONS, LONG_OPTIONS, argv);
+    while let Some(c) = w.next_opt() {
+        match c {
+            'h' => opts.print_help = true,
+            'U' => opts.universal = true,
+            VARIANT_ARG => {
+                let name = w.woptarg.unwrap();
+                let Some(variant) = ThemeVariant::from_name(name) else {
+                    streams.err.append(wgettext_fmt!(
+                        "%ls: %ls: invalid variant, expected 'light' or 'dark'\n",
+                        cmd,
+                        name
+                    ));
+                    return STATUS_INVALID_ARGS;
+                };
+                opts.variant = Some(variant);
+            }
+            ':' => {
+                builtin_missing_argument(parser, streams, cmd, argv[w.wopt_index - 1], true);
+                return STATUS_INVALID_ARGS;
+            }
+            '?' => {
+                builtin_unknown_option(parser, streams, cmd, argv[w.wopt_index - 1], true);
+                return STATUS_INVALID_ARGS;
+            }
+            _ => panic!("unexpected retval from WGetopter"),
+        }
+    }
+    let optind = w.wopt_index;
+
+    if opts.print_help {
+        builtin_print_help(parser, streams, cmd);
+        return STATUS_CMD_OK;
+    }
+
+    let (subcmd, args) = match argv[optind..].split_first() {
+        Some((subcmd, args)) => (*subcmd, args),
+        None => (L!("list"), &[][..]),
+    };
+
+    if opts.universal && subcmd != "choose" {
+        streams.err.append(wgettext_fmt!(
+            BUILTIN_ERR_COMBO2,
+            cmd,
+            wgettext!("--universal can only be used with choose")
+        ));
+        return STATUS_INVALID_ARGS;
+    }
+
+    match subcmd {
+        _ if subcmd == "list" => {
+            if opts.variant.is_some() || !args.is_empty() {
+                streams.err.append(wgettext_fmt!(
+                    "%ls: %ls: subcommand takes no options or arguments\n",
+                    cmd,
+                    subcmd
+                ));
+                return STATUS_INVALID_ARGS;
+            }
+            for name in theme_names(parser.vars()) {
+                streams.out.appendln(name);
+            }
+            STATUS_CMD_OK
+        }
+        _ if subcmd == "show" => show(parser, streams, cmd, &opts, args),
+        _ if subcmd == "choose" => {
+            if args.len() != 1 {
+                streams.err.append(wgettext_fmt!(
+                    BUILTIN_ERR_ARG_COUNT2,
+                    cmd,
+                    subcmd,
+                    1,
+                    args.len()
+                ));
+                return STATUS_INVALID_ARGS;
+            }
+            let theme = match theme_load(parser.vars(), args[0]) {
+                Ok((theme, warnings)) => {
+                    for warning in warnings {
+                        streams.err.append(sprintf!("%ls: %ls\n", cmd, warning));
+                    }
+                    theme
+                }
+                Err(err) => {
+                    streams.err.append(sprintf!("%ls: %ls\n", cmd, err));
+                    return STATUS_CMD_ERROR;
+                }
+            };
+            let mode = if opts.universal {
+                EnvMode::UNIVERSAL
+            } else {
+                EnvMode::GLOBAL
+            };
+            // Only fire events once all variables are set, so handlers see the whole theme.
+            for name in theme_choose(parser.vars(), &theme, opts.variant, mode) {
+                event::fire(parser, Event::variable_set(name));
+            }
+            STATUS_CMD_OK
+        }
+        _ => {
+            streams
+                .err
+                .append(wgettext_fmt!(BUILTIN_ERR_INVALID_SUBCMD, cmd, subcmd));
+            STATUS_INVALID_ARGS
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use bitflags::bitflags;
+use once_cell::sync::Lazy;
 use std::cmp::Ordering;
+use std::sync::Mutex;
 
 use crate::wchar::prelude::*;
 
@@ -34,13 +36,80 @@ pub enum Type {
 
 bitflags! {
     #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
-    pub struct Flags: u8 {
+    pub struct Flags: u16 {
         const DEFAULT = 0;
         const BOLD = 1<<0;
         const UNDERLINE = 1<<1;
         const ITALICS = 1<<2;
         const DIM = 1<<3;
         const REVERSE = 1<<4;
+        const STRIKETHROUGH = 1<<5;
+        const OVERLINE = 1<<6;
+        const UNDERLINE_DOUBLE = 1<<7;
+        const UNDERLINE_CURLY = 1<<8;
+        const UNDERLINE_DOTTED = 1<<9;
+        const UNDERLINE_DASHED = 1<<10;
+        /// All the underline styles, at most one of which is set.
+        const ANY_UNDERLINE = Self::UNDERLINE.bits()
+            | Self::UNDERLINE_DOUBLE.bits()
+            | Self::UNDERLINE_CURLY.bits()
+            | Self::UNDERLINE_DOTTED.bits()
+            | Self::UNDERLINE_DASHED.bits();
+    }
+}
+
+/// The ways text may be underlined.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnderlineStyle {
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl UnderlineStyle {
+    pub const ALL: [UnderlineStyle; 5] = [
+        UnderlineStyle::Single,
+        UnderlineStyle::Double,
+        UnderlineStyle::Curly,
+        UnderlineStyle::Dotted,
+        UnderlineStyle::Dashed,
+    ];
+
+    pub fn name(self) -> &'static wstr {
+        match self {
+            UnderlineStyle::Single => L!("single"),
+            UnderlineStyle::Double => L!("double"),
+            UnderlineStyle::Curly => L!("curly"),
+            UnderlineStyle::Dotted => L!("dotted"),
+            UnderlineStyle::Dashed => L!("dashed"),
+        }
+    }
+
+    pub fn from_name(name: &wstr) -> Option<Self> {
+        Self::ALL.into_iter().find(|style| style.name() == name)
+    }
+
+    /// The parameter of the SGR sequence that selects this style, as in `CSI 4:3 m`.
+    pub fn sgr_param(self) -> u8 {
+        match self {
+            UnderlineStyle::Single => 1,
+            UnderlineStyle::Double => 2,
+            UnderlineStyle::Curly => 3,
+            UnderlineStyle::Dotted => 4,
+            UnderlineStyle::Dashed => 5,
+        }
+    }
+
+    fn flag(self) -> Flags {
+        match self {
+            UnderlineStyle::Single => Flags::UNDERLINE,
+            UnderlineStyle::Double => Flags::UNDERLINE_DOUBLE,
+            UnderlineStyle::Curly => Flags::UNDERLINE_CURLY,
+            UnderlineStyle::Dotted => Flags::UNDERLINE_DOTTED,
+            UnderlineStyle::Dashed => Flags::UNDERLINE_DASHED,
+        }
     }
 }
 
@@ -49,6 +118,8 @@ bitflags! {
 pub struct RgbColor {
     pub typ: Type,
     pub flags: Flags,
+    /// The color of the underline, or [`Type::None`] to use the text color.
+    pub underline_color: Type,
 }
 
 impl RgbColor {
@@ -56,30 +127,35 @@ impl RgbColor {
     pub const WHITE: Self = Self {
         typ: Type::Named { idx: 7 },
         flags: Flags::DEFAULT,
+        underline_color: Type::None,
     };
 
     /// The color black
     pub const BLACK: Self = Self {
         typ: Type::Named { idx: 0 },
         flags: Flags::DEFAULT,
+        underline_color: Type::None,
     };
 
     /// The reset special color.
     pub const RESET: Self = Self {
         typ: Type::Reset,
         flags: Flags::DEFAULT,
+        underline_color: Type::None,
     };
 
     /// The normal special color.
     pub const NORMAL: Self = Self {
         typ: Type::Normal,
         flags: Flags::DEFAULT,
+        underline_color: Type::None,
     };
 
     /// The none special color.
     pub const NONE: Self = Self {
         typ: Type::None,
         flags: Flags::DEFAULT,
+        underline_color: Type::None,
     };
 
     /// Parse a color from a string.
@@ -94,6 +170,7 @@ impl RgbColor {
         Self {
             typ: Type::Rgb(Color24 { r, g, b }),
             flags: Flags::DEFAULT,
+            underline_color: Type::None,
         }
     }
 
@@ -137,14 +214,49 @@ impl RgbColor {
         self.flags.set(Flags::BOLD, bold)
     }
 
-    /// Returns whether the color is underlined.
+    /// Returns whether the color is underlined, in any style.
     pub const fn is_underline(self) -> bool {
-        self.flags.contains(Flags::UNDERLINE)
+        self.flags.intersects(Flags::ANY_UNDERLINE)
     }
 
-    /// Set whether the color is underline.
+    /// Set whether the color is underline. An existing underline keeps its style.
     pub fn set_underline(&mut self, underline: bool) {
-        self.flags.set(Flags::UNDERLINE, underline)
+        if !underline {
+            self.set_underline_style(None);
+        } else if !self.is_underline() {
+            self.set_underline_style(Some(UnderlineStyle::Single));
+        }
+    }
+
+    /// Returns how the color is underlined, if at all.
+    pub fn underline_style(self) -> Option<UnderlineStyle> {
+        UnderlineStyle::ALL
+            .into_iter()
+            .find(|style| self.flags.contains(style.flag()))
+    }
+
+    /// Set how the color is underlined, or that it is not.
+    pub fn set_underline_style(&mut self, style: Option<UnderlineStyle>) {
+        self.flags.remove(Flags::ANY_UNDERLINE);
+        if let Some(style) = style {
+            self.flags.insert(style.flag());
+        }
+    }
+
+    /// Returns the color of the underline, if it differs from the text color.
+    pub fn underline_color(self) -> Option<RgbColor> {
+        let mut color = RgbColor::NONE;
+        color.typ = self.underline_color;
+        (color.is_named() || color.is_rgb()).then_some(color)
+    }
+
+    /// Set the color of the underline. Special colors mean the text color.
+    pub fn set_underline_color(&mut self, color: RgbColor) {
+        self.underline_color = if color.is_special() {
+            Type::None
+        } else {
+            color.typ
+        };
     }
 
     /// Returns whether the color is italics.
@@ -177,6 +289,37 @@ impl RgbColor {
         self.flags.set(Flags::REVERSE, reverse)
     }
 
+    /// Returns whether the color is struck through.
+    pub const fn is_strikethrough(self) -> bool {
+        self.flags.contains(Flags::STRIKETHROUGH)
+    }
+
+    /// Set whether the color is struck through.
+    pub fn set_strikethrough(&mut self, strikethrough: bool) {
+        self.flags.set(Flags::STRIKETHROUGH, strikethrough)
+    }
+
+    /// Returns whether the color is overlined.
+    pub const fn is_overline(self) -> bool {
+        self.flags.contains(Flags::OVERLINE)
+    }
+
+    /// Set whether the color is overlined.
+    pub fn set_overline(&mut self, overline: bool) {
+        self.flags.set(Flags::OVERLINE, overline)
+    }
+
+    /// Add the modifiers of `other` to ours. Where both specify an underline style or color, ours
+    /// wins.
+    pub fn add_modifiers(&mut self, other: RgbColor) {
+        let underline_style = self.underline_style().or(other.underline_style());
+        self.flags |= other.flags;
+        self.set_underline_style(underline_style);
+        if self.underline_color().is_none() {
+            self.underline_color = other.underline_color;
+        }
+    }
+
     /// Returns the name index for the given color. Requires that the color be named or RGB.
     pub fn to_name_index(self) -> u8 {
         // TODO: This should look for the nearest color.
@@ -241,6 +384,7 @@ impl RgbColor {
         Some(Self {
             typ,
             flags: Flags::default(),
+            underline_color: Type::None,
         })
     }
 
@@ -297,6 +441,7 @@ impl RgbColor {
                 idx: NAMED_COLORS[i].idx,
             },
             flags: Flags::default(),
+            underline_color: Type::None,
         })
     }
 }
@@ -346,87 +491,148 @@ const NAMED_COLORS: &[NamedColor] = &[
 
 assert_sorted_by_name!(NAMED_COLORS);
 
-fn convert_color(color: Color24, colors: &[u32]) -> usize {
-    fn squared_difference(a: u8, b: u8) -> u32 {
-        let a = u32::from(a);
-        let b = u32::from(b);
-        a.abs_diff(b).pow(2)
+/// A color in the OKLab color space, where the distance between colors approximates how different
+/// they look.
+#[derive(Clone, Copy, Debug)]
+struct Oklab {
+    l: f64,
+    a: f64,
+    b: f64,
+}
+
+impl Oklab {
+    fn from_rgb(color: Color24) -> Self {
+        // Undo the sRGB gamma curve.
+        fn linear(c: u8) -> f64 {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        let (r, g, b) = (linear(color.r), linear(color.g), linear(color.b));
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+        Self {
+            l: 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            a: 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            b: 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+        }
     }
 
+    fn distance_squared(self, other: Oklab) -> f64 {
+        // Weigh differences in hue and chroma more than differences in lightness. Otherwise, with
+        // a palette as sparse as the 16 named colors, greys end up as dark red or blue because
+        // those happen to be closer in lightness than black or grey.
+        const CHROMA_WEIGHT: f64 = 3.0;
+        (self.l - other.l).powi(2)
+            + CHROMA_WEIGHT * ((self.a - other.a).powi(2) + (self.b - other.b).powi(2))
+    }
+}
+
+/// Return the given colors in the OKLab color space.
+fn oklab_palette<const N: usize>(colors: &[u32; N]) -> [Oklab; N] {
+    colors.map(|bits| Oklab::from_rgb(Color24::from_bits(bits)))
+}
+
+/// Return the index of the color in `colors` that looks most like `color`.
+fn convert_color(color: Color24, colors: &[Oklab]) -> usize {
+    let color = Oklab::from_rgb(color);
     colors
         .iter()
+        .map(|&c| color.distance_squared(c))
         .enumerate()
-        .min_by_key(|&(_i, c)| {
-            let Color24 { r, g, b } = Color24::from_bits(*c);
-
-            squared_difference(r, color.r)
-                + squared_difference(g, color.g)
-                + squared_difference(b, color.b)
-        })
+        .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
         .expect("convert_color() called with empty color list")
         .0
 }
 
+/// The colors that terminals commonly use for the 16 named colors.
+const DEFAULT_TERM16_PALETTE: [u32; 16] = [
+    0x000000, // Black
+    0x800000, // Red
+    0x008000, // Green
+    0x808000, // Yellow
+    0x000080, // Blue
+    0x800080, // Magenta
+    0x008080, // Cyan
+    0xc0c0c0, // White
+    0x808080, // Bright Black
+    0xFF0000, // Bright Red
+    0x00FF00, // Bright Green
+    0xFFFF00, // Bright Yellow
+    0x0000FF, // Bright Blue
+    0xFF00FF, // Bright Magenta
+    0x00FFFF, // Bright Cyan
+    0xFFFFFF, // Bright White
+];
+
+/// The colors of the 16 named colors, as far as the terminal told us.
+static TERM16_PALETTE: Lazy<Mutex<[Oklab; 16]>> =
+    Lazy::new(|| Mutex::new(oklab_palette(&DEFAULT_TERM16_PALETTE)));
+
+/// Record the actual color the terminal uses for the named color with the given index, so that
+/// RGB colors are mapped to the named color that really looks closest.
+pub fn set_term16_palette_color(idx: u8, color: Color24) {
+    TERM16_PALETTE.lock().unwrap()[usize::from(idx)] = Oklab::from_rgb(color);
+}
+
 fn term16_color_for_rgb(color: Color24) -> u8 {
-    const COLORS: &[u32] = &[
-        0x000000, // Black
-        0x800000, // Red
-        0x008000, // Green
-        0x808000, // Yellow
-        0x000080, // Blue
-        0x800080, // Magenta
-        0x008080, // Cyan
-        0xc0c0c0, // White
-        0x808080, // Bright Black
-        0xFF0000, // Bright Red
-        0x00FF00, // Bright Green
-        0xFFFF00, // Bright Yellow
-        0x0000FF, // Bright Blue
-        0xFF00FF, // Bright Magenta
-        0x00FFFF, // Bright Cyan
-        0xFFFFFF, // Bright White
-    ];
+    let palette = *TERM16_PALETTE.lock().unwrap();
+    term16_color_in_palette(color, &palette)
+}
 
-    convert_color(color, COLORS).try_into().unwrap()
+fn term16_color_in_palette(color: Color24, palette: &[Oklab; 16]) -> u8 {
+    convert_color(color, palette).try_into().unwrap()
 }
 
-fn term256_color_for_rgb(color: Color24) -> u8 {
-    const COLORS: &[u32] = &[
-        0x000000, 0x00005f, 0x000087, 0x0000af, 0x0000d7, 0x0000ff, 0x005f00, 0x005f5f, 0x005f87,
-        0x005faf, 0x005fd7, 0x005fff, 0x008700, 0x00875f, 0x008787, 0x0087af, 0x0087d7, 0x0087ff,
-        0x00af00, 0x00af5f, 0x00af87, 0x00afaf, 0x00afd7, 0x00afff, 0x00d700, 0x00d75f, 0x00d787,
-        0x00d7af, 0x00d7d7, 0x00d7ff, 0x00ff00, 0x00ff5f, 0x00ff87, 0x00ffaf, 0x00ffd7, 0x00ffff,
-        0x5f0000, 0x5f005f, 0x5f0087, 0x5f00af, 0x5f00d7, 0x5f00ff, 0x5f5f00, 0x5f5f5f, 0x5f5f87,
-        0x5f5faf, 0x5f5fd7, 0x5f5fff, 0x5f8700, 0x5f875f, 0x5f8787, 0x5f87af, 0x5f87d7, 0x5f87ff,
-        0x5faf00, 0x5faf5f, 0x5faf87, 0x5fafaf, 0x5fafd7, 0x5fafff, 0x5fd700, 0x5fd75f, 0x5fd787,
-        0x5fd7af, 0x5fd7d7, 0x5fd7ff, 0x5fff00, 0x5fff5f, 0x5fff87, 0x5fffaf, 0x5fffd7, 0x5fffff,
-        0x870000, 0x87005f, 0x870087, 0x8700af, 0x8700d7, 0x8700ff, 0x875f00, 0x875f5f, 0x875f87,
-        0x875faf, 0x875fd7, 0x875fff, 0x878700, 0x87875f, 0x878787, 0x8787af, 0x8787d7, 0x8787ff,
-        0x87af00, 0x87af5f, 0x87af87, 0x87afaf, 0x87afd7, 0x87afff, 0x87d700, 0x87d75f, 0x87d787,
-        0x87d7af, 0x87d7d7, 0x87d7ff, 0x87ff00, 0x87ff5f, 0x87ff87, 0x87ffaf, 0x87ffd7, 0x87ffff,
-        0xaf0000, 0xaf005f, 0xaf0087, 0xaf00af, 0xaf00d7, 0xaf00ff, 0xaf5f00, 0xaf5f5f, 0xaf5f87,
-        0xaf5faf, 0xaf5fd7, 0xaf5fff, 0xaf8700, 0xaf875f, 0xaf8787, 0xaf87af, 0xaf87d7, 0xaf87ff,
-        0xafaf00, 0xafaf5f, 0xafaf87, 0xafafaf, 0xafafd7, 0xafafff, 0xafd700, 0xafd75f, 0xafd787,
-        0xafd7af, 0xafd7d7, 0xafd7ff, 0xafff00, 0xafff5f, 0xafff87, 0xafffaf, 0xafffd7, 0xafffff,
-        0xd70000, 0xd7005f, 0xd70087, 0xd700af, 0xd700d7, 0xd700ff, 0xd75f00, 0xd75f5f, 0xd75f87,
-        0xd75faf, 0xd75fd7, 0xd75fff, 0xd78700, 0xd7875f, 0xd78787, 0xd787af, 0xd787d7, 0xd787ff,
-        0xd7af00, 0xd7af5f, 0xd7af87, 0xd7afaf, 0xd7afd7, 0xd7afff, 0xd7d700, 0xd7d75f, 0xd7d787,
-        0xd7d7af, 0xd7d7d7, 0xd7d7ff, 0xd7ff00, 0xd7ff5f, 0xd7ff87, 0xd7ffaf, 0xd7ffd7, 0xd7ffff,
-        0xff0000, 0xff005f, 0xff0087, 0xff00af, 0xff00d7, 0xff00ff, 0xff5f00, 0xff5f5f, 0xff5f87,
-        0xff5faf, 0xff5fd7, 0xff5fff, 0xff8700, 0xff875f, 0xff8787, 0xff87af, 0xff87d7, 0xff87ff,
-        0xffaf00, 0xffaf5f, 0xffaf87, 0xffafaf, 0xffafd7, 0xffafff, 0xffd700, 0xffd75f, 0xffd787,
-        0xffd7af, 0xffd7d7, 0xffd7ff, 0xffff00, 0xffff5f, 0xffff87, 0xffffaf, 0xffffd7, 0xffffff,
-        0x080808, 0x121212, 0x1c1c1c, 0x262626, 0x303030, 0x3a3a3a, 0x444444, 0x4e4e4e, 0x585858,
-        0x626262, 0x6c6c6c, 0x767676, 0x808080, 0x8a8a8a, 0x949494, 0x9e9e9e, 0xa8a8a8, 0xb2b2b2,
-        0xbcbcbc, 0xc6c6c6, 0xd0d0d0, 0xdadada, 0xe4e4e4, 0xeeeeee,
-    ];
+/// The colors of the 256-color palette, other than the 16 named colors.
+static TERM256_PALETTE: Lazy<[Oklab; 240]> = Lazy::new(|| oklab_palette(&TERM256_COLORS));
 
-    (16 + convert_color(color, COLORS)).try_into().unwrap()
+fn term256_color_for_rgb(color: Color24) -> u8 {
+    (16 + convert_color(color, &*TERM256_PALETTE))
+        .try_into()
+        .unwrap()
 }
 
+const TERM256_COLORS: [u32; 240] = [
+    0x000000, 0x00005f, 0x000087, 0x0000af, 0x0000d7, 0x0000ff, 0x005f00, 0x005f5f, 0x005f87,
+    0x005faf, 0x005fd7, 0x005fff, 0x008700, 0x00875f, 0x008787, 0x0087af, 0x0087d7, 0x0087ff,
+    0x00af00, 0x00af5f, 0x00af87, 0x00afaf, 0x00afd7, 0x00afff, 0x00d700, 0x00d75f, 0x00d787,
+    0x00d7af, 0x00d7d7, 0x00d7ff, 0x00ff00, 0x00ff5f, 0x00ff87, 0x00ffaf, 0x00ffd7, 0x00ffff,
+    0x5f0000, 0x5f005f, 0x5f0087, 0x5f00af, 0x5f00d7, 0x5f00ff, 0x5f5f00, 0x5f5f5f, 0x5f5f87,
+    0x5f5faf, 0x5f5fd7, 0x5f5fff, 0x5f8700, 0x5f875f, 0x5f8787, 0x5f87af, 0x5f87d7, 0x5f87ff,
+    0x5faf00, 0x5faf5f, 0x5faf87, 0x5fafaf, 0x5fafd7, 0x5fafff, 0x5fd700, 0x5fd75f, 0x5fd787,
+    0x5fd7af, 0x5fd7d7, 0x5fd7ff, 0x5fff00, 0x5fff5f, 0x5fff87, 0x5fffaf, 0x5fffd7, 0x5fffff,
+    0x870000, 0x87005f, 0x870087, 0x8700af, 0x8700d7, 0x8700ff, 0x875f00, 0x875f5f, 0x875f87,
+    0x875faf, 0x875fd7, 0x875fff, 0x878700, 0x87875f, 0x878787, 0x8787af, 0x8787d7, 0x8787ff,
+    0x87af00, 0x87af5f, 0x87af87, 0x87afaf, 0x87afd7, 0x87afff, 0x87d700, 0x87d75f, 0x87d787,
+    0x87d7af, 0x87d7d7, 0x87d7ff, 0x87ff00, 0x87ff5f, 0x87ff87, 0x87ffaf, 0x87ffd7, 0x87ffff,
+    0xaf0000, 0xaf005f, 0xaf0087, 0xaf00af, 0xaf00d7, 0xaf00ff, 0xaf5f00, 0xaf5f5f, 0xaf5f87,
+    0xaf5faf, 0xaf5fd7, 0xaf5fff, 0xaf8700, 0xaf875f, 0xaf8787, 0xaf87af, 0xaf87d7, 0xaf87ff,
+    0xafaf00, 0xafaf5f, 0xafaf87, 0xafafaf, 0xafafd7, 0xafafff, 0xafd700, 0xafd75f, 0xafd787,
+    0xafd7af, 0xafd7d7, 0xafd7ff, 0xafff00, 0xafff5f, 0xafff87, 0xafffaf, 0xafffd7, 0xafffff,
+    0xd70000, 0xd7005f, 0xd70087, 0xd700af, 0xd700d7, 0xd700ff, 0xd75f00, 0xd75f5f, 0xd75f87,
+    0xd75faf, 0xd75fd7, 0xd75fff, 0xd78700, 0xd7875f, 0xd78787, 0xd787af, 0xd787d7, 0xd787ff,
+    0xd7af00, 0xd7af5f, 0xd7af87, 0xd7afaf, 0xd7afd7, 0xd7afff, 0xd7d700, 0xd7d75f, 0xd7d787,
+    0xd7d7af, 0xd7d7d7, 0xd7d7ff, 0xd7ff00, 0xd7ff5f, 0xd7ff87, 0xd7ffaf, 0xd7ffd7, 0xd7ffff,
+    0xff0000, 0xff005f, 0xff0087, 0xff00af, 0xff00d7, 0xff00ff, 0xff5f00, 0xff5f5f, 0xff5f87,
+    0xff5faf, 0xff5fd7, 0xff5fff, 0xff8700, 0xff875f, 0xff8787, 0xff87af, 0xff87d7, 0xff87ff,
+    0xffaf00, 0xffaf5f, 0xffaf87, 0xffafaf, 0xffafd7, 0xffafff, 0xffd700, 0xffd75f, 0xffd787,
+    0xffd7af, 0xffd7d7, 0xffd7ff, 0xffff00, 0xffff5f, 0xffff87, 0xffffaf, 0xffffd7, 0xffffff,
+    0x080808, 0x121212, 0x1c1c1c, 0x262626, 0x303030, 0x3a3a3a, 0x444444, 0x4e4e4e, 0x585858,
+    0x626262, 0x6c6c6c, 0x767676, 0x808080, 0x8a8a8a, 0x949494, 0x9e9e9e, 0xa8a8a8, 0xb2b2b2,
+    0xbcbcbc, 0xc6c6c6, 0xd0d0d0, 0xdadada, 0xe4e4e4, 0xeeeeee,
+];
+
 #[cfg(test)]
 mod tests {
-    use crate::color::{Color24, Flags, RgbColor, Type};
+    use crate::color::{
+        oklab_palette, term16_color_in_palette, term256_color_for_rgb, Color24, Flags, Oklab,
+        RgbColor, Type, UnderlineStyle, DEFAULT_TERM16_PALETTE,
+    };
     use crate::wchar::prelude::*;
 
     #[test]
@@ -452,6 +658,59 @@ mod tests {
         assert!(RgbColor::from_wstr(L!("FAF")) == Some(RgbColor::from_rgb(0xff, 0xaa, 0xff)));
     }
 
+    #[test]
+    fn underline_styles() {
+        let mut color = RgbColor::NORMAL;
+        assert_eq!(color.underline_style(), None);
+        color.set_underline_style(Some(UnderlineStyle::Curly));
+        assert!(color.is_underline());
+        // Turning on underlining keeps the style.
+        color.set_underline(true);
+        assert_eq!(color.underline_style(), Some(UnderlineStyle::Curly));
+
+        let mut other = RgbColor::from_wstr(L!("red")).unwrap();
+        other.set_underline(true);
+        other.set_strikethrough(true);
+        other.set_underline_color(RgbColor::from_wstr(L!("blue")).unwrap());
+        color.add_modifiers(other);
+        assert_eq!(color.underline_style(), Some(UnderlineStyle::Curly));
+        assert!(color.is_strikethrough());
+        assert_eq!(color.underline_color(), RgbColor::from_wstr(L!("blue")));
+
+        color.set_underline(false);
+        assert!(!color.is_underline());
+    }
+
+    #[test]
+    fn test_perceptual_color_matching() {
+        let rgb = |bits| Color24::from_bits(bits);
+        let default_palette = oklab_palette(&DEFAULT_TERM16_PALETTE);
+        // Every palette color maps to itself.
+        for (idx, &bits) in DEFAULT_TERM16_PALETTE.iter().enumerate() {
+            assert_eq!(
+                usize::from(term16_color_in_palette(rgb(bits), &default_palette)),
+                idx
+            );
+        }
+        // Greys stay grey instead of picking up a hue.
+        assert_eq!(term16_color_in_palette(rgb(0x202020), &default_palette), 0);
+        assert_eq!(term16_color_in_palette(rgb(0x606060), &default_palette), 8);
+        // Brown is dark red, not olive.
+        assert_eq!(term16_color_in_palette(rgb(0x8b4513), &default_palette), 1);
+        // A saturated blue stays blue rather than turning cyan.
+        assert_eq!(term16_color_in_palette(rgb(0x005fd7), &default_palette), 12);
+
+        // The terminal's actual palette is taken into account.
+        let mut palette = default_palette;
+        palette[4] = Oklab::from_rgb(rgb(0x3465a4));
+        assert_eq!(term16_color_in_palette(rgb(0x3060a0), &default_palette), 6);
+        assert_eq!(term16_color_in_palette(rgb(0x3060a0), &palette), 4);
+
+        // The 256-color cube and grey ramp have exact matches.
+        assert_eq!(term256_color_for_rgb(rgb(0x5f87af)), 67);
+        assert_eq!(term256_color_for_rgb(rgb(0x808080)), 244);
+    }
+
     // Regression test for multiplicative overflow in convert_color.
     #[test]
     fn test_term16_color_for_rgb() {
@@ -459,6 +718,7 @@ mod tests {
             let color = RgbColor {
                 typ: Type::Rgb(Color24 { r: c, g: c, b: c }),
                 flags: Flags::DEFAULT,
+                underline_color: Type::None,
             };
             let _ = color.to_name_index();
         }
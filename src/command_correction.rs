@@ -0,0 +1,202 @@
+//! Suggestions for mistyped commands.
+//!
+//! When a command is not found, we look for a known name that is only a few keystrokes away. Edits
+//! are weighted so that typical typing mistakes, like hitting a neighboring key or swapping two
+//! characters, count less than arbitrary ones.
+
+use crate::abbrs::with_abbrs;
+use crate::builtins::shared::builtin_get_names;
+use crate::env::Environment;
+use crate::function;
+use crate::wchar::prelude::*;
+use crate::wutil::dir_iter::DirIter;
+use crate::wutil::waccess;
+
+/// The cost of inserting or deleting a character, or replacing it with an unrelated one.
+const EDIT_COST: usize = 2;
+
+/// The cost of a likely typo: a neighboring key, a different case, or two swapped characters.
+const TYPO_COST: usize = 1;
+
+/// The rows of a QWERTY keyboard, each shifted half a key to the right of the one above.
+const KEYBOARD_ROWS: [&str; 4] = ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"];
+
+/// Return the row and column of a character on the keyboard.
+fn key_position(c: char) -> Option<(usize, usize)> {
+    let c = c.to_ascii_lowercase();
+    KEYBOARD_ROWS
+        .iter()
+        .enumerate()
+        .find_map(|(row, keys)| keys.chars().position(|k| k == c).map(|col| (row, col)))
+}
+
+/// Return whether two characters are on neighboring keys.
+fn keys_are_adjacent(a: char, b: char) -> bool {
+    let (Some((row_a, col_a)), Some((row_b, col_b))) = (key_position(a), key_position(b)) else {
+        return false;
+    };
+    if row_a == row_b {
+        return col_a.abs_diff(col_b) == 1;
+    }
+    // A key touches the key below it, and the one to the left of that.
+    let ((upper_col, lower_col), rows_apart) = if row_a < row_b {
+        ((col_a, col_b), row_b - row_a)
+    } else {
+        ((col_b, col_a), row_a - row_b)
+    };
+    rows_apart == 1 && (lower_col == upper_col || lower_col + 1 == upper_col)
+}
+
+/// Return the cost of replacing `a` with `b`.
+fn substitution_cost(a: char, b: char) -> usize {
+    if a == b {
+        0
+    } else if a.to_lowercase().eq(b.to_lowercase()) || keys_are_adjacent(a, b) {
+        TYPO_COST
+    } else {
+        EDIT_COST
+    }
+}
+
+/// Return the weighted edit distance between two strings. This is the optimal string alignment
+/// distance, where swapping two adjacent characters is a single edit.
+pub fn correction_distance(typed: &wstr, candidate: &wstr) -> usize {
+    let a = typed.as_char_slice();
+    let b = candidate.as_char_slice();
+    // dist[i][j] is the distance between the first i characters of a and the first j of b.
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i * EDIT_COST;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j * EDIT_COST;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let mut cost = (dist[i - 1][j] + EDIT_COST)
+                .min(dist[i][j - 1] + EDIT_COST)
+                .min(dist[i - 1][j - 1] + substitution_cost(a[i - 1], b[j - 1]));
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cost = cost.min(dist[i - 2][j - 2] + TYPO_COST);
+            }
+            dist[i][j] = cost;
+        }
+    }
+    dist[a.len()][b.len()]
+}
+
+/// Return the largest distance at which a name is still offered as a correction for `typed`.
+/// Longer names tolerate more mistakes.
+fn max_distance(typed: &wstr) -> usize {
+    EDIT_COST * (1 + typed.len() / 5)
+}
+
+/// The best correction found so far.
+struct Best {
+    name: WString,
+    distance: usize,
+}
+
+impl Best {
+    /// Return whether `name` at `distance` would be a better match than what we have.
+    fn is_improved_by(&self, name: &wstr, distance: usize) -> bool {
+        (distance, name) < (self.distance, self.name.as_utfstr())
+    }
+}
+
+/// Return the most likely intended name for the command `typed`, which was not found.
+/// Builtins, functions, abbreviations and executables in $PATH are considered.
+pub fn command_correction(typed: &wstr, vars: &dyn Environment) -> Option<WString> {
+    // Paths and anything that is expanded are not simple typos.
+    if typed.is_empty()
+        || typed
+            .chars()
+            .any(|c| !c.is_alphanumeric() && !"-_.+".contains(c))
+    {
+        return None;
+    }
+
+    let max_distance = max_distance(typed);
+    let mut best = Best {
+        name: WString::new(),
+        distance: max_distance + 1,
+    };
+    let within_reach =
+        |name: &wstr| name.len().abs_diff(typed.len()) * EDIT_COST <= max_distance && name != typed;
+    let consider = |best: &mut Best, name: &wstr| {
+        if !within_reach(name) {
+            return;
+        }
+        let distance = correction_distance(typed, name);
+        if best.is_improved_by(name, distance) {
+            best.name = name.to_owned();
+            best.distance = distance;
+        }
+    };
+
+    for name in builtin_get_names() {
+        consider(&mut best, name);
+    }
+    for name in function::get_names(false) {
+        consider(&mut best, &name);
+    }
+    let abbr_names: Vec<WString> = with_abbrs(|set| {
+        set.list()
+            .iter()
+            .filter(|abbr| abbr.regex.is_none())
+            .map(|abbr| abbr.key.clone())
+            .collect()
+    });
+    for name in &abbr_names {
+        consider(&mut best, name);
+    }
+
+    if let Some(path_var) = vars.get(L!("PATH")) {
+        for dir_name in path_var.as_list() {
+            let Ok(mut dir) = DirIter::new(dir_name) else {
+                continue;
+            };
+            while let Some(entry) = dir.next() {
+                let Ok(entry) = entry else { continue };
+                if !within_reach(&entry.name) {
+                    continue;
+                }
+                // Only check that the file is executable if it would win.
+                let distance = correction_distance(typed, &entry.name);
+                if !best.is_improved_by(&entry.name, distance) || entry.is_dir() {
+                    continue;
+                }
+                let path = dir_name.clone() + L!("/") + entry.name.as_utfstr();
+                if waccess(&path, libc::X_OK) == 0 {
+                    best.name = entry.name.clone();
+                    best.distance = distance;
+                }
+            }
+        }
+    }
+
+    (best.distance <= max_distance).then_some(best.name)
+}
+
+#[test]
+fn test_correction_distance() {
+    assert_eq!(correction_distance(L!("git"), L!("git")), 0);
+    // Swapped characters.
+    assert_eq!(correction_distance(L!("gti"), L!("git")), TYPO_COST);
+    assert_eq!(correction_distance(L!("sl"), L!("ls")), TYPO_COST);
+    // Neighboring keys count less than others.
+    assert_eq!(correction_distance(L!("vum"), L!("vim")), TYPO_COST);
+    assert_eq!(correction_distance(L!("vpm"), L!("vim")), EDIT_COST);
+    assert_eq!(correction_distance(L!("Make"), L!("make")), TYPO_COST);
+    // Missing and extra characters.
+    assert_eq!(correction_distance(L!("gi"), L!("git")), EDIT_COST);
+    assert_eq!(correction_distance(L!("gitt"), L!("git")), EDIT_COST);
+    assert_eq!(correction_distance(L!("abc"), L!("xyz")), 3 * EDIT_COST);
+
+    assert!(keys_are_adjacent('a', 'q'));
+    assert!(keys_are_adjacent('a', 'w'));
+    assert!(keys_are_adjacent('w', 'a'));
+    assert!(!keys_are_adjacent('a', 'e'));
+    assert!(!keys_are_adjacent('a', 'x'));
+    assert!(keys_are_adjacent('s', 'x'));
+}
@@ -82,6 +82,8 @@ pub enum EscapeStringStyle {
     Url,
     Var,
     Regex,
+    /// A JSON string literal, including the quotes.
+    Json,
 }
 
 impl Default for EscapeStringStyle {
@@ -177,6 +179,7 @@ pub fn escape_string(s: &wstr, style: EscapeStringStyle) -> WString {
         EscapeStringStyle::Url => escape_string_url(s),
         EscapeStringStyle::Var => escape_string_var(s),
         EscapeStringStyle::Regex => escape_string_pcre2(s),
+        EscapeStringStyle::Json => escape_string_json(s),
     }
 }
 
@@ -411,6 +414,29 @@ fn escape_string_url(input: &wstr) -> WString {
     out
 }
 
+/// Escape a string as a JSON string literal, for machine-readable output.
+/// JSON strings cannot hold bytes that are not valid UTF-8, so these become U+FFFD.
+fn escape_string_json(input: &wstr) -> WString {
+    let mut out = WString::with_capacity(input.len() + 2);
+    out.push('"');
+    for c in input.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if u32::from(c) < 0x20 || c == '\x7f' => {
+                out.push_utfstr(&sprintf!("\\u%04x", u32::from(c)));
+            }
+            c if (ENCODE_DIRECT_BASE..ENCODE_DIRECT_END).contains(&c) => out.push_str("\\ufffd"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 /// Escape a string in a fashion suitable for using as a fish var name. Store the result in out_str.
 fn escape_string_var(input: &wstr) -> WString {
     let mut prev_was_hex_encoded = false;
@@ -2,6 +2,7 @@
 use crate::future::IsSomeAnd;
 use crate::highlight::HighlightSpec;
 use crate::wchar::prelude::*;
+use std::collections::VecDeque;
 
 /// An edit action that can be undone.
 #[derive(Clone, Eq, PartialEq)]
@@ -89,6 +90,71 @@ impl UndoHistory {
     }
 }
 
+/// The maximum number of drafts we remember.
+const DRAFT_RING_MAX: usize = 64;
+
+/// Command lines which were discarded without being executed, e.g. by cancel-commandline or by
+/// recalling a line from history. They live for the whole session, so a lost draft can be brought
+/// back later.
+#[derive(Clone, Default)]
+pub struct DraftRing {
+    /// The drafts, most recent first.
+    drafts: VecDeque<WString>,
+    /// The index of the draft that was restored last, if we are cycling through drafts.
+    cycle_index: Option<usize>,
+}
+
+impl DraftRing {
+    /// Remember a discarded command line. Empty lines are ignored, and an existing copy of the same
+    /// text is moved to the front.
+    pub fn add(&mut self, draft: &wstr) {
+        self.cycle_index = None;
+        if draft.is_empty() {
+            return;
+        }
+        if let Some(idx) = self.drafts.iter().position(|d| d == draft) {
+            self.drafts.remove(idx);
+        }
+        self.drafts.push_front(draft.to_owned());
+        self.drafts.truncate(DRAFT_RING_MAX);
+    }
+
+    /// Return the draft to restore in place of the command line `current`, which is remembered as
+    /// the most recent draft first so it is not lost. Consecutive calls without an intervening
+    /// [`Self::reset_cycle`] return successively older drafts, wrapping around at the oldest.
+    pub fn next(&mut self, current: &wstr) -> Option<&wstr> {
+        if self.cycle_index.is_none() && !current.is_empty() {
+            self.add(current);
+            if self.drafts.len() == 1 {
+                // The current line is the only draft, so there is nothing else to restore.
+                return None;
+            }
+            self.cycle_index = Some(0);
+        }
+        if self.drafts.is_empty() {
+            return None;
+        }
+        let idx = self
+            .cycle_index
+            .map_or(0, |idx| (idx + 1) % self.drafts.len());
+        self.cycle_index = Some(idx);
+        Some(&self.drafts[idx])
+    }
+
+    /// Start the next [`Self::next`] at the most recent draft again.
+    pub fn reset_cycle(&mut self) {
+        self.cycle_index = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.drafts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.drafts.is_empty()
+    }
+}
+
 /// Helper class for storing a command line.
 #[derive(Clone, Default)]
 pub struct EditableLine {
@@ -5,7 +5,11 @@ use crate::env::{setenv_lock, unsetenv_lock, EnvMode, EnvStack, Environment};
 use crate::env::{CURSES_INITIALIZED, READ_BYTE_LIMIT, TERM_HAS_XN};
 use crate::flog::FLOG;
 use crate::function;
-use crate::input_common::{update_wait_on_escape_ms, update_wait_on_sequence_key_ms};
+use crate::highlight::highlight_invalidate_cache;
+use crate::input_common::{
+    update_mouse_tracking, update_sequence_hint_delay_ms, update_wait_on_escape_ms,
+    update_wait_on_sequence_key_ms,
+};
 use crate::output::ColorSupport;
 use crate::proc::is_interactive_session;
 use crate::reader::{
@@ -14,6 +18,7 @@ use crate::reader::{
 };
 use crate::screen::screen_set_midnight_commander_hack;
 use crate::screen::LAYOUT_CACHE_SHARED;
+use crate::terminal::{terminal_report_working_directory, terminal_supports, TerminalFeature};
 use crate::wchar::prelude::*;
 use crate::wutil::fish_wcstoi;
 use std::borrow::Cow;
@@ -61,6 +66,11 @@ static VAR_DISPATCH_TABLE: once_cell::sync::Lazy<VarDispatchTable> =
             L!("fish_sequence_key_delay_ms"),
             update_wait_on_sequence_key_ms,
         );
+        table.add_anon(
+            L!("fish_sequence_hint_delay_ms"),
+            update_sequence_hint_delay_ms,
+        );
+        table.add_anon(L!("fish_mouse"), update_mouse_tracking);
         table.add_anon(L!("fish_emoji_width"), guess_emoji_width);
         table.add_anon(L!("fish_ambiguous_width"), handle_change_ambiguous_width);
         table.add_anon(L!("LINES"), handle_term_size_change);
@@ -69,6 +79,10 @@ static VAR_DISPATCH_TABLE: once_cell::sync::Lazy<VarDispatchTable> =
         table.add_anon(L!("fish_function_path"), handle_function_path_change);
         table.add_anon(L!("fish_read_limit"), handle_read_limit_change);
         table.add_anon(L!("fish_history"), handle_fish_history_change);
+        table.add_anon(
+            L!("fish_shared_killring"),
+            handle_fish_shared_killring_change,
+        );
         table.add_anon(
             L!("fish_autosuggestion_enabled"),
             handle_autosuggestion_change,
@@ -78,6 +92,7 @@ static VAR_DISPATCH_TABLE: once_cell::sync::Lazy<VarDispatchTable> =
             handle_fish_use_posix_spawn_change,
         );
         table.add_anon(L!("fish_trace"), handle_fish_trace);
+        table.add_anon(L!("PWD"), handle_pwd_change);
         table.add_anon(
             L!("fish_cursor_selection_mode"),
             handle_fish_cursor_selection_mode_change,
@@ -211,6 +226,9 @@ pub fn env_dispatch_var_change(key: &wstr, vars: &EnvStack) {
 
     // We want to ignore variable changes until the dispatch table is explicitly initialized.
     if let Some(dispatch_table) = Lazy::get(&VAR_DISPATCH_TABLE) {
+        // Any variable may change how the commandline is highlighted, like $PATH, or one that an
+        // argument expands.
+        highlight_invalidate_cache();
         dispatch_table.dispatch(key, vars);
     }
 }
@@ -220,6 +238,12 @@ fn handle_fish_term_change(vars: &EnvStack) {
     reader_schedule_prompt_repaint();
 }
 
+/// Called when the terminal has told us what it supports.
+pub fn env_dispatch_terminal_probed() {
+    crate::theme::theme_apply_pending(EnvStack::principal());
+    handle_fish_term_change(EnvStack::principal());
+}
+
 fn handle_change_ambiguous_width(vars: &EnvStack) {
     let new_width = vars
         .get(L!("fish_ambiguous_width"))
@@ -242,6 +266,14 @@ fn handle_fish_history_change(vars: &EnvStack) {
     reader_change_history(&session_id);
 }
 
+fn handle_fish_shared_killring_change(vars: &EnvStack) {
+    let shared = vars
+        .get_unless_empty(L!("fish_shared_killring"))
+        .map(|v| crate::wcstringutil::bool_from_string(&v.as_string()))
+        .unwrap_or(false);
+    crate::kill::kill_set_shared(shared);
+}
+
 fn handle_fish_cursor_selection_mode_change(vars: &EnvStack) {
     use crate::reader::CursorSelectionMode;
 
@@ -356,6 +388,12 @@ fn handle_fish_trace(vars: &EnvStack) {
     crate::trace::trace_set_enabled(enabled);
 }
 
+fn handle_pwd_change(vars: &EnvStack) {
+    if is_interactive_session() {
+        terminal_report_working_directory(vars);
+    }
+}
+
 pub fn env_dispatch_init(vars: &EnvStack) {
     use once_cell::sync::Lazy;
 
@@ -372,9 +410,12 @@ fn run_inits(vars: &EnvStack) {
     guess_emoji_width(vars);
     update_wait_on_escape_ms(vars);
     update_wait_on_sequence_key_ms(vars);
+    update_sequence_hint_delay_ms(vars);
+    update_mouse_tracking(vars);
     handle_read_limit_change(vars);
     handle_fish_use_posix_spawn_change(vars);
     handle_fish_trace(vars);
+    handle_fish_shared_killring_change(vars);
 }
 
 /// Updates our idea of whether we support term256 and term24bit (see issue #10222).
@@ -439,6 +480,13 @@ fn update_fish_color_support(vars: &EnvStack) {
             term_support,
             "True-color support: disabled for eterm/screen"
         );
+    } else if terminal_supports(TerminalFeature::Truecolor) {
+        // The terminal told us.
+        supports_24bit = true;
+        FLOG!(
+            term_support,
+            "True-color support: enabled per terminal query"
+        );
     } else if max_colors.unwrap_or(0) > 32767 {
         // $TERM wins, xterm-direct reports 32767 colors and we assume that's the minimum as xterm
         // is weird when it comes to color.
@@ -590,6 +590,12 @@ pub fn fire_delayed(parser: &Parser) {
     }
 }
 
+/// Enqueue an event, to be fired along with the delayed ones. This is for code that has no parser
+/// at hand.
+pub fn enqueue(event: Event) {
+    BLOCKED_EVENTS.lock().expect("Mutex poisoned!").push(event);
+}
+
 /// Enqueue a signal event. Invoked from a signal handler.
 pub fn enqueue_signal(signal: libc::c_int) {
     // Beware, we are in a signal handler
@@ -9,6 +9,7 @@ use crate::complete::complete_get_wrap_targets;
 use crate::env::{EnvStack, Environment};
 use crate::event::{self, EventDescription};
 use crate::global_safety::RelaxedAtomicBool;
+use crate::highlight::highlight_invalidate_cache;
 use crate::parse_tree::NodeRef;
 use crate::parser::Parser;
 use crate::parser_keywords::parser_keywords_is_reserved;
@@ -200,6 +201,7 @@ pub fn add(name: WString, props: Arc<FunctionProperties>) {
         existing.is_none(),
         "Function should not already be present in the table"
     );
+    highlight_invalidate_cache();
 }
 
 /// Return the properties for a function, or None. This does not trigger autoloading.
@@ -251,6 +253,7 @@ pub fn remove(name: &wstr) {
     funcset.remove(name);
     // Prevent (re-)autoloading this function.
     funcset.autoload_tombstones.insert(name.to_owned());
+    highlight_invalidate_cache();
 }
 
 // Return the body of a function (everything after the header, up to but not including the 'end').
@@ -313,6 +316,7 @@ pub fn copy(name: &wstr, new_name: WString, parser: &Parser) -> bool {
     // Note this will NOT overwrite an existing function with the new name.
     // TODO: rationalize if this behavior is desired.
     funcset.funcs.entry(new_name).or_insert(Arc::new(new_props));
+    highlight_invalidate_cache();
     return true;
 }
 
@@ -339,6 +343,7 @@ pub fn invalidate_path() {
     let mut funcset = FUNCTION_SET.lock().unwrap();
     funcset.funcs.retain(|_, props| !props.is_autoload.load());
     funcset.autoloader.clear();
+    highlight_invalidate_cache();
 }
 
 impl FunctionProperties {
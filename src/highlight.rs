@@ -1,16 +1,18 @@
 //! Functions for syntax highlighting.
 use crate::abbrs::{self, with_abbrs};
 use crate::ast::{
-    self, Argument, Ast, BlockStatement, BlockStatementHeaderVariant, DecoratedStatement, Keyword,
-    Leaf, List, Node, NodeVisitor, Redirection, Token, Type, VariableAssignment,
+    self, Argument, Ast, BlockStatement, BlockStatementHeaderVariant, DecoratedStatement,
+    IfStatement, JobConjunction, Keyword, Leaf, List, Node, NodeVisitor, Redirection,
+    SwitchStatement, Token, Type, VariableAssignment,
 };
 use crate::builtins::shared::builtin_exists;
 use crate::color::RgbColor;
+use crate::command_correction::command_correction;
 use crate::common::{
     unescape_string_in_place, valid_var_name, valid_var_name_char, UnescapeFlags, ASCII_MAX,
     EXPAND_RESERVED_BASE, EXPAND_RESERVED_END,
 };
-use crate::env::Environment;
+use crate::env::{ElectricVar, Environment};
 use crate::expand::{
     expand_one, expand_tilde, expand_to_command_and_args, ExpandFlags, ExpandResultCode,
     HOME_DIRECTORY, PROCESS_EXPAND_SELF_STR,
@@ -29,7 +31,7 @@ use crate::parse_constants::{
     ParseKeyword, ParseTokenType, ParseTreeFlags, SourceRange, StatementDecoration,
 };
 use crate::parse_util::{
-    parse_util_locate_cmdsubst_range, parse_util_slice_length, MaybeParentheses,
+    parse_util_locate_cmdsubst_range, parse_util_slice_length, MaybeParentheses, Parentheses,
 };
 use crate::path::{
     path_apply_working_directory, path_as_implicit_cd, path_get_cdpath, path_get_path,
@@ -37,7 +39,7 @@ use crate::path::{
 };
 use crate::redirection::RedirectionMode;
 use crate::threads::assert_is_background_thread;
-use crate::tokenizer::{variable_assignment_equals_pos, PipeOrRedir};
+use crate::tokenizer::{quote_end, variable_assignment_equals_pos, PipeOrRedir};
 use crate::wchar::{wstr, WString, L};
 use crate::wchar_ext::WExt;
 use crate::wcstringutil::{
@@ -50,9 +52,12 @@ use crate::wutil::{normalize_path, waccess, wstat};
 use crate::wutil::{wbasename, wdirname};
 use bitflags::bitflags;
 use libc::{ENOENT, PATH_MAX, R_OK, W_OK};
-use std::collections::hash_map::Entry;
+use std::collections::hash_map::{DefaultHasher, Entry};
 use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
 
 impl HighlightSpec {
     pub fn new() -> Self {
@@ -115,6 +120,295 @@ pub fn highlight_shell(
     *color = highlighter.highlight();
 }
 
+/// Like highlight_shell(), but reuse the colors of statements and the results of filesystem checks
+/// from earlier passes that are remembered in `cache`, and remember new ones there. This keeps
+/// editing a large commandline responsive, because only the statements that changed are colored
+/// again, and only the tokens that changed need I/O. The commandline is still parsed in full, which
+/// is cheap next to the rest. The brackets, quotes and block keywords that belong together are
+/// stored in `matching_pairs`.
+pub fn highlight_shell_with_cache(
+    buff: &wstr,
+    color: &mut Vec<HighlightSpec>,
+    matching_pairs: &mut MatchingPairs,
+    ctx: &OperationContext<'_>,
+    io_ok: bool,
+    cursor: Option<usize>,
+    cache: &Mutex<HighlightCache>,
+) {
+    let working_directory = ctx.vars().get_pwd_slash();
+    {
+        // The results are relative to the working directory, and to the functions, abbreviations
+        // and variables at the time.
+        let mut cache = cache.lock().unwrap();
+        let generation = HIGHLIGHT_CACHE_GENERATION.load(Ordering::Relaxed);
+        if cache.working_directory != working_directory || cache.generation != generation {
+            cache.clear();
+            cache.working_directory = working_directory.clone();
+            cache.generation = generation;
+        }
+        // Only keep the statements of the last pass, which this one is likely to share.
+        cache.old_statements = std::mem::take(&mut cache.statements);
+    }
+    let mut highlighter = Highlighter::new(buff, cursor, ctx, working_directory, io_ok);
+    highlighter.cache = Some(cache);
+    *color = highlighter.highlight();
+    highlighter.matching_pairs.text = buff.to_owned();
+    *matching_pairs = highlighter.matching_pairs;
+}
+
+/// Return the likely correction of the unknown command `cmd`, like [`command_correction`], but
+/// remember it in `cache`. Finding it lists $PATH, so this must be called in the background.
+pub fn command_correction_with_cache(
+    cmd: &wstr,
+    vars: &dyn Environment,
+    cache: &Mutex<HighlightCache>,
+) -> Option<WString> {
+    assert_is_background_thread();
+    let generation = {
+        let cache = cache.lock().unwrap();
+        if let Some(correction) = cache.corrections.get(cmd) {
+            return correction.clone();
+        }
+        cache.generation
+    };
+    let correction = command_correction(cmd, vars);
+    let mut cache = cache.lock().unwrap();
+    // Don't remember it if the commands changed in the meantime.
+    if cache.generation == generation
+        && HIGHLIGHT_CACHE_GENERATION.load(Ordering::Relaxed) == generation
+    {
+        cache.corrections.insert(cmd.to_owned(), correction.clone());
+    }
+    correction
+}
+
+/// Counts changes to what a HighlightCache remembers results about, other than the filesystem.
+static HIGHLIGHT_CACHE_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// Make highlighting forget its results, because functions, abbreviations or variables changed,
+/// like when tab completion autoloads a function.
+pub fn highlight_invalidate_cache() {
+    HIGHLIGHT_CACHE_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The results of the filesystem checks done while highlighting, kept between highlighting passes
+/// of the same commandline. Running a command may change the filesystem, so this should be cleared
+/// before reading the next commandline.
+#[derive(Default)]
+pub struct HighlightCache {
+    /// The working directory the results are relative to.
+    working_directory: WString,
+    /// The value of HIGHLIGHT_CACHE_GENERATION the results are from.
+    generation: u32,
+    /// Whether an argument is a valid path, by its source and whether the cursor is at it.
+    paths: HashMap<(WString, bool), bool>,
+    /// Whether an argument to cd is a valid directory, by its expansion and whether the cursor is
+    /// at it.
+    cd_paths: HashMap<(WString, bool), bool>,
+    /// The role of a command, by its expansion and decoration.
+    commands: HashMap<(WString, StatementDecoration), Option<HighlightRole>>,
+    /// The likely correction of an unknown command, by the command.
+    corrections: HashMap<WString, Option<WString>>,
+    /// The statements colored by the current pass.
+    statements: HashMap<StatementKey, HighlightedStatement>,
+    /// The statements colored by the previous pass.
+    old_statements: HashMap<StatementKey, HighlightedStatement>,
+}
+
+impl HighlightCache {
+    /// Forget all results.
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.cd_paths.clear();
+        self.commands.clear();
+        self.corrections.clear();
+        self.statements.clear();
+        self.old_statements.clear();
+    }
+}
+
+/// What the colors of a statement (a job conjunction) depend on.
+#[derive(PartialEq, Eq, Hash)]
+struct StatementKey {
+    /// The source of the statement.
+    source: WString,
+    /// The position of the cursor relative to the statement, if it is in it.
+    cursor: Option<usize>,
+    /// Whether I/O was allowed.
+    io_ok: bool,
+    /// A hash of the variables the commandline defines before the statement, and whether it is in
+    /// a function.
+    state: u64,
+}
+
+/// The colors of a statement, and what it changes for the statements after it.
+struct HighlightedStatement {
+    colors: ColorArray,
+    pending_variables: Vec<WString>,
+    assigned_variables: Vec<WString>,
+    runs_argparse: bool,
+    /// The pairs in the statement, relative to it.
+    matching_pairs: MatchingPairs,
+}
+
+/// The brackets, quotes and block keywords of a commandline that belong together.
+#[derive(Clone, Debug, Default)]
+pub struct MatchingPairs {
+    /// The commandline these are for.
+    pub text: WString,
+    /// Matching brackets and quotes, as (opening, closing) positions.
+    delimiters: Vec<(usize, usize)>,
+    /// The keywords of each block, like "if", "else" and "end".
+    keyword_groups: Vec<Vec<SourceRange>>,
+}
+
+impl MatchingPairs {
+    /// Find the pairs in `buff`. This parses it, so it belongs on a background thread. When
+    /// highlighting anyway, use the pairs from highlight_shell_with_cache() instead.
+    pub fn new(buff: &wstr) -> Self {
+        let ctx = OperationContext::empty();
+        let mut highlighter = Highlighter::new(buff, None, &ctx, WString::new(), false);
+        highlighter.highlight();
+        highlighter.matching_pairs.text = buff.to_owned();
+        highlighter.matching_pairs
+    }
+
+    /// Return how many delimiters and keyword groups there are.
+    fn counts(&self) -> (usize, usize) {
+        (self.delimiters.len(), self.keyword_groups.len())
+    }
+
+    /// Return the pairs added since there were `counts` of them, moved back by `offset`.
+    fn since(&self, counts: (usize, usize), offset: usize) -> MatchingPairs {
+        MatchingPairs {
+            delimiters: self.delimiters[counts.0..]
+                .iter()
+                .map(|&(open, close)| (open - offset, close - offset))
+                .collect(),
+            keyword_groups: self.keyword_groups[counts.1..]
+                .iter()
+                .map(|group| {
+                    group
+                        .iter()
+                        .map(|range| SourceRange::new(range.start() - offset, range.length()))
+                        .collect()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Add the pairs from `other`, moved by `offset`.
+    fn extend_moved(&mut self, other: &MatchingPairs, offset: usize) {
+        self.delimiters.extend(
+            other
+                .delimiters
+                .iter()
+                .map(|&(open, close)| (open + offset, close + offset)),
+        );
+        self.keyword_groups
+            .extend(other.keyword_groups.iter().map(|group| {
+                group
+                    .iter()
+                    .map(|range| SourceRange::new(range.start() + offset, range.length()))
+                    .collect()
+            }));
+    }
+
+    /// Add the keywords of a block, like "if", "else" and "end", that are there.
+    fn add_keyword_group(&mut self, keywords: &[&dyn Node]) {
+        let group: Vec<SourceRange> = keywords
+            .iter()
+            .filter_map(|kw| kw.try_source_range())
+            .filter(|range| range.length() > 0)
+            .collect();
+        // An unterminated block has nothing to match yet.
+        if group.len() > 1 {
+            self.keyword_groups.push(group);
+        }
+    }
+
+    /// Add the quotes, slice brackets and braces in `token`, which starts at `start`, skipping over
+    /// its command substitutions.
+    fn add_token_delimiters(&mut self, token: &wstr, start: usize, cmdsubs: &[Parentheses]) {
+        let cmdsub_end = |pos: usize| {
+            cmdsubs
+                .iter()
+                .find(|parens| parens.start() == pos)
+                .map(|parens| parens.end())
+        };
+        let mut braces = vec![];
+        let mut i = 0;
+        while i < token.len() {
+            if let Some(end) = cmdsub_end(i) {
+                i = end;
+                continue;
+            }
+            let c = token.char_at(i);
+            match c {
+                '\\' => i += 1,
+                '\'' | '"' => {
+                    // Command substitutions interrupt double quotes.
+                    let mut pos = i;
+                    let close = loop {
+                        match quote_end(token, pos, c) {
+                            Some(end) if token.char_at(end) == c => break Some(end),
+                            Some(dollar) => match cmdsub_end(dollar + 1) {
+                                Some(end) => pos = end - 1,
+                                None => break None,
+                            },
+                            None => break None,
+                        }
+                    };
+                    // An unterminated quote runs to the end of the token.
+                    let Some(close) = close else {
+                        break;
+                    };
+                    self.delimiters.push((start + i, start + close));
+                    i = close;
+                }
+                '[' => {
+                    if let Some(len) = parse_util_slice_length(&token[i..]).filter(|&len| len > 0) {
+                        self.delimiters.push((start + i, start + i + len - 1));
+                    }
+                }
+                '{' => braces.push(i),
+                '}' => {
+                    if let Some(open) = braces.pop() {
+                        self.delimiters.push((start + open, start + i));
+                    }
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+
+    /// Return the ranges of the bracket, quote or block keyword at the cursor, or else just before
+    /// it, together with the ranges of its partners, like the closing parenthesis, or the "else"
+    /// and "end" of an "if". Return an empty list if there is nothing to match there.
+    pub fn at(&self, cursor: usize) -> Vec<SourceRange> {
+        for pos in [Some(cursor), cursor.checked_sub(1)].into_iter().flatten() {
+            if pos >= self.text.len() {
+                continue;
+            }
+            if let Some(&(open, close)) =
+                self.delimiters.iter().find(|(o, c)| *o == pos || *c == pos)
+            {
+                return vec![SourceRange::new(open, 1), SourceRange::new(close, 1)];
+            }
+            if let Some(group) = self
+                .keyword_groups
+                .iter()
+                .find(|group| group.iter().any(|r| r.start() <= pos && pos < r.end()))
+            {
+                return group.clone();
+            }
+        }
+        vec![]
+    }
+}
+
 /// highlight_color_resolver_t resolves highlight specs (like "a command") to actual RGB colors.
 /// It maintains a cache with no invalidation mechanism. The lifetime of these should typically be
 /// one screen redraw.
@@ -181,28 +475,10 @@ impl HighlightColorResolver {
                 } else if !result2.is_normal() {
                     // Valid path has an actual color, use it and merge the modifiers.
                     let mut rescol = result2;
-                    rescol.set_bold(result.is_bold() || result2.is_bold());
-                    rescol.set_underline(result.is_underline() || result2.is_underline());
-                    rescol.set_italics(result.is_italics() || result2.is_italics());
-                    rescol.set_dim(result.is_dim() || result2.is_dim());
-                    rescol.set_reverse(result.is_reverse() || result2.is_reverse());
+                    rescol.add_modifiers(result);
                     result = rescol;
                 } else {
-                    if result2.is_bold() {
-                        result.set_bold(true)
-                    };
-                    if result2.is_underline() {
-                        result.set_underline(true)
-                    };
-                    if result2.is_italics() {
-                        result.set_italics(true)
-                    };
-                    if result2.is_dim() {
-                        result.set_dim(true)
-                    };
-                    if result2.is_reverse() {
-                        result.set_reverse(true)
-                    };
+                    result.add_modifiers(result2);
                 }
             }
         }
@@ -215,12 +491,14 @@ impl HighlightColorResolver {
     }
 }
 
-fn command_is_valid(
+/// Return the role to highlight `cmd` with, depending on what kind of command it is, or None if it
+/// is not a valid command.
+pub fn command_role(
     cmd: &wstr,
     decoration: StatementDecoration,
     working_directory: &wstr,
     vars: &dyn Environment,
-) -> bool {
+) -> Option<HighlightRole> {
     // Determine which types we check, based on the decoration.
     let mut builtin_ok = true;
     let mut function_ok = true;
@@ -244,36 +522,23 @@ fn command_is_valid(
         implicit_cd_ok = false;
     }
 
-    // Check them.
-    let mut is_valid = false;
-
-    // Builtins
-    if !is_valid && builtin_ok {
-        is_valid = builtin_exists(cmd)
-    };
-
-    // Functions
-    if !is_valid && function_ok {
-        is_valid = function::exists_no_autoload(cmd)
-    };
-
-    // Abbreviations
-    if !is_valid && abbreviation_ok {
-        is_valid = with_abbrs(|set| set.has_match(cmd, abbrs::Position::Command, L!("")))
-    };
-
-    // Regular commands
-    if !is_valid && command_ok {
-        is_valid = path_get_path(cmd, vars).is_some()
-    };
-
-    // Implicit cd
-    if !is_valid && implicit_cd_ok {
-        is_valid = path_as_implicit_cd(cmd, working_directory, vars).is_some();
+    // Check them. Abbreviations are expanded before anything runs, and functions shadow builtins.
+    if abbreviation_ok && with_abbrs(|set| set.has_match(cmd, abbrs::Position::Command, L!(""))) {
+        return Some(HighlightRole::abbreviation);
     }
-
-    // Return what we got.
-    return is_valid;
+    if function_ok && function::exists_no_autoload(cmd) {
+        return Some(HighlightRole::function);
+    }
+    if builtin_ok && builtin_exists(cmd) {
+        return Some(HighlightRole::builtin);
+    }
+    if command_ok && path_get_path(cmd, vars).is_some() {
+        return Some(HighlightRole::command);
+    }
+    if implicit_cd_ok && path_as_implicit_cd(cmd, working_directory, vars).is_some() {
+        return Some(HighlightRole::command);
+    }
+    None
 }
 
 fn has_expand_reserved(s: &wstr) -> bool {
@@ -369,8 +634,13 @@ pub fn autosuggest_validate_from_history(
 }
 
 // Highlights the variable starting with 'in', setting colors within the 'colors' array. Returns the
-// number of characters consumed.
-fn color_variable(inp: &wstr, colors: &mut [HighlightSpec]) -> usize {
+// number of characters consumed. Variables for which `var_is_set` returns false are highlighted as
+// unset.
+fn color_variable(
+    inp: &wstr,
+    colors: &mut [HighlightSpec],
+    var_is_set: &dyn Fn(&wstr) -> bool,
+) -> usize {
     assert!(inp.char_at(0) == '$');
 
     // Handle an initial run of $s.
@@ -393,9 +663,11 @@ fn color_variable(inp: &wstr, colors: &mut [HighlightSpec]) -> usize {
 
     // Handle a sequence of variable characters.
     // It may contain an escaped newline - see #8444.
+    let mut name = WString::new();
     loop {
         if valid_var_name_char(inp.char_at(idx)) {
             colors[idx] = HighlightSpec::with_fg(HighlightRole::operat);
+            name.push(inp.char_at(idx));
             idx += 1;
         } else if inp.char_at(idx) == '\\' && inp.char_at(idx + 1) == '\n' {
             colors[idx] = HighlightSpec::with_fg(HighlightRole::operat);
@@ -407,6 +679,15 @@ fn color_variable(inp: &wstr, colors: &mut [HighlightSpec]) -> usize {
         }
     }
 
+    // Make typos like $PAHT stand out.
+    if !name.is_empty() && !var_is_set(&name) {
+        for color in &mut colors[..idx] {
+            if color.foreground == HighlightRole::operat {
+                *color = HighlightSpec::with_fg(HighlightRole::unset_variable);
+            }
+        }
+    }
+
     // Handle a slice, up to dollar_count of them. Note that we currently don't do any validation of
     // the slice's contents, e.g. $foo[blah] will not show an error even though it's invalid.
     for _slice_count in 0..dollar_count {
@@ -435,13 +716,21 @@ fn color_variable(inp: &wstr, colors: &mut [HighlightSpec]) -> usize {
 
 /// This function is a disaster badly in need of refactoring. It colors an argument or command,
 /// without regard to command substitutions.
-fn color_string_internal(buffstr: &wstr, base_color: HighlightSpec, colors: &mut [HighlightSpec]) {
+fn color_string_internal(
+    buffstr: &wstr,
+    base_color: HighlightSpec,
+    colors: &mut [HighlightSpec],
+    var_is_set: &dyn Fn(&wstr) -> bool,
+) {
     // Clarify what we expect.
     assert!(
         [
             HighlightSpec::with_fg(HighlightRole::param),
             HighlightSpec::with_fg(HighlightRole::option),
-            HighlightSpec::with_fg(HighlightRole::command)
+            HighlightSpec::with_fg(HighlightRole::command),
+            HighlightSpec::with_fg(HighlightRole::builtin),
+            HighlightSpec::with_fg(HighlightRole::function),
+            HighlightSpec::with_fg(HighlightRole::abbreviation),
         ]
         .contains(&base_color),
         "Unexpected base color"
@@ -564,7 +853,11 @@ fn color_string_internal(buffstr: &wstr, base_color: HighlightSpec, colors: &mut
                         }
                         '$' => {
                             assert!(in_pos < buff_len);
-                            in_pos += color_variable(&buffstr[in_pos..], &mut colors[in_pos..]);
+                            in_pos += color_variable(
+                                &buffstr[in_pos..],
+                                &mut colors[in_pos..],
+                                var_is_set,
+                            );
                             // Subtract one to account for the upcoming loop increment.
                             in_pos -= 1;
                         }
@@ -643,7 +936,8 @@ fn color_string_internal(buffstr: &wstr, base_color: HighlightSpec, colors: &mut
                         }
                     }
                     '$' => {
-                        in_pos += color_variable(&buffstr[in_pos..], &mut colors[in_pos..]);
+                        in_pos +=
+                            color_variable(&buffstr[in_pos..], &mut colors[in_pos..], var_is_set);
                         // Subtract one to account for the upcoming increment in the loop.
                         in_pos -= 1;
                     }
@@ -906,7 +1200,19 @@ struct Highlighter<'s> {
     color_array: ColorArray,
     // A stack of variables that the current commandline probably defines.  We mark redirections
     // as valid if they use one of these variables, to avoid marking valid targets as error.
-    pending_variables: Vec<&'s wstr>,
+    pending_variables: Vec<WString>,
+    // Variables that the commandline defines before the current position, in any block. We don't
+    // mark expansions of these as unset, even though they are not set yet.
+    assigned_variables: Vec<WString>,
+    // Whether the commandline runs argparse, which defines _flag_ variables.
+    runs_argparse: bool,
+    // Whether we are in a function body. It runs later, when other variables may be set, so we
+    // don't mark expansions there as unset.
+    in_function: bool,
+    // The results of earlier filesystem checks, if we should reuse them.
+    cache: Option<&'s Mutex<HighlightCache>>,
+    // The brackets, quotes and block keywords that belong together.
+    matching_pairs: MatchingPairs,
     done: bool,
 }
 
@@ -926,6 +1232,11 @@ impl<'s> Highlighter<'s> {
             working_directory,
             color_array: vec![],
             pending_variables: vec![],
+            assigned_variables: vec![],
+            runs_argparse: false,
+            in_function: false,
+            cache: None,
+            matching_pairs: MatchingPairs::default(),
             done: false,
         }
     }
@@ -988,17 +1299,49 @@ impl<'s> Highlighter<'s> {
         self.io_ok && !self.ctx.check_cancel()
     }
 
-    // Color a command.
-    fn color_command(&mut self, node: &ast::String_) {
+    /// Return the result of a check that does I/O from the part of our cache that `map` selects,
+    /// or else run the check and remember its result.
+    fn cached<K: Eq + Hash, V: Copy>(
+        &self,
+        map: fn(&mut HighlightCache) -> &mut HashMap<K, V>,
+        key: K,
+        check: impl FnOnce() -> V,
+    ) -> V {
+        let Some(cache) = self.cache else {
+            return check();
+        };
+        if let Some(value) = map(&mut cache.lock().unwrap()).get(&key).copied() {
+            return value;
+        }
+        let value = check();
+        // A canceled check has no meaningful result, and one that raced with a change to
+        // functions, abbreviations or variables may be stale.
+        let mut cache = cache.lock().unwrap();
+        if !self.ctx.check_cancel()
+            && cache.generation == HIGHLIGHT_CACHE_GENERATION.load(Ordering::Relaxed)
+        {
+            map(&mut cache).insert(key, value);
+        }
+        value
+    }
+
+    // Color a command, with the role for its kind of command.
+    fn color_command(&mut self, node: &ast::String_, role: HighlightRole) {
         let source_range = node.source_range();
         let cmd_str = self.get_source(source_range);
 
         let arg_start = source_range.start();
+        let (ctx, runs_argparse) = (self.ctx, self.runs_argparse);
+        let check_vars = self.io_ok && !self.in_function;
+        let assigned = &self.assigned_variables;
         color_string_internal(
             cmd_str,
-            HighlightSpec::with_fg(HighlightRole::command),
+            HighlightSpec::with_fg(role),
             &mut self.color_array[arg_start..],
+            &|name: &wstr| !check_vars || variable_is_set(name, ctx, assigned, runs_argparse),
         );
+        self.matching_pairs
+            .add_token_delimiters(cmd_str, arg_start, &[]);
     }
     // Color a node as if it were an argument.
     fn color_as_argument(&mut self, node: &dyn ast::Node, options_allowed: bool /* = true */) {
@@ -1009,21 +1352,23 @@ impl<'s> Highlighter<'s> {
         let arg_start = source_range.start();
 
         // Color this argument without concern for command substitutions.
-        if options_allowed && arg_str.char_at(0) == '-' {
-            color_string_internal(
-                arg_str,
-                HighlightSpec::with_fg(HighlightRole::option),
-                &mut self.color_array[arg_start..],
-            );
+        let role = if options_allowed && arg_str.char_at(0) == '-' {
+            HighlightRole::option
         } else {
-            color_string_internal(
-                arg_str,
-                HighlightSpec::with_fg(HighlightRole::param),
-                &mut self.color_array[arg_start..],
-            );
-        }
+            HighlightRole::param
+        };
+        let (ctx, runs_argparse) = (self.ctx, self.runs_argparse);
+        let check_vars = self.io_ok && !self.in_function;
+        let assigned = &self.assigned_variables;
+        color_string_internal(
+            arg_str,
+            HighlightSpec::with_fg(role),
+            &mut self.color_array[arg_start..],
+            &|name: &wstr| !check_vars || variable_is_set(name, ctx, assigned, runs_argparse),
+        );
 
         // Now do command substitutions.
+        let mut cmdsubs = vec![];
         let mut cmdsub_cursor = 0;
         let mut is_quoted = false;
         while let MaybeParentheses::CommandSubstitution(parens) = parse_util_locate_cmdsubst_range(
@@ -1040,6 +1385,12 @@ impl<'s> Highlighter<'s> {
                 .fill(HighlightSpec::with_fg(HighlightRole::operat));
             self.color_array[arg_start..][parens.closing()]
                 .fill(HighlightSpec::with_fg(HighlightRole::operat));
+            if !parens.closing().is_empty() {
+                self.matching_pairs.delimiters.push((
+                    arg_start + parens.start(),
+                    arg_start + parens.closing().start,
+                ));
+            }
 
             // Highlight it recursively.
             let arg_cursor = self
@@ -1053,12 +1404,25 @@ impl<'s> Highlighter<'s> {
                 self.working_directory.clone(),
                 self.io_still_ok(),
             );
+            cmdsub_highlighter.assigned_variables = self.assigned_variables.clone();
+            cmdsub_highlighter.cache = self.cache;
+            cmdsub_highlighter.runs_argparse = self.runs_argparse;
+            cmdsub_highlighter.in_function = self.in_function;
             let subcolors = cmdsub_highlighter.highlight();
 
             // Copy out the subcolors back into our array.
             assert!(subcolors.len() == cmdsub_contents.len());
             self.color_array[arg_start..][parens.command()].copy_from_slice(&subcolors);
+            // Command substitutions have their own job list, so block keywords in them are
+            // matched separately.
+            self.matching_pairs.extend_moved(
+                &cmdsub_highlighter.matching_pairs,
+                arg_start + parens.command().start,
+            );
+            cmdsubs.push(parens);
         }
+        self.matching_pairs
+            .add_token_delimiters(arg_str, arg_start, &cmdsubs);
     }
     // Colors the source range of a node with a given color.
     fn color_node(&mut self, node: &dyn ast::Node, color: HighlightSpec) {
@@ -1135,12 +1499,18 @@ impl<'s> Highlighter<'s> {
                 let is_help = string_prefixes_string(&param, L!("--help"))
                     || string_prefixes_string(&param, L!("-h"));
                 if !is_help {
-                    is_valid_path = is_potential_cd_path(
-                        &param,
-                        at_cursor,
-                        &self.working_directory,
-                        self.ctx,
-                        PathFlags::PATH_EXPAND_TILDE,
+                    is_valid_path = self.cached(
+                        |cache| &mut cache.cd_paths,
+                        (param.clone(), at_cursor),
+                        || {
+                            is_potential_cd_path(
+                                &param,
+                                at_cursor,
+                                &self.working_directory,
+                                self.ctx,
+                                PathFlags::PATH_EXPAND_TILDE,
+                            )
+                        },
                     );
                     if !is_valid_path {
                         self.color_node(
@@ -1150,12 +1520,18 @@ impl<'s> Highlighter<'s> {
                     }
                 }
             }
-        } else if range_is_potential_path(
-            self.buff,
-            arg.range().unwrap(),
-            at_cursor,
-            self.ctx,
-            &self.working_directory,
+        } else if self.cached(
+            |cache| &mut cache.paths,
+            (arg.source(self.buff).to_owned(), at_cursor),
+            || {
+                range_is_potential_path(
+                    self.buff,
+                    arg.range().unwrap(),
+                    at_cursor,
+                    self.ctx,
+                    &self.working_directory,
+                )
+            },
         ) {
             is_valid_path = true;
         }
@@ -1194,6 +1570,11 @@ impl<'s> Highlighter<'s> {
         if has_cmdsub(&target) {
             self.color_as_argument(redir.target.leaf_as_node(), true);
         } else {
+            self.matching_pairs.add_token_delimiters(
+                redir.target.source(self.buff),
+                redir.target.source_range().start(),
+                &[],
+            );
             // No command substitution, so we can highlight the target file or fd. For example,
             // disallow redirections into a non-existent directory.
             let target_is_valid;
@@ -1301,7 +1682,8 @@ impl<'s> Highlighter<'s> {
             let equals_loc = varas.source_range().start() + offset;
             self.color_array[equals_loc] = HighlightSpec::with_fg(HighlightRole::operat);
             let var_name = &varas.source(self.buff)[..offset];
-            self.pending_variables.push(var_name);
+            self.pending_variables.push(var_name.to_owned());
+            self.assigned_variables.push(var_name.to_owned());
         }
     }
     fn visit_semi_nl(&mut self, node: &dyn Node) {
@@ -1324,31 +1706,37 @@ impl<'s> Highlighter<'s> {
         let cmd = stmt.command.source(self.buff);
 
         let mut expanded_cmd = WString::new();
-        let mut is_valid_cmd = false;
+        let mut cmd_role = None;
         if !self.io_still_ok() {
             // We cannot check if the command is invalid, so just assume it's valid.
-            is_valid_cmd = true;
+            cmd_role = Some(HighlightRole::command);
         } else if variable_assignment_equals_pos(cmd).is_some() {
-            is_valid_cmd = true;
+            cmd_role = Some(HighlightRole::command);
         } else {
             // Check to see if the command is valid.
             // Try expanding it. If we cannot, it's an error.
             if let Some(expanded) = statement_get_expanded_command(self.buff, stmt, self.ctx) {
                 expanded_cmd = expanded;
                 if !has_expand_reserved(&expanded_cmd) {
-                    is_valid_cmd = command_is_valid(
-                        &expanded_cmd,
-                        stmt.decoration(),
-                        &self.working_directory,
-                        self.ctx.vars(),
+                    cmd_role = self.cached(
+                        |cache| &mut cache.commands,
+                        (expanded_cmd.clone(), stmt.decoration()),
+                        || {
+                            command_role(
+                                &expanded_cmd,
+                                stmt.decoration(),
+                                &self.working_directory,
+                                self.ctx.vars(),
+                            )
+                        },
                     );
                 }
             }
         }
 
         // Color our statement.
-        if is_valid_cmd {
-            self.color_command(&stmt.command);
+        if let Some(role) = cmd_role {
+            self.color_command(&stmt.command, role);
         } else {
             self.color_node(&stmt.command, HighlightSpec::with_fg(HighlightRole::error))
         }
@@ -1357,19 +1745,34 @@ impl<'s> Highlighter<'s> {
         // Except if our command is 'cd' we have special logic for how arguments are colored.
         let is_cd = expanded_cmd == "cd";
         let mut is_set = expanded_cmd == "set";
+        let is_read = expanded_cmd == "read";
+        if expanded_cmd == "argparse" {
+            self.runs_argparse = true;
+        }
         // If we have seen a "--" argument, color all options from then on as normal arguments.
         let mut have_dashdash = false;
         for v in &stmt.args_or_redirs {
             if v.is_argument() {
-                if is_set {
-                    let arg = v.argument().source(self.buff);
-                    if valid_var_name(arg) {
-                        self.pending_variables.push(arg);
-                        is_set = false;
-                    }
+                let arg = v.argument().source(self.buff);
+                if is_set && valid_var_name(arg) {
+                    self.pending_variables.push(arg.to_owned());
+                    self.assigned_variables.push(arg.to_owned());
+                    is_set = false;
+                } else if is_read && valid_var_name(arg) {
+                    self.assigned_variables.push(arg.to_owned());
                 }
                 self.visit_argument(v.argument(), is_cd, !have_dashdash);
-                if v.argument().source(self.buff) == "--" {
+                if self.io_still_ok()
+                    && with_abbrs(|set| {
+                        set.has_match(arg, abbrs::Position::Anywhere, &expanded_cmd)
+                    })
+                {
+                    self.color_node(
+                        v.argument().as_node(),
+                        HighlightSpec::with_fg(HighlightRole::abbreviation),
+                    );
+                }
+                if arg == "--" {
                     have_dashdash = true;
                 }
             } else {
@@ -1377,23 +1780,126 @@ impl<'s> Highlighter<'s> {
             }
         }
     }
+    // Visit a statement, or reuse its colors from an earlier pass.
+    fn visit_job_conjunction(&mut self, job: &JobConjunction) {
+        // A statement with missing parts, like an unterminated block, has no source range to keep
+        // colors for.
+        let (Some(cache), Some(range)) = (self.cache, job.try_source_range()) else {
+            return self.visit_children(job);
+        };
+        let mut state = DefaultHasher::new();
+        (
+            &self.pending_variables,
+            &self.assigned_variables,
+            self.runs_argparse,
+            self.in_function,
+        )
+            .hash(&mut state);
+        let key = StatementKey {
+            source: self.get_source(range).to_owned(),
+            cursor: self
+                .cursor
+                .filter(|&c| range.contains_inclusive(c))
+                .map(|c| c - range.start()),
+            io_ok: self.io_ok,
+            state: state.finish(),
+        };
+        {
+            let mut cache = cache.lock().unwrap();
+            let statement = match cache.old_statements.remove(&key) {
+                Some(statement) => Some(cache.statements.entry(key).or_insert(statement)),
+                None => cache.statements.get_mut(&key),
+            };
+            if let Some(statement) = statement {
+                self.color_array[range.start()..range.end()].copy_from_slice(&statement.colors);
+                self.pending_variables
+                    .extend_from_slice(&statement.pending_variables);
+                self.assigned_variables
+                    .extend_from_slice(&statement.assigned_variables);
+                self.runs_argparse |= statement.runs_argparse;
+                self.matching_pairs
+                    .extend_moved(&statement.matching_pairs, range.start());
+                return;
+            }
+        }
+
+        let pending_variables_count = self.pending_variables.len();
+        let assigned_variables_count = self.assigned_variables.len();
+        let runs_argparse = self.runs_argparse;
+        let matching_pairs_counts = self.matching_pairs.counts();
+        self.visit_children(job);
+
+        // Like in cached(), don't remember canceled or stale results.
+        let mut cache = cache.lock().unwrap();
+        if !self.ctx.check_cancel()
+            && cache.generation == HIGHLIGHT_CACHE_GENERATION.load(Ordering::Relaxed)
+        {
+            let statement = HighlightedStatement {
+                colors: self.color_array[range.start()..range.end()].to_vec(),
+                pending_variables: self.pending_variables[pending_variables_count..].to_vec(),
+                assigned_variables: self.assigned_variables[assigned_variables_count..].to_vec(),
+                runs_argparse: self.runs_argparse && !runs_argparse,
+                matching_pairs: self
+                    .matching_pairs
+                    .since(matching_pairs_counts, range.start()),
+            };
+            cache.statements.insert(key, statement);
+        }
+    }
     fn visit_block_statement(&mut self, block: &BlockStatement) {
-        match &*block.header {
+        let (header, opener): (&dyn Node, &dyn Node) = match &*block.header {
             BlockStatementHeaderVariant::None => panic!(),
-            BlockStatementHeaderVariant::ForHeader(node) => self.visit(node),
-            BlockStatementHeaderVariant::WhileHeader(node) => self.visit(node),
-            BlockStatementHeaderVariant::FunctionHeader(node) => self.visit(node),
-            BlockStatementHeaderVariant::BeginHeader(node) => self.visit(node),
-        }
+            BlockStatementHeaderVariant::ForHeader(node) => (node, &node.kw_for),
+            BlockStatementHeaderVariant::WhileHeader(node) => (node, &node.kw_while),
+            BlockStatementHeaderVariant::FunctionHeader(node) => (node, &node.kw_function),
+            BlockStatementHeaderVariant::BeginHeader(node) => (node, &node.kw_begin),
+        };
+        self.matching_pairs.add_keyword_group(&[opener, &block.end]);
+        self.visit(header);
         self.visit(&block.args_or_redirs);
         let pending_variables_count = self.pending_variables.len();
+        let in_function = self.in_function;
         if let Some(fh) = block.header.as_for_header() {
             let var_name = fh.var_name.source(self.buff);
-            self.pending_variables.push(var_name);
+            self.pending_variables.push(var_name.to_owned());
+            self.assigned_variables.push(var_name.to_owned());
+        } else if let Some(fh) = block.header.as_function_header() {
+            self.in_function = true;
+            self.assigned_variables.push(L!("argv").to_owned());
+            // Like --argument-names and --inherit-variable.
+            for arg in &fh.args {
+                let arg = arg.source(self.buff);
+                if valid_var_name(arg) {
+                    self.assigned_variables.push(arg.to_owned());
+                }
+            }
         }
         self.visit(&block.jobs);
         self.visit(&block.end);
         self.pending_variables.truncate(pending_variables_count);
+        self.in_function = in_function;
+    }
+    fn visit_if_statement(&mut self, stmt: &IfStatement) {
+        let mut keywords: Vec<&dyn Node> = vec![&stmt.if_clause.kw_if];
+        for clause in &stmt.elseif_clauses {
+            keywords.push(&clause.kw_else);
+            keywords.push(&clause.if_clause.kw_if);
+        }
+        if let Some(clause) = &stmt.else_clause {
+            keywords.push(&clause.kw_else);
+        }
+        keywords.push(&stmt.end);
+        self.matching_pairs.add_keyword_group(&keywords);
+        self.visit_children(stmt);
+    }
+    fn visit_switch_statement(&mut self, stmt: &SwitchStatement) {
+        let mut keywords: Vec<&dyn Node> = vec![&stmt.kw_switch];
+        for item in &stmt.cases {
+            keywords.push(&item.kw_case);
+        }
+        keywords.push(&stmt.end);
+        self.matching_pairs.add_keyword_group(&keywords);
+        self.visit_children(stmt);
     }
 }
 
@@ -1407,7 +1913,21 @@ fn has_cmdsub(src: &wstr) -> bool {
     }
 }
 
-fn contains_pending_variable(pending_variables: &[&wstr], haystack: &wstr) -> bool {
+/// Return whether the variable `name` is set, or is defined by the commandline before it is used.
+fn variable_is_set(
+    name: &wstr,
+    ctx: &OperationContext,
+    assigned_variables: &[WString],
+    runs_argparse: bool,
+) -> bool {
+    // Electric variables are always there, but some, like $history, can't be read here.
+    assigned_variables.iter().any(|var| var == name)
+        || (runs_argparse && name.starts_with("_flag_"))
+        || ElectricVar::for_name(name).is_some()
+        || ctx.vars().get(name).is_some()
+}
+
+fn contains_pending_variable(pending_variables: &[WString], haystack: &wstr) -> bool {
     for var_name in pending_variables {
         let mut nextpos = 0;
         while let Some(relpos) = &haystack[nextpos..]
@@ -1453,6 +1973,11 @@ impl<'s, 'a> NodeVisitor<'a> for Highlighter<'s> {
                 self.visit_decorated_statement(node.as_decorated_statement().unwrap())
             }
             Type::block_statement => self.visit_block_statement(node.as_block_statement().unwrap()),
+            Type::if_statement => self.visit_if_statement(node.as_if_statement().unwrap()),
+            Type::switch_statement => {
+                self.visit_switch_statement(node.as_switch_statement().unwrap())
+            }
+            Type::job_conjunction => self.visit_job_conjunction(node.as_job_conjunction().unwrap()),
             // Default implementation is to just visit children.
             _ => self.visit_children(node),
         }
@@ -1473,7 +1998,7 @@ fn statement_get_expanded_command(
     (err == ExpandResultCode::ok).then_some(out_cmd)
 }
 
-fn get_highlight_var_name(role: HighlightRole) -> &'static wstr {
+pub(crate) fn get_highlight_var_name(role: HighlightRole) -> &'static wstr {
     match role {
         HighlightRole::normal => L!("fish_color_normal"),
         HighlightRole::error => L!("fish_color_error"),
@@ -1490,6 +2015,12 @@ fn get_highlight_var_name(role: HighlightRole) -> &'static wstr {
         HighlightRole::redirection => L!("fish_color_redirection"),
         HighlightRole::autosuggestion => L!("fish_color_autosuggestion"),
         HighlightRole::selection => L!("fish_color_selection"),
+        HighlightRole::paste => L!("fish_color_paste"),
+        HighlightRole::builtin => L!("fish_color_builtin"),
+        HighlightRole::function => L!("fish_color_function"),
+        HighlightRole::abbreviation => L!("fish_color_abbreviation"),
+        HighlightRole::unset_variable => L!("fish_color_unset_variable"),
+        HighlightRole::matching_pair => L!("fish_color_match"),
         HighlightRole::pager_progress => L!("fish_pager_color_progress"),
         HighlightRole::pager_background => L!("fish_pager_color_background"),
         HighlightRole::pager_prefix => L!("fish_pager_color_prefix"),
@@ -1508,7 +2039,7 @@ fn get_highlight_var_name(role: HighlightRole) -> &'static wstr {
 
 // Table used to fetch fallback highlights in case the specified one
 // wasn't set.
-fn get_fallback(role: HighlightRole) -> HighlightRole {
+pub(crate) fn get_fallback(role: HighlightRole) -> HighlightRole {
     match role {
         HighlightRole::normal
         | HighlightRole::error
@@ -1528,7 +2059,13 @@ fn get_fallback(role: HighlightRole) -> HighlightRole {
         | HighlightRole::pager_prefix
         | HighlightRole::pager_completion
         | HighlightRole::pager_description => HighlightRole::normal,
-        HighlightRole::keyword => HighlightRole::command,
+        HighlightRole::keyword
+        | HighlightRole::builtin
+        | HighlightRole::function
+        | HighlightRole::abbreviation => HighlightRole::command,
+        HighlightRole::unset_variable => HighlightRole::error,
+        HighlightRole::matching_pair => HighlightRole::search_match,
+        HighlightRole::paste => HighlightRole::selection,
         HighlightRole::option => HighlightRole::param,
         HighlightRole::pager_secondary_background => HighlightRole::pager_background,
         HighlightRole::pager_secondary_prefix | HighlightRole::pager_selected_prefix => {
@@ -1582,6 +2119,45 @@ impl Default for HighlightRole {
     }
 }
 
+impl HighlightRole {
+    pub const ALL: [HighlightRole; 34] = [
+        HighlightRole::normal,
+        HighlightRole::error,
+        HighlightRole::command,
+        HighlightRole::keyword,
+        HighlightRole::statement_terminator,
+        HighlightRole::param,
+        HighlightRole::option,
+        HighlightRole::comment,
+        HighlightRole::search_match,
+        HighlightRole::operat,
+        HighlightRole::escape,
+        HighlightRole::quote,
+        HighlightRole::redirection,
+        HighlightRole::autosuggestion,
+        HighlightRole::selection,
+        HighlightRole::paste,
+        HighlightRole::builtin,
+        HighlightRole::function,
+        HighlightRole::abbreviation,
+        HighlightRole::unset_variable,
+        HighlightRole::matching_pair,
+        HighlightRole::pager_progress,
+        HighlightRole::pager_background,
+        HighlightRole::pager_prefix,
+        HighlightRole::pager_completion,
+        HighlightRole::pager_description,
+        HighlightRole::pager_secondary_background,
+        HighlightRole::pager_secondary_prefix,
+        HighlightRole::pager_secondary_completion,
+        HighlightRole::pager_secondary_description,
+        HighlightRole::pager_selected_background,
+        HighlightRole::pager_selected_prefix,
+        HighlightRole::pager_selected_completion,
+        HighlightRole::pager_selected_description,
+    ];
+}
+
 impl Default for HighlightSpec {
     fn default() -> Self {
         Self {
@@ -1612,6 +2188,12 @@ pub enum HighlightRole {
     redirection,          // redirection
     autosuggestion,       // autosuggestion
     selection,
+    paste,          // pasted text awaiting confirmation
+    builtin,        // command that is a builtin
+    function,       // command that is a function
+    abbreviation,   // token that will be expanded as an abbreviation
+    unset_variable, // expansion of a variable that is not set
+    matching_pair,  // bracket, quote or block keyword matching the one at the cursor
 
     // Pager support.
     // NOTE: pager.cpp relies on these being in this order.
@@ -4,9 +4,13 @@ use crate::env::{Environment, CURSES_INITIALIZED};
 use crate::event;
 use crate::flog::FLOG;
 use crate::input_common::{
-    CharEvent, CharInputStyle, InputEventQueuer, ReadlineCmd, R_END_INPUT_FUNCTIONS,
+    kitty_keyboard_flags_for_key, kitty_keyboard_set_flags, sequence_hint_delay_ms, CharEvent,
+    CharInputStyle, InputEventQueuer, ReadlineCmd, R_END_INPUT_FUNCTIONS,
 };
-use crate::key::{self, canonicalize_raw_escapes, ctrl, Key, Modifiers};
+use crate::key::{
+    self, canonicalize_raw_escapes, ctrl, Key, KeyEventType, Modifiers, KEY_SEPARATOR,
+};
+use crate::kill::kill_sync;
 use crate::parser::Parser;
 use crate::proc::job_reap;
 use crate::reader::{
@@ -15,8 +19,11 @@ use crate::reader::{
 use crate::signal::signal_clear_cancel;
 use crate::threads::assert_is_main_thread;
 use crate::wchar::prelude::*;
+use crate::wcstringutil::join_strings;
+use crate::wutil::write_to_fd;
+use libc::STDOUT_FILENO;
 use once_cell::sync::{Lazy, OnceCell};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::CString;
 use std::os::fd::RawFd;
 use std::rc::Rc;
@@ -44,6 +51,34 @@ pub enum KeyNameStyle {
     Terminfo(WString),
 }
 
+/// What to do when the next key of a sequence with its own timeout does not arrive in time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TimeoutFallback {
+    /// Handle the keys typed so far like any others, which usually runs the binding for them.
+    #[default]
+    Prefix,
+    /// Insert the keys typed so far as text.
+    Insert,
+}
+
+/// How long to wait between the keys of a sequence, overriding fish_sequence_key_delay_ms.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SequenceTimeout {
+    pub ms: usize,
+    pub fallback: TimeoutFallback,
+}
+
+/// The optional parts of a keybinding.
+#[derive(Debug, Clone, Default)]
+pub struct InputMappingOptions {
+    /// New mode that should be switched to after command evaluation, or None to leave the mode unchanged.
+    pub sets_mode: Option<WString>,
+    /// How long to wait for each key after the first, or None for the default of the mode.
+    pub timeout: Option<SequenceTimeout>,
+    /// What this binding does, for showing in sequence hints.
+    pub description: Option<WString>,
+}
+
 /// Struct representing a keybinding. Returned by input_get_mappings.
 #[derive(Debug, Clone)]
 struct InputMapping {
@@ -55,10 +90,10 @@ struct InputMapping {
     specification_order: u32,
     /// Mode in which this command should be evaluated.
     mode: WString,
-    /// New mode that should be switched to after command evaluation, or None to leave the mode unchanged.
-    sets_mode: Option<WString>,
     /// Perhaps this binding was created using a raw escape sequence or terminfo.
     key_name_style: KeyNameStyle,
+    /// The mode to switch to, the timeout and the description.
+    options: InputMappingOptions,
 }
 
 impl InputMapping {
@@ -67,13 +102,13 @@ impl InputMapping {
         seq: Vec<Key>,
         commands: Vec<WString>,
         mode: WString,
-        sets_mode: Option<WString>,
         key_name_style: KeyNameStyle,
+        options: InputMappingOptions,
     ) -> InputMapping {
         static LAST_INPUT_MAP_SPEC_ORDER: AtomicU32 = AtomicU32::new(0);
         let specification_order = 1 + LAST_INPUT_MAP_SPEC_ORDER.fetch_add(1, Ordering::Relaxed);
         assert!(
-            sets_mode.is_none() || !mode.is_empty(),
+            options.sets_mode.is_none() || !mode.is_empty(),
             "sets_mode set but mode is empty"
         );
         InputMapping {
@@ -81,8 +116,8 @@ impl InputMapping {
             commands,
             specification_order,
             mode,
-            sets_mode,
             key_name_style,
+            options,
         }
     }
 
@@ -128,6 +163,10 @@ const INPUT_FUNCTION_METADATA: &[InputFunctionMetadata] = &[
     make_md(L!(""), ReadlineCmd::DisableMouseTracking),
     make_md(L!(""), ReadlineCmd::FocusIn),
     make_md(L!(""), ReadlineCmd::FocusOut),
+    make_md(L!(""), ReadlineCmd::BeginPaste),
+    make_md(L!(""), ReadlineCmd::EndPaste),
+    make_md(L!(""), ReadlineCmd::CursorPositionReport),
+    make_md(L!(""), ReadlineCmd::ShowSequenceHints),
     make_md(L!("accept-autosuggestion"), ReadlineCmd::AcceptAutosuggestion),
     make_md(L!("and"), ReadlineCmd::FuncAnd),
     make_md(L!("backward-bigword"), ReadlineCmd::BackwardBigword),
@@ -141,6 +180,7 @@ const INPUT_FUNCTION_METADATA: &[InputFunctionMetadata] = &[
     make_md(L!("backward-kill-path-component"), ReadlineCmd::BackwardKillPathComponent),
     make_md(L!("backward-kill-word"), ReadlineCmd::BackwardKillWord),
     make_md(L!("backward-word"), ReadlineCmd::BackwardWord),
+    make_md(L!("begin-macro"), ReadlineCmd::BeginMacro),
     make_md(L!("begin-selection"), ReadlineCmd::BeginSelection),
     make_md(L!("begin-undo-group"), ReadlineCmd::BeginUndoGroup),
     make_md(L!("beginning-of-buffer"), ReadlineCmd::BeginningOfBuffer),
@@ -152,10 +192,12 @@ const INPUT_FUNCTION_METADATA: &[InputFunctionMetadata] = &[
     make_md(L!("clear-screen"), ReadlineCmd::ClearScreenAndRepaint),
     make_md(L!("complete"), ReadlineCmd::Complete),
     make_md(L!("complete-and-search"), ReadlineCmd::CompleteAndSearch),
+    make_md(L!("correct-command"), ReadlineCmd::CorrectCommand),
     make_md(L!("delete-char"), ReadlineCmd::DeleteChar),
     make_md(L!("delete-or-exit"), ReadlineCmd::DeleteOrExit),
     make_md(L!("down-line"), ReadlineCmd::DownLine),
     make_md(L!("downcase-word"), ReadlineCmd::DowncaseWord),
+    make_md(L!("end-macro"), ReadlineCmd::EndMacro),
     make_md(L!("end-of-buffer"), ReadlineCmd::EndOfBuffer),
     make_md(L!("end-of-history"), ReadlineCmd::EndOfHistory),
     make_md(L!("end-of-line"), ReadlineCmd::EndOfLine),
@@ -188,15 +230,20 @@ const INPUT_FUNCTION_METADATA: &[InputFunctionMetadata] = &[
     make_md(L!("kill-selection"), ReadlineCmd::KillSelection),
     make_md(L!("kill-whole-line"), ReadlineCmd::KillWholeLine),
     make_md(L!("kill-word"), ReadlineCmd::KillWord),
+    make_md(L!("mouse-click"), ReadlineCmd::MouseClick),
     make_md(L!("nextd-or-forward-word"), ReadlineCmd::NextdOrForwardWord),
     make_md(L!("or"), ReadlineCmd::FuncOr),
     make_md(L!("pager-toggle-search"), ReadlineCmd::PagerToggleSearch),
+    make_md(L!("play-macro"), ReadlineCmd::PlayMacro),
     make_md(L!("prevd-or-backward-word"), ReadlineCmd::PrevdOrBackwardWord),
     make_md(L!("redo"), ReadlineCmd::Redo),
     make_md(L!("repaint"), ReadlineCmd::Repaint),
     make_md(L!("repaint-mode"), ReadlineCmd::RepaintMode),
     make_md(L!("repeat-jump"), ReadlineCmd::RepeatJump),
     make_md(L!("repeat-jump-reverse"), ReadlineCmd::ReverseRepeatJump),
+    make_md(L!("restore-draft"), ReadlineCmd::RestoreDraft),
+    make_md(L!("scroll-down"), ReadlineCmd::ScrollDown),
+    make_md(L!("scroll-up"), ReadlineCmd::ScrollUp),
     make_md(L!("self-insert"), ReadlineCmd::SelfInsert),
     make_md(L!("self-insert-notfirst"), ReadlineCmd::SelfInsertNotFirst),
     make_md(L!("suppress-autosuggestion"), ReadlineCmd::SuppressAutosuggestion),
@@ -240,6 +287,9 @@ pub fn describe_char(c: i32) -> WString {
 pub struct InputMappingSet {
     mapping_list: Vec<InputMapping>,
     preset_mapping_list: Vec<InputMapping>,
+    /// The sequence timeouts of bind modes, for bindings without their own.
+    mode_timeouts: HashMap<WString, SequenceTimeout>,
+    preset_mode_timeouts: HashMap<WString, SequenceTimeout>,
 }
 
 /// Access the singleton input mapping set.
@@ -261,13 +311,30 @@ fn input_get_bind_mode(vars: &dyn Environment) -> WString {
     }
 }
 
+/// Return how the given incomplete sequence may continue in the current bind mode.
+pub fn input_sequence_hints(vars: &dyn Environment, prefix: &[Key]) -> Vec<(WString, WString)> {
+    input_mappings().continuations(prefix, &input_get_bind_mode(vars))
+}
+
+/// Return the commands bound to `key` in the current bind mode, if any. `raw` is the sequence the
+/// key was received as, which bindings of raw escape sequences and terminfo keys match.
+pub fn input_binding_commands(
+    vars: &dyn Environment,
+    key: Key,
+    raw: &wstr,
+) -> Option<Vec<WString>> {
+    input_mappings().binding_commands(key, raw, &input_get_bind_mode(vars))
+}
+
 /// Returns the arity of a given input function.
 fn input_function_arity(function: ReadlineCmd) -> usize {
     match function {
         ReadlineCmd::ForwardJump
         | ReadlineCmd::BackwardJump
         | ReadlineCmd::ForwardJumpTill
-        | ReadlineCmd::BackwardJumpTill => 1,
+        | ReadlineCmd::BackwardJumpTill
+        | ReadlineCmd::BeginMacro
+        | ReadlineCmd::PlayMacro => 1,
         _ => 0,
     }
 }
@@ -290,7 +357,7 @@ impl InputMappingSet {
         key_name_style: KeyNameStyle,
         commands: Vec<WString>,
         mode: WString,
-        sets_mode: Option<WString>,
+        options: InputMappingOptions,
         user: bool,
     ) {
         // Update any existing mapping with this sequence.
@@ -303,14 +370,15 @@ impl InputMappingSet {
         for m in ml.iter_mut() {
             if m.seq == sequence && m.mode == mode {
                 m.commands = commands;
-                m.sets_mode = sets_mode;
+                m.options = options;
                 return;
             }
         }
 
         // Add a new mapping, using the next order.
-        let new_mapping = InputMapping::new(sequence, commands, mode, sets_mode, key_name_style);
+        let new_mapping = InputMapping::new(sequence, commands, mode, key_name_style, options);
         input_mapping_insert_sorted(ml, new_mapping);
+        kitty_keyboard_set_flags(self.kitty_keyboard_flags());
     }
 
     // Like add(), but takes a single command.
@@ -323,14 +391,56 @@ impl InputMappingSet {
         sets_mode: Option<WString>,
         user: bool,
     ) {
-        self.add(
-            sequence,
-            key_name_style,
-            vec![command],
-            mode,
+        let options = InputMappingOptions {
             sets_mode,
-            user,
-        );
+            ..Default::default()
+        };
+        self.add(sequence, key_name_style, vec![command], mode, options, user);
+    }
+
+    /// Sets the sequence timeout of bindings in `mode` that have none of their own.
+    pub fn set_mode_timeout(&mut self, mode: WString, timeout: SequenceTimeout, user: bool) {
+        let timeouts = if user {
+            &mut self.mode_timeouts
+        } else {
+            &mut self.preset_mode_timeouts
+        };
+        timeouts.insert(mode, timeout);
+    }
+
+    /// Returns the modes with a sequence timeout, and their timeouts, sorted by mode.
+    pub fn get_mode_timeouts(&self, user: bool) -> Vec<(WString, SequenceTimeout)> {
+        let timeouts = if user {
+            &self.mode_timeouts
+        } else {
+            &self.preset_mode_timeouts
+        };
+        let mut result: Vec<_> = timeouts
+            .iter()
+            .map(|(mode, timeout)| (mode.clone(), *timeout))
+            .collect();
+        result.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        result
+    }
+
+    /// Returns the kitty keyboard flags needed to recognize the bound keys, beyond the ones we
+    /// always ask for.
+    pub fn kitty_keyboard_flags(&self) -> u32 {
+        self.mapping_list
+            .iter()
+            .chain(&self.preset_mapping_list)
+            .flat_map(|m| &m.seq)
+            .fold(0, |flags, key| flags | kitty_keyboard_flags_for_key(key))
+    }
+
+    /// Returns the sequence timeout of `mapping`, which may come from its mode.
+    fn timeout_of(&self, mapping: &InputMapping) -> Option<SequenceTimeout> {
+        mapping.options.timeout.or_else(|| {
+            self.mode_timeouts
+                .get(&mapping.mode)
+                .or_else(|| self.preset_mode_timeouts.get(&mapping.mode))
+                .copied()
+        })
     }
 }
 
@@ -393,6 +503,49 @@ pub fn init_input() {
     }
 }
 
+/// A recording of the events returned by [`Inputter::read_char`], which can be replayed as a
+/// keyboard macro.
+#[derive(Debug, Clone, Default)]
+struct InputMacro {
+    events: Vec<CharEvent>,
+    /// The arguments of the readline functions in `events`, in the order they are popped.
+    args: Vec<char>,
+}
+
+/// The register that refers to the most recently recorded macro.
+pub const LAST_MACRO_REGISTER: char = '@';
+
+/// How often a macro can be replayed at once.
+const MAX_MACRO_REPEAT: usize = 999;
+
+/// Recorded macros, by the register they were recorded into.
+#[derive(Default)]
+struct MacroRegisters {
+    macros: HashMap<char, InputMacro>,
+    /// The register that was recorded into last.
+    last: Option<char>,
+}
+
+impl MacroRegisters {
+    fn get(&self, register: char) -> Option<&InputMacro> {
+        let register = if register == LAST_MACRO_REGISTER {
+            self.last?
+        } else {
+            register
+        };
+        self.macros.get(&register)
+    }
+}
+
+/// The recorded macros, shared by all readers.
+static MACRO_REGISTERS: Lazy<Mutex<MacroRegisters>> =
+    Lazy::new(|| Mutex::new(MacroRegisters::default()));
+
+/// Return whether a macro can be recorded into `register`. Digits are repeat counts instead.
+fn is_macro_register(register: char) -> bool {
+    register != LAST_MACRO_REGISTER && !register.is_ascii_digit()
+}
+
 /// CommandHandler is used to run commands. When a character is encountered that
 /// would invoke a fish command, it is unread and CharEventType::CheckExit is returned.
 /// Note the handler is not stored.
@@ -405,7 +558,18 @@ pub struct Inputter {
     // We need a parser to evaluate bindings.
     parser: Rc<Parser>,
     input_function_args: Vec<char>,
+    // The repeat counts of play-macro, read along with its register.
+    macro_repeat_counts: Vec<usize>,
     function_status: bool,
+    // The macro being recorded, if any, and its register.
+    macro_recording: Option<(char, InputMacro)>,
+    // The last position reported by the mouse.
+    mouse_position: Option<(usize, usize)>,
+    // Whether we asked the terminal for the cursor position, and its answer.
+    cursor_position_awaited: bool,
+    cursor_position: Option<(usize, usize)>,
+    // The keys of an incomplete sequence for which we asked to show hints, if any.
+    sequence_hint_prefix: Option<Vec<Key>>,
 
     // Transient storage to avoid repeated allocations.
     event_storage: Vec<CharEvent>,
@@ -458,6 +622,7 @@ impl InputEventQueuer for Inputter {
 
     fn uvar_change_notified(&mut self) {
         self.parser.sync_uvars_and_fire(true /* always */);
+        kill_sync();
     }
 
     fn paste_start_buffering(&mut self) {
@@ -472,14 +637,28 @@ impl InputEventQueuer for Inputter {
         let Some(buffer) = self.paste_buffer.take() else {
             return;
         };
+        // Bracket the insertion so the reader knows which part of the command line was pasted.
+        self.push_front(CharEvent::from_readline(ReadlineCmd::EndPaste));
         self.push_front(CharEvent::Command(sprintf!(
             "__fish_paste %s",
             escape(&str2wcstring(&buffer))
         )));
+        self.push_front(CharEvent::from_readline(ReadlineCmd::BeginPaste));
     }
     fn paste_push_char(&mut self, b: u8) {
         self.paste_buffer.as_mut().unwrap().push(b)
     }
+
+    fn mouse_position_set(&mut self, x: usize, y: usize) {
+        self.mouse_position = Some((x, y));
+    }
+    fn cursor_position_awaited(&self) -> bool {
+        self.cursor_position_awaited
+    }
+    fn cursor_position_set(&mut self, x: usize, y: usize) {
+        self.cursor_position_awaited = false;
+        self.cursor_position = Some((x, y));
+    }
 }
 
 impl Inputter {
@@ -491,7 +670,13 @@ impl Inputter {
             paste_buffer: None,
             parser,
             input_function_args: Vec::new(),
+            macro_repeat_counts: Vec::new(),
             function_status: false,
+            macro_recording: None,
+            mouse_position: None,
+            cursor_position_awaited: false,
+            cursor_position: None,
+            sequence_hint_prefix: None,
             event_storage: Vec::new(),
         }
     }
@@ -506,6 +691,13 @@ impl Inputter {
             .expect("function_pop_arg underflow")
     }
 
+    /// Return the repeat count that was read for play-macro.
+    pub fn function_pop_repeat_count(&mut self) -> usize {
+        self.macro_repeat_counts
+            .pop()
+            .expect("function_pop_repeat_count underflow")
+    }
+
     fn function_push_args(&mut self, code: ReadlineCmd) {
         let arity = input_function_arity(code);
         assert!(
@@ -513,21 +705,25 @@ impl Inputter {
             "event_storage should be empty"
         );
         let mut skipped = std::mem::take(&mut self.event_storage);
-        for _ in 0..arity {
-            // Skip and queue up any function codes. See issue #2357.
-            let arg: char;
-            loop {
-                let evt = self.readch();
-                if let Some(kevt) = evt.get_key() {
-                    if let Some(c) = kevt.key.codepoint_text() {
-                        // TODO forward the whole key
-                        arg = c;
-                        break;
+        if code == ReadlineCmd::PlayMacro {
+            // The register may be preceded by a repeat count, like "3a".
+            let mut count = 0;
+            let register = loop {
+                let c = self.read_function_arg(&mut skipped);
+                match c.to_digit(10) {
+                    Some(digit) => {
+                        count = (count * 10 + usize::try_from(digit).unwrap()).min(MAX_MACRO_REPEAT)
                     }
+                    None => break c,
                 }
-                skipped.push(evt);
+            };
+            self.function_push_arg(register);
+            self.macro_repeat_counts.push(count.max(1));
+        } else {
+            for _ in 0..arity {
+                let arg = self.read_function_arg(&mut skipped);
+                self.function_push_arg(arg);
             }
-            self.function_push_arg(arg);
         }
 
         // Push the function codes back into the input stream.
@@ -536,6 +732,22 @@ impl Inputter {
         self.event_storage.clear();
     }
 
+    /// Read a character that is an argument to an input function. Any other events are added to
+    /// `skipped`, to be queued up again.
+    fn read_function_arg(&mut self, skipped: &mut Vec<CharEvent>) -> char {
+        // Skip and queue up any function codes. See issue #2357.
+        loop {
+            let evt = self.readch();
+            if let Some(kevt) = evt.get_key() {
+                if let Some(c) = kevt.key.codepoint_text() {
+                    // TODO forward the whole key
+                    return c;
+                }
+            }
+            skipped.push(evt);
+        }
+    }
+
     /// Perform the action of the specified binding.
     fn mapping_execute(&mut self, m: &InputMapping) {
         let has_command = m
@@ -565,7 +777,7 @@ impl Inputter {
             self.push_front(evt);
         }
         // Missing bind mode indicates to not reset the mode (#2871)
-        if let Some(mode) = m.sets_mode.as_ref() {
+        if let Some(mode) = m.options.sets_mode.as_ref() {
             self.push_front(CharEvent::Command(sprintf!(
                 "set --global %s %s",
                 FISH_BIND_MODE_VAR,
@@ -584,6 +796,92 @@ impl Inputter {
     pub fn function_set_status(&mut self, status: bool) {
         self.function_status = status;
     }
+
+    /// Ask the terminal where the cursor is, which relates the last mouse report to the screen.
+    /// The answer is announced by a CursorPositionReport event.
+    /// Return the position of the mouse, or None if the mouse has not been reported.
+    pub fn query_cursor_position(&mut self) -> Option<(usize, usize)> {
+        let mouse_position = self.mouse_position?;
+        self.cursor_position_awaited = true;
+        self.cursor_position = None;
+        let _ = write_to_fd(b"\x1b[6n", STDOUT_FILENO);
+        Some(mouse_position)
+    }
+
+    /// Return the cursor position reported by the terminal, unless it has already been taken.
+    pub fn take_cursor_position(&mut self) -> Option<(usize, usize)> {
+        self.cursor_position.take()
+    }
+
+    /// Return the keys of the incomplete sequence for which hints should be shown, announced by a
+    /// ShowSequenceHints event. If empty, hints should be hidden.
+    pub fn sequence_hint_prefix(&self) -> &[Key] {
+        self.sequence_hint_prefix.as_deref().unwrap_or_default()
+    }
+
+    /// Start recording the events returned by read_char into `register`, discarding any
+    /// recording in progress. Return false if `register` cannot hold a macro.
+    pub fn macro_begin(&mut self, register: char) -> bool {
+        if !is_macro_register(register) {
+            self.macro_recording = None;
+            return false;
+        }
+        self.macro_recording = Some((register, InputMacro::default()));
+        true
+    }
+
+    /// Stop recording, and store the recording in its register.
+    /// Return false if we were not recording.
+    pub fn macro_end(&mut self) -> bool {
+        let Some((register, recording)) = self.macro_recording.take() else {
+            return false;
+        };
+        let mut registers = MACRO_REGISTERS.lock().unwrap();
+        registers.macros.insert(register, recording);
+        registers.last = Some(register);
+        true
+    }
+
+    /// Enqueue the events of the macro in `register` `count` times, so they are returned next by
+    /// read_char. Return false if nothing has been recorded into it.
+    pub fn macro_play(&mut self, register: char, count: usize) -> bool {
+        let registers = MACRO_REGISTERS.lock().unwrap();
+        let Some(recording) = registers.get(register) else {
+            return false;
+        };
+        for _ in 0..count {
+            // Arguments are popped from the end, so the first function's argument must be last.
+            self.input_function_args.extend(recording.args.iter().rev());
+            self.insert_front(recording.events.iter().cloned());
+        }
+        true
+    }
+
+    /// Add an event returned by read_char to the macro being recorded.
+    fn macro_record(&mut self, evt: &CharEvent) {
+        let Some((_, recording)) = self.macro_recording.as_mut() else {
+            return;
+        };
+        match evt {
+            CharEvent::Readline(readline_event) => {
+                let cmd = readline_event.cmd;
+                if matches!(
+                    cmd,
+                    ReadlineCmd::BeginMacro | ReadlineCmd::EndMacro | ReadlineCmd::PlayMacro
+                ) {
+                    return;
+                }
+                // The arguments were read from the input already, and are waiting to be popped.
+                let arity = input_function_arity(cmd);
+                let first_arg = self.input_function_args.len().saturating_sub(arity);
+                let args = &self.input_function_args[first_arg..];
+                recording.args.extend(args.iter().rev());
+                recording.events.push(evt.clone());
+            }
+            CharEvent::Key(_) | CharEvent::Command(_) => recording.events.push(evt.clone()),
+            CharEvent::Eof | CharEvent::CheckExit => (),
+        }
+    }
 }
 
 /// A struct which allows accumulating input events, or returns them to the queue.
@@ -595,6 +893,12 @@ struct EventQueuePeeker<'q> {
     /// If set, then some previous timed event timed out.
     had_timeout: bool,
 
+    /// If set, the last key of a sequence did not arrive within the sequence's own timeout.
+    had_sequence_timeout: bool,
+
+    /// If set, the user paused long enough partway through a sequence that we should show hints.
+    had_hint_timeout: bool,
+
     /// The current index. This never exceeds peeked.len().
     idx: usize,
     /// The current index within a the raw characters within a single key event.
@@ -609,6 +913,8 @@ impl EventQueuePeeker<'_> {
         EventQueuePeeker {
             peeked: Vec::new(),
             had_timeout: false,
+            had_sequence_timeout: false,
+            had_hint_timeout: false,
             idx: 0,
             subidx: 0,
             event_queue,
@@ -634,7 +940,14 @@ impl EventQueuePeeker<'_> {
 
     /// Check if the next event is the given character. This advances the index on success only.
     /// If `escaped` is set, then return false if this (or any other) character had a timeout.
-    fn next_is_char(&mut self, style: &KeyNameStyle, key: Key, escaped: bool) -> bool {
+    /// `timeout` overrides how long to wait for a key that is not the first of a sequence.
+    fn next_is_char(
+        &mut self,
+        style: &KeyNameStyle,
+        key: Key,
+        escaped: bool,
+        timeout: Option<SequenceTimeout>,
+    ) -> bool {
         assert!(
             self.idx <= self.peeked.len(),
             "Index must not be larger than dequeued event count"
@@ -643,6 +956,9 @@ impl EventQueuePeeker<'_> {
         if escaped && self.had_timeout {
             return false;
         }
+        if self.had_hint_timeout && self.idx == self.peeked.len() {
+            return false;
+        }
         // Grab a new event if we have exhausted what we have already peeked.
         // Use either readch or readch_timed, per our param.
         if self.idx == self.peeked.len() {
@@ -655,6 +971,24 @@ impl EventQueuePeeker<'_> {
                         return false;
                     }
                 }
+            } else if let Some(delay) = self.sequence_hint_delay(style, timeout) {
+                FLOG!(reader, "readch timed sequence key before showing hints");
+                match self.event_queue.readch_timed(delay) {
+                    Some(evt) => evt,
+                    None => {
+                        self.had_hint_timeout = true;
+                        return false;
+                    }
+                }
+            } else if let Some(timeout) = timeout {
+                FLOG!(reader, "readch timed sequence key with binding timeout");
+                match self.event_queue.readch_timed(timeout.ms) {
+                    Some(evt) => evt,
+                    None => {
+                        self.had_sequence_timeout = true;
+                        return false;
+                    }
+                }
             } else {
                 FLOG!(reader, "readch timed sequence key");
                 match self.event_queue.readch_timed_sequence_key() {
@@ -678,7 +1012,7 @@ impl EventQueuePeeker<'_> {
             self.idx += 1;
             self.subidx = 0;
             FLOG!(reader, "matched delayed escape prefix in alt sequence");
-            return self.next_is_char(style, Key::from_raw(key.codepoint), true);
+            return self.next_is_char(style, Key::from_raw(key.codepoint), true, timeout);
         }
         if *style == KeyNameStyle::Plain {
             if kevt.key == key {
@@ -714,7 +1048,7 @@ impl EventQueuePeeker<'_> {
                     self.idx += 1;
                     self.subidx = 0;
                     FLOG!(reader, "matched escape prefix in raw escape sequence");
-                    return self.next_is_char(style, Key::from_raw(key.codepoint), true);
+                    return self.next_is_char(style, Key::from_raw(key.codepoint), true, timeout);
                 } else if actual_seq
                     .get(self.subidx + 1)
                     .cloned()
@@ -734,6 +1068,23 @@ impl EventQueuePeeker<'_> {
         false
     }
 
+    /// Return how long to wait for the next key of a sequence before showing hints, or None if we
+    /// should not show any.
+    fn sequence_hint_delay(
+        &self,
+        style: &KeyNameStyle,
+        timeout: Option<SequenceTimeout>,
+    ) -> Option<usize> {
+        // Only between keys of a sequence, and only once per sequence.
+        if self.idx == 0
+            || *style != KeyNameStyle::Plain
+            || self.event_queue.sequence_hint_prefix.is_some()
+        {
+            return None;
+        }
+        sequence_hint_delay_ms(timeout.map(|timeout| timeout.ms))
+    }
+
     /// Consume all events up to the current index.
     /// Remaining events are returned to the queue.
     fn consume(mut self) {
@@ -755,6 +1106,7 @@ impl EventQueuePeeker<'_> {
     fn restart(&mut self) {
         self.idx = 0;
         self.subidx = 0;
+        self.had_sequence_timeout = false;
     }
 }
 
@@ -768,8 +1120,15 @@ impl Drop for EventQueuePeeker<'_> {
     }
 }
 
-/// Return true if a given `peeker` matches a given sequence of char events given by `str`.
-fn try_peek_sequence(peeker: &mut EventQueuePeeker, style: &KeyNameStyle, seq: &[Key]) -> bool {
+/// Return true if a given `peeker` matches a given mapping's sequence of char events.
+/// `timeout` is how long to wait for each key after the first, or None for the global default.
+fn try_peek_sequence(
+    peeker: &mut EventQueuePeeker,
+    m: &InputMapping,
+    timeout: Option<SequenceTimeout>,
+) -> bool {
+    let style = &m.key_name_style;
+    let seq = &m.seq;
     assert!(
         !seq.is_empty(),
         "Empty sequence passed to try_peek_sequence"
@@ -779,7 +1138,7 @@ fn try_peek_sequence(peeker: &mut EventQueuePeeker, style: &KeyNameStyle, seq: &
         // If we just read an escape, we need to add a timeout for the next char,
         // to distinguish between the actual escape key and an "alt"-modifier.
         let escaped = *style != KeyNameStyle::Plain && prev == Key::from_raw(key::Escape);
-        if !peeker.next_is_char(style, *key, escaped) {
+        if !peeker.next_is_char(style, *key, escaped, timeout) {
             return false;
         }
         prev = *key;
@@ -820,7 +1179,8 @@ impl Inputter {
             }
 
             // FLOG!(reader, "trying mapping", format!("{:?}", m));
-            if try_peek_sequence(peeker, &m.key_name_style, &m.seq) {
+            let timeout = ip.timeout_of(m);
+            if try_peek_sequence(peeker, m, timeout) {
                 // A binding for just escape should also be deferred
                 // so escape sequences take precedence.
                 if m.seq == vec![Key::from_raw(key::Escape)] {
@@ -830,8 +1190,24 @@ impl Inputter {
                 } else {
                     return Some(m.clone());
                 }
+            } else if peeker.had_sequence_timeout
+                && timeout.is_some_and(|timeout| timeout.fallback == TimeoutFallback::Insert)
+            {
+                // The user stopped typing partway through this sequence and wants what they typed
+                // so far inserted, rather than looked up as a shorter binding.
+                FLOG!(reader, "sequence timed out, inserting typed keys");
+                return Some(InputMapping {
+                    seq: m.seq[..peeker.idx].to_vec(),
+                    commands: vec![L!("self-insert").to_owned()],
+                    options: InputMappingOptions::default(),
+                    ..m.clone()
+                });
             }
             peeker.restart();
+            if peeker.had_hint_timeout {
+                // The user paused partway through this sequence, so don't settle for a shorter one.
+                return None;
+            }
         }
         if peeker.char_sequence_interrupted() {
             // We might have matched a longer sequence, but we were interrupted, e.g. by a signal.
@@ -863,6 +1239,7 @@ impl Inputter {
             );
             peeker.consume();
             self.mapping_execute(&mapping);
+            self.end_sequence_hints();
             return;
         }
         peeker.restart();
@@ -876,9 +1253,33 @@ impl Inputter {
             return;
         }
 
+        if peeker.had_hint_timeout {
+            // Return the keys to the queue and have the reader show how they may continue. Then we
+            // wait for the rest of the sequence as usual.
+            FLOG!(reader, "sequence incomplete, showing hints");
+            let prefix = peeker
+                .peeked
+                .iter()
+                .filter_map(|evt| evt.get_key().map(|kevt| kevt.key))
+                .collect();
+            peeker.consume();
+            self.sequence_hint_prefix = Some(prefix);
+            self.push_front(CharEvent::from_readline(ReadlineCmd::ShowSequenceHints));
+            return;
+        }
+
         FLOG!(reader, "no generic found, ignoring char...");
         let _ = peeker.next();
         peeker.consume();
+        self.end_sequence_hints();
+    }
+
+    /// If we asked to show hints for an incomplete sequence, ask to hide them again, before
+    /// anything else that is queued.
+    fn end_sequence_hints(&mut self) {
+        if self.sequence_hint_prefix.take().is_some() {
+            self.push_front(CharEvent::from_readline(ReadlineCmd::ShowSequenceHints));
+        }
     }
 
     /// Helper function. Picks through the queue of incoming characters until we get to one that's not a
@@ -910,6 +1311,12 @@ impl Inputter {
 
     /// Read a key from stdin.
     pub fn read_char(&mut self) -> CharEvent {
+        let evt = self.read_char_unrecorded();
+        self.macro_record(&evt);
+        evt
+    }
+
+    fn read_char_unrecorded(&mut self) -> CharEvent {
         // Clear the interrupted flag.
         reader_reset_interrupted();
 
@@ -959,7 +1366,11 @@ impl Inputter {
                     // Allow the reader to check for exit conditions.
                     return evt;
                 }
-                CharEvent::Key(ref kevt) => {
+                CharEvent::Key(mut kevt) => {
+                    let Some(key) = downgrade_unbound_key(kevt.key) else {
+                        continue;
+                    };
+                    kevt.key = key;
                     FLOG!(
                         reader,
                         "Read char",
@@ -970,7 +1381,7 @@ impl Inputter {
                             kevt.seq.chars().map(u32::from).collect::<Vec<_>>()
                         )
                     );
-                    self.push_front(evt);
+                    self.push_front(CharEvent::Key(kevt));
                     self.mapping_execute_matching_or_generic();
                 }
             }
@@ -978,7 +1389,88 @@ impl Inputter {
     }
 }
 
+/// Keypad keys and key repeat and release events are only reported by terminals that implement
+/// the kitty keyboard protocol. Unless they are bound, treat them like the equivalent legacy key:
+/// keypad keys as normal keys and repeats as presses. Unbound releases are dropped.
+fn downgrade_unbound_key(mut key: Key) -> Option<Key> {
+    if !key.modifiers.keypad && key.modifiers.event == KeyEventType::Press {
+        return Some(key);
+    }
+    let mappings = input_mappings();
+    if key.modifiers.keypad && !mappings.is_key_bound(&key) {
+        key.modifiers.keypad = false;
+    }
+    match key.modifiers.event {
+        KeyEventType::Press => (),
+        _ if mappings.is_key_bound(&key) => (),
+        KeyEventType::Repeat => key.modifiers.event = KeyEventType::Press,
+        KeyEventType::Release => return None,
+    }
+    Some(key)
+}
+
 impl InputMappingSet {
+    /// Return whether any mapping uses the given key.
+    fn is_key_bound(&self, key: &Key) -> bool {
+        self.mapping_list
+            .iter()
+            .chain(self.preset_mapping_list.iter())
+            .any(|m| m.seq.contains(key))
+    }
+
+    /// Return how the sequences bound in the given mode may continue after `prefix`, as the
+    /// remaining keys and a description of what the binding does. A user binding shadows a preset
+    /// binding for the same sequence.
+    pub fn continuations(&self, prefix: &[Key], mode: &wstr) -> Vec<(WString, WString)> {
+        let mut seen: Vec<&[Key]> = vec![];
+        let mut result = vec![];
+        for m in self
+            .mapping_list
+            .iter()
+            .chain(self.preset_mapping_list.iter())
+        {
+            if m.mode != mode
+                || m.key_name_style != KeyNameStyle::Plain
+                || m.seq.len() <= prefix.len()
+                || !m.seq.starts_with(prefix)
+                || seen.contains(&m.seq.as_slice())
+            {
+                continue;
+            }
+            seen.push(&m.seq);
+            let keys: Vec<WString> = m.seq[prefix.len()..]
+                .iter()
+                .map(|&key| WString::from(key))
+                .collect();
+            let description = m
+                .options
+                .description
+                .clone()
+                .unwrap_or_else(|| join_strings(&m.commands, ' '));
+            result.push((join_strings(&keys, KEY_SEPARATOR), description));
+        }
+        result
+    }
+
+    /// Return the commands bound to `key` in the given mode, if any. `raw` is the sequence the key
+    /// was received as. A user binding shadows a preset binding for the same sequence.
+    pub fn binding_commands(&self, key: Key, raw: &wstr, mode: &wstr) -> Option<Vec<WString>> {
+        let raw_keys = canonicalize_raw_escapes(raw.chars().map(Key::from_raw).collect());
+        self.mapping_list
+            .iter()
+            .chain(self.preset_mapping_list.iter())
+            .find(|m| {
+                m.mode == mode
+                    && match m.key_name_style {
+                        KeyNameStyle::Plain => m.seq == [key],
+                        KeyNameStyle::RawEscapeSequence | KeyNameStyle::Terminfo(_) => {
+                            !raw_keys.is_empty() && m.seq == raw_keys
+                        }
+                    }
+            })
+            .map(|m| m.commands.clone())
+    }
+
     /// Returns all mapping names and modes.
     pub fn get_names(&self, user: bool) -> Vec<InputMappingName> {
         // Sort the mappings by the user specification order, so we can return them in the same order
@@ -998,15 +1490,20 @@ impl InputMappingSet {
             .collect()
     }
 
-    /// Erase all bindings.
+    /// Erase all bindings, and the sequence timeouts of their modes.
     pub fn clear(&mut self, mode: Option<&wstr>, user: bool) {
-        let ml = if user {
-            &mut self.mapping_list
+        let (ml, timeouts) = if user {
+            (&mut self.mapping_list, &mut self.mode_timeouts)
         } else {
-            &mut self.preset_mapping_list
+            (
+                &mut self.preset_mapping_list,
+                &mut self.preset_mode_timeouts,
+            )
         };
-        let should_erase = |m: &InputMapping| mode.is_none() || mode.unwrap() == m.mode;
-        ml.retain(|m| !should_erase(m));
+        let should_erase = |m: &wstr| mode.is_none() || mode.unwrap() == m;
+        ml.retain(|m| !should_erase(m.mode.as_utfstr()));
+        timeouts.retain(|m, _| !should_erase(m.as_utfstr()));
+        kitty_keyboard_set_flags(self.kitty_keyboard_flags());
     }
 
     /// Erase binding for specified key sequence.
@@ -1024,6 +1521,9 @@ impl InputMappingSet {
                 break;
             }
         }
+        if result {
+            kitty_keyboard_set_flags(self.kitty_keyboard_flags());
+        }
         result
     }
 
@@ -1035,7 +1535,7 @@ impl InputMappingSet {
         mode: &wstr,
         out_cmds: &mut &'a [WString],
         user: bool,
-        out_sets_mode: &mut Option<&'a wstr>,
+        out_options: &mut Option<&'a InputMappingOptions>,
         out_key_name_style: &mut KeyNameStyle,
     ) -> bool {
         let ml = if user {
@@ -1046,7 +1546,7 @@ impl InputMappingSet {
         for m in ml {
             if m.seq == sequence && m.mode == mode {
                 *out_cmds = &m.commands;
-                *out_sets_mode = m.sets_mode.as_deref();
+                *out_options = Some(&m.options);
                 *out_key_name_style = m.key_name_style.clone();
                 return true;
             }
@@ -1117,6 +1617,57 @@ fn create_input_terminfo() -> Box<[TerminfoMapping]> {
     ]);
 }
 
+/// Possible errors from input_last_macro_commands.
+pub enum MacroCommandsError {
+    /// No macro has been recorded.
+    NoMacro,
+    /// The macro contains a function with arguments, like forward-jump, which cannot be bound.
+    HasArguments,
+}
+
+/// Return the macro in `register` as a list of commands, suitable for a binding.
+/// Inserted characters become `commandline -i` invocations.
+pub fn input_macro_commands(register: char) -> Result<Vec<WString>, MacroCommandsError> {
+    let registers = MACRO_REGISTERS.lock().unwrap();
+    let recording = registers.get(register).ok_or(MacroCommandsError::NoMacro)?;
+    if !recording.args.is_empty() {
+        return Err(MacroCommandsError::HasArguments);
+    }
+
+    let mut commands = vec![];
+    let mut pending_text = WString::new();
+    let flush_text = |commands: &mut Vec<WString>, text: &mut WString| {
+        if !text.is_empty() {
+            commands.push(sprintf!("commandline -i -- %s", escape(text)));
+            text.clear();
+        }
+    };
+    for evt in &recording.events {
+        match evt {
+            CharEvent::Key(kevt) => {
+                if let Some(c) = kevt.key.codepoint_text() {
+                    pending_text.push(c);
+                }
+            }
+            CharEvent::Readline(readline_event) => {
+                // Internal functions like begin-paste have no name, and are implied by the
+                // commands around them.
+                if let Some(name) = input_function_get_name(readline_event.cmd) {
+                    flush_text(&mut commands, &mut pending_text);
+                    commands.push(name.to_owned());
+                }
+            }
+            CharEvent::Command(cmd) => {
+                flush_text(&mut commands, &mut pending_text);
+                commands.push(cmd.clone());
+            }
+            CharEvent::Eof | CharEvent::CheckExit => (),
+        }
+    }
+    flush_text(&mut commands, &mut pending_text);
+    Ok(commands)
+}
+
 /// Possible errors from from input_terminfo_get_sequence.
 pub enum GetSequenceError {
     /// The mapping was not found.
@@ -1189,3 +1740,11 @@ pub fn input_function_get_code(name: &wstr) -> Option<ReadlineCmd> {
     // a binary search for the matching name.
     get_by_sorted_name(name, INPUT_FUNCTION_METADATA).map(|md| md.code)
 }
+
+/// Return the name of the given input function, or None for internal functions.
+pub fn input_function_get_name(code: ReadlineCmd) -> Option<&'static wstr> {
+    INPUT_FUNCTION_METADATA
+        .iter()
+        .find(|md| md.code == code && !md.name.is_empty())
+        .map(|md| md.name)
+}
@@ -9,12 +9,19 @@ use crate::flog::FLOG;
 use crate::global_safety::RelaxedAtomicBool;
 use crate::key::{
     self, alt, canonicalize_control_char, canonicalize_keyed_control_char, function_key, shift,
-    Key, Modifiers,
+    Key, KeyEventType, Modifiers,
 };
 use crate::reader::{reader_current_data, reader_test_and_clear_interrupted};
+use crate::terminal::{
+    parse_dcs_reply, parse_decrpm_reply, parse_osc_reply, terminal_primary_device_attributes,
+    terminal_probe_check_timeout, terminal_probe_is_pending, terminal_probe_record,
+    terminal_probe_send_ifn, terminal_probe_time_left, terminal_record_focus, terminal_supports,
+    ProbeReply, TerminalFeature,
+};
 use crate::threads::{iothread_port, iothread_service_main, MainThread};
 use crate::universal_notifier::default_notifier;
 use crate::wchar::{encode_byte_to_char, prelude::*};
+use crate::wcstringutil::bool_from_string;
 use crate::wutil::encoding::{mbrtowc, mbstate_t, zero_mbstate};
 use crate::wutil::{fish_wcstol, write_to_fd};
 use std::cell::RefCell;
@@ -22,7 +29,7 @@ use std::collections::VecDeque;
 use std::ops::ControlFlow;
 use std::os::fd::RawFd;
 use std::ptr;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 
 // The range of key codes for inputrc-style keyboard functions.
 pub const R_END_INPUT_FUNCTIONS: usize = (ReadlineCmd::ReverseRepeatJump as usize) + 1;
@@ -118,12 +125,24 @@ pub enum ReadlineCmd {
     Cancel,
     Undo,
     Redo,
+    RestoreDraft,
+    BeginMacro,
+    EndMacro,
+    PlayMacro,
+    CorrectCommand,
+    MouseClick,
+    ScrollUp,
+    ScrollDown,
     BeginUndoGroup,
     EndUndoGroup,
     RepeatJump,
     DisableMouseTracking,
     FocusIn,
     FocusOut,
+    BeginPaste,
+    EndPaste,
+    CursorPositionReport,
+    ShowSequenceHints,
     // ncurses uses the obvious name
     ClearScreenAndRepaint,
     // NOTE: This one has to be last.
@@ -285,6 +304,10 @@ static WAIT_ON_ESCAPE_MS: AtomicUsize = AtomicUsize::new(WAIT_ON_ESCAPE_DEFAULT)
 const WAIT_ON_SEQUENCE_KEY_INFINITE: usize = usize::MAX;
 static WAIT_ON_SEQUENCE_KEY_MS: AtomicUsize = AtomicUsize::new(WAIT_ON_SEQUENCE_KEY_INFINITE);
 
+/// Time in milliseconds to wait for the next key of a sequence before showing how it continues.
+const SEQUENCE_HINT_DELAY_DISABLED: usize = usize::MAX;
+static SEQUENCE_HINT_DELAY_MS: AtomicUsize = AtomicUsize::new(SEQUENCE_HINT_DELAY_DISABLED);
+
 /// Internal function used by readch to read one byte.
 /// This calls select() on three fds: input (e.g. stdin), the ioport notifier fd (for main thread
 /// requests), and the uvar notifier. This returns either the byte which was read, or one of the
@@ -306,6 +329,9 @@ enum ReadbResult {
     IOPortNotified,
 
     NothingToRead,
+
+    // We stopped waiting, to give up on the terminal answering our probe.
+    TimedOut,
 }
 
 fn readb(in_fd: RawFd, blocking: bool) -> ReadbResult {
@@ -326,12 +352,16 @@ fn readb(in_fd: RawFd, blocking: bool) -> ReadbResult {
             fdset.add(notifier_fd);
         }
 
-        // Here's where we call select().
-        let select_res = fdset.check_readable(if blocking {
-            FdReadableSet::kNoTimeout
-        } else {
+        // Here's where we call select(). While we wait for the terminal to answer our probe, wake
+        // up in time to give up on it.
+        let timeout = if !blocking {
             0
-        });
+        } else if let Some(time_left) = terminal_probe_time_left() {
+            u64::try_from(time_left.as_micros()).unwrap()
+        } else {
+            FdReadableSet::kNoTimeout
+        };
+        let select_res = fdset.check_readable(timeout);
         if select_res < 0 {
             let err = errno::errno().0;
             if err == libc::EINTR || err == libc::EAGAIN {
@@ -369,6 +399,9 @@ fn readb(in_fd: RawFd, blocking: bool) -> ReadbResult {
         if !blocking {
             return ReadbResult::NothingToRead;
         }
+        if select_res == 0 {
+            return ReadbResult::TimedOut;
+        }
 
         // Check for iothread completions only if there is no data to be read from the stdin.
         // This gives priority to the foreground.
@@ -428,17 +461,112 @@ pub fn update_wait_on_sequence_key_ms(vars: &EnvStack) {
     }
 }
 
+// Update the sequence hint delay in response to the fish_sequence_hint_delay_ms user variable being
+// set.
+pub fn update_sequence_hint_delay_ms(vars: &EnvStack) {
+    let sequence_hint_delay_ms = vars.get_unless_empty(L!("fish_sequence_hint_delay_ms"));
+    let Some(sequence_hint_delay_ms) = sequence_hint_delay_ms else {
+        SEQUENCE_HINT_DELAY_MS.store(SEQUENCE_HINT_DELAY_DISABLED, Ordering::Relaxed);
+        return;
+    };
+    let sequence_hint_delay_ms = sequence_hint_delay_ms.as_string();
+    match fish_wcstol(&sequence_hint_delay_ms) {
+        Ok(val) if (10..5000).contains(&val) => {
+            SEQUENCE_HINT_DELAY_MS.store(val.try_into().unwrap(), Ordering::Relaxed);
+        }
+        _ => {
+            eprintln!(
+                concat!(
+                    "ignoring fish_sequence_hint_delay_ms: value '{}' ",
+                    "is not an integer or is < 10 or >= 5000 ms"
+                ),
+                sequence_hint_delay_ms
+            )
+        }
+    }
+}
+
+/// Return how long to wait for the next key of a sequence before showing hints, if at all.
+/// `timeout_ms` is how long we would wait for that key in total.
+pub(crate) fn sequence_hint_delay_ms(timeout_ms: Option<usize>) -> Option<usize> {
+    let delay = SEQUENCE_HINT_DELAY_MS.load(Ordering::Relaxed);
+    let timeout = timeout_ms.unwrap_or(WAIT_ON_SEQUENCE_KEY_MS.load(Ordering::Relaxed));
+    // Without a delay, or if the sequence times out first, there is no time for hints.
+    (delay != SEQUENCE_HINT_DELAY_DISABLED && delay < timeout).then_some(delay)
+}
+
 static TERMINAL_PROTOCOLS: MainThread<RefCell<Option<TerminalProtocols>>> =
     MainThread::new(RefCell::new(None));
 
 pub(crate) static IS_TMUX: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
 
+/// Whether to ask the terminal for mouse reports, see $fish_mouse.
+static MOUSE_TRACKING: RelaxedAtomicBool = RelaxedAtomicBool::new(false);
+
+// Update whether we track the mouse in response to the fish_mouse user variable being set.
+// This takes effect the next time the terminal protocols are enabled.
+pub fn update_mouse_tracking(vars: &EnvStack) {
+    let enabled = vars
+        .get_unless_empty(L!("fish_mouse"))
+        .is_some_and(|v| bool_from_string(&v.as_string()));
+    MOUSE_TRACKING.store(enabled);
+}
+
+// Progressive enhancement flags of the kitty keyboard protocol.
+const KITTY_DISAMBIGUATE_ESCAPE_CODES: u32 = 1;
+const KITTY_REPORT_EVENT_TYPES: u32 = 2;
+const KITTY_REPORT_ALTERNATE_KEYS: u32 = 4;
+const KITTY_REPORT_ALL_KEYS_AS_ESCAPE_CODES: u32 = 8;
+const KITTY_REPORT_ASSOCIATED_TEXT: u32 = 16;
+
+/// The kitty keyboard flags we always ask the terminal for.
+const KITTY_KEYBOARD_BASE_FLAGS: u32 =
+    KITTY_DISAMBIGUATE_ESCAPE_CODES | KITTY_REPORT_ALTERNATE_KEYS;
+
+/// The kitty keyboard flags we ask the terminal for. Release and repeat events as well as keypad
+/// keys are only requested while something is bound to them, because they make the terminal encode
+/// every key as an escape sequence.
+static KITTY_KEYBOARD_FLAGS: AtomicU32 = AtomicU32::new(KITTY_KEYBOARD_BASE_FLAGS);
+
+/// Return the kitty keyboard flags the terminal needs to report `key`, beyond the ones we always
+/// ask for.
+pub(crate) fn kitty_keyboard_flags_for_key(key: &Key) -> u32 {
+    // Mouse buttons are reported independently.
+    if (key::MouseLeft..=key::WheelDown).contains(&key.codepoint) {
+        return 0;
+    }
+    let mut flags = 0;
+    if key.modifiers.event != KeyEventType::Press {
+        flags |= KITTY_REPORT_EVENT_TYPES
+            | KITTY_REPORT_ALL_KEYS_AS_ESCAPE_CODES
+            | KITTY_REPORT_ASSOCIATED_TEXT;
+    }
+    if key.modifiers.keypad {
+        flags |= KITTY_REPORT_ALL_KEYS_AS_ESCAPE_CODES | KITTY_REPORT_ASSOCIATED_TEXT;
+    }
+    flags
+}
+
+/// Ask the terminal for the given kitty keyboard flags on top of the ones we always ask for,
+/// replacing any earlier request. This takes effect the next time the terminal protocols are
+/// enabled.
+pub(crate) fn kitty_keyboard_set_flags(flags: u32) {
+    KITTY_KEYBOARD_FLAGS.store(KITTY_KEYBOARD_BASE_FLAGS | flags, Ordering::Relaxed);
+}
+
 pub(crate) fn terminal_protocols_enable_ifn() {
     let mut term_protocols = TERMINAL_PROTOCOLS.get().borrow_mut();
-    if term_protocols.is_some() {
+    let settings = ProtocolSettings::current();
+    if term_protocols
+        .as_ref()
+        .is_some_and(|protocols| protocols.settings == settings)
+    {
         return;
     }
-    *term_protocols = Some(TerminalProtocols::new());
+    // Disable the old protocols first, so the kitty flags are popped before we push new ones.
+    *term_protocols = None;
+    *term_protocols = Some(TerminalProtocols::new(settings));
+    terminal_probe_send_ifn();
     reader_current_data().map(|data| data.save_screen_state());
 }
 
@@ -452,15 +580,44 @@ pub(crate) fn terminal_protocols_try_disable_ifn() {
     }
 }
 
-struct TerminalProtocols {}
+/// The optional protocols we enable.
+#[derive(Clone, Copy, Eq, PartialEq)]
+struct ProtocolSettings {
+    kitty_flags: u32,
+    modify_other_keys: bool,
+    mouse: bool,
+}
+
+impl ProtocolSettings {
+    fn current() -> Self {
+        Self {
+            kitty_flags: KITTY_KEYBOARD_FLAGS.load(Ordering::Relaxed),
+            // Terminals that speak the kitty keyboard protocol don't need the older alternative.
+            modify_other_keys: !terminal_supports(TerminalFeature::KittyKeyboard),
+            mouse: MOUSE_TRACKING.load(),
+        }
+    }
+}
+
+struct TerminalProtocols {
+    settings: ProtocolSettings,
+}
 
 impl TerminalProtocols {
-    fn new() -> Self {
-        let sequences = concat!(
-            "\x1b[?2004h", // Bracketed paste
-            "\x1b[>4;1m",  // XTerm's modifyOtherKeys
-            "\x1b[>5u",    // CSI u with kitty progressive enhancement
-            "\x1b=",       // set application keypad mode, so the keypad keys send unique codes
+    fn new(settings: ProtocolSettings) -> Self {
+        let sequences = format!(
+            concat!(
+                "\x1b[?2004h", // Bracketed paste
+                "{}",          // XTerm's modifyOtherKeys
+                "\x1b[>{}u",   // CSI u with kitty progressive enhancement
+                "\x1b=",       // set application keypad mode, so the keypad keys send unique codes
+            ),
+            if settings.modify_other_keys {
+                "\x1b[>4;1m"
+            } else {
+                ""
+            },
+            settings.kitty_flags
         );
         FLOG!(
             term_protocols,
@@ -473,7 +630,11 @@ impl TerminalProtocols {
         if IS_TMUX.load() {
             let _ = write_to_fd("\x1b[?1004h".as_bytes(), STDOUT_FILENO);
         }
-        Self {}
+        if settings.mouse {
+            // Button presses and releases, in SGR encoding.
+            let _ = write_to_fd("\x1b[?1000h\x1b[?1006h".as_bytes(), STDOUT_FILENO);
+        }
+        Self { settings }
     }
 }
 
@@ -496,6 +657,9 @@ impl Drop for TerminalProtocols {
         if IS_TMUX.load() {
             let _ = write_to_fd("\x1b[?1004l".as_bytes(), STDOUT_FILENO);
         }
+        if self.settings.mouse {
+            let _ = write_to_fd("\x1b[?1006l\x1b[?1000l".as_bytes(), STDOUT_FILENO);
+        }
     }
 }
 
@@ -504,6 +668,16 @@ fn parse_mask(mask: u32) -> Modifiers {
         ctrl: (mask & 4) != 0,
         alt: (mask & 2) != 0,
         shift: (mask & 1) != 0,
+        ..Default::default()
+    }
+}
+
+/// Parse the event type of the kitty keyboard protocol, which defaults to a press.
+fn parse_event_type(event_type: u32) -> KeyEventType {
+    match event_type {
+        2 => KeyEventType::Repeat,
+        3 => KeyEventType::Release,
+        _ => KeyEventType::Press,
     }
 }
 
@@ -529,6 +703,7 @@ pub trait InputEventQueuer {
 
             // We are going to block; but first allow any override to inject events.
             self.prepare_to_select();
+            terminal_probe_check_timeout();
             if let Some(mevt) = self.try_pop() {
                 return mevt;
             }
@@ -551,6 +726,8 @@ pub trait InputEventQueuer {
                     iothread_service_main(reader_current_data().unwrap());
                 }
 
+                ReadbResult::TimedOut => {}
+
                 ReadbResult::Byte(read_byte) => {
                     let mut have_escape_prefix = false;
                     let mut buffer = vec![read_byte];
@@ -645,6 +822,17 @@ pub trait InputEventQueuer {
             // potential SS3
             return Some(self.parse_ss3(buffer).unwrap_or(alt('O')));
         }
+        if next == b'P' && terminal_probe_is_pending() {
+            // Device control string, which is only expected as answer to our probe. Otherwise this
+            // is alt-P.
+            self.parse_dcs(buffer);
+            return Some(Key::from_raw(key::Invalid));
+        }
+        if next == b']' && terminal_probe_is_pending() {
+            // Operating system command, which is likewise only expected as answer to our probe.
+            self.parse_osc(buffer);
+            return Some(Key::from_raw(key::Invalid));
+        }
         match canonicalize_control_char(next) {
             Some(mut key) => {
                 key.modifiers.alt = true;
@@ -758,6 +946,7 @@ pub trait InputEventQueuer {
         let masked_key = |mut codepoint, shifted_codepoint| {
             let mask = params[1][0].saturating_sub(1);
             let mut modifiers = parse_mask(mask);
+            modifiers.event = parse_event_type(params[1][1]);
             if let Some(shifted_codepoint) = shifted_codepoint {
                 if shifted_codepoint != '\0' && modifiers.shift {
                     modifiers.shift = false;
@@ -773,8 +962,11 @@ pub trait InputEventQueuer {
         let key = match c {
             b'$' => {
                 if private_mode == Some(b'?') && next_char(self) == b'y' {
-                    // DECRPM
-                    return None;
+                    // DECRPM, which we only ask for synchronized output.
+                    if let Some(reply) = parse_decrpm_reply(params[0][0], params[1][0]) {
+                        terminal_probe_record(reply);
+                    }
+                    return Some(Key::from_raw(key::Invalid));
                 }
                 match params[0][0] {
                     23 | 24 => shift(
@@ -785,11 +977,48 @@ pub trait InputEventQueuer {
             }
             b'A' | b'a' => masked_key(key::Up, None),
             b'B' | b'b' => masked_key(key::Down, None),
+            b'c' if private_mode == Some(b'?') => {
+                // Primary device attributes, the last answer to our probe or focus query.
+                terminal_primary_device_attributes();
+                return Some(Key::from_raw(key::Invalid));
+            }
             b'C' | b'c' => masked_key(key::Right, None),
             b'D' | b'd' => masked_key(key::Left, None),
-            b'E' => masked_key('5', None),       // Numeric keypad
+            b'E' => {
+                // Numeric keypad
+                let mut key = masked_key('5', None);
+                key.modifiers.keypad = true;
+                key
+            }
             b'F' => masked_key(key::End, None),  // PC/xterm style
             b'H' => masked_key(key::Home, None), // PC/xterm style
+            b'M' | b'm' if private_mode == Some(b'<') && MOUSE_TRACKING.load() => {
+                // SGR mouse report, with semicolon-separated parameters for button code, Px and
+                // Py, ending with 'M' for button press or 'm' for button release.
+                let button = params[0][0];
+                let (Some(x), Some(y)) = (params[1][0].checked_sub(1), params[2][0].checked_sub(1))
+                else {
+                    return None;
+                };
+                self.mouse_position_set(x as usize, y as usize);
+                let codepoint = match button & !(4 | 8 | 16) {
+                    0 => key::MouseLeft,
+                    1 => key::MouseMiddle,
+                    2 => key::MouseRight,
+                    64 => key::WheelUp,
+                    65 => key::WheelDown,
+                    // Motion and other buttons.
+                    _ => return Some(Key::from_raw(key::Invalid)),
+                };
+                let mut modifiers = parse_mask((button >> 2) & 7);
+                if c == b'm' {
+                    modifiers.event = KeyEventType::Release;
+                }
+                Key {
+                    modifiers,
+                    codepoint,
+                }
+            }
             b'M' | b'm' => {
                 self.disable_mouse_tracking();
                 let sgr = private_mode == Some(b'<');
@@ -827,6 +1056,16 @@ pub trait InputEventQueuer {
             }
             b'P' => masked_key(function_key(1), None),
             b'Q' => masked_key(function_key(2), None),
+            b'R' if private_mode.is_none() && self.cursor_position_awaited() => {
+                // Cursor position report, which looks like F3 with modifiers.
+                let (Some(y), Some(x)) = (params[0][0].checked_sub(1), params[1][0].checked_sub(1))
+                else {
+                    return None;
+                };
+                self.cursor_position_set(x as usize, y as usize);
+                self.push_front(CharEvent::from_readline(ReadlineCmd::CursorPositionReport));
+                return Some(Key::from_raw(key::Invalid));
+            }
             b'R' => masked_key(function_key(3), None),
             b'S' => masked_key(function_key(4), None),
             b'~' => match params[0][0] {
@@ -872,52 +1111,82 @@ pub trait InputEventQueuer {
                 }
                 _ => return None,
             },
+            b'u' if private_mode == Some(b'?') => {
+                // The current flags of the kitty keyboard protocol, so the terminal supports it.
+                terminal_probe_record(ProbeReply::Feature(TerminalFeature::KittyKeyboard));
+                return Some(Key::from_raw(key::Invalid));
+            }
             b'u' => {
-                // Treat numpad keys the same as their non-numpad counterparts. Could add a numpad modifier here.
-                let key = match params[0][0] {
-                    57399 => '0',
-                    57400 => '1',
-                    57401 => '2',
-                    57402 => '3',
-                    57403 => '4',
-                    57404 => '5',
-                    57405 => '6',
-                    57406 => '7',
-                    57407 => '8',
-                    57408 => '9',
-                    57409 => '.',
-                    57410 => '/',
-                    57411 => '*',
-                    57412 => '-',
-                    57413 => '+',
-                    57414 => key::Enter,
-                    57415 => '=',
-                    57417 => key::Left,
-                    57418 => key::Right,
-                    57419 => key::Up,
-                    57420 => key::Down,
-                    57421 => key::PageUp,
-                    57422 => key::PageDown,
-                    57423 => key::Home,
-                    57424 => key::End,
-                    57425 => key::Insert,
-                    57426 => key::Delete,
-                    cp => canonicalize_keyed_control_char(char::from_u32(cp).unwrap()),
+                // Keys that only change modifiers or lock state are reported once we ask for
+                // every key as escape sequence. They are not bindable.
+                if matches!(params[0][0], 57358..=57360 | 57441..=57454) {
+                    return Some(Key::from_raw(key::Invalid));
+                }
+                // Numpad keys are the same as their non-numpad counterparts, plus a modifier.
+                let (key, keypad) = match params[0][0] {
+                    57399 => ('0', true),
+                    57400 => ('1', true),
+                    57401 => ('2', true),
+                    57402 => ('3', true),
+                    57403 => ('4', true),
+                    57404 => ('5', true),
+                    57405 => ('6', true),
+                    57406 => ('7', true),
+                    57407 => ('8', true),
+                    57408 => ('9', true),
+                    57409 => ('.', true),
+                    57410 => ('/', true),
+                    57411 => ('*', true),
+                    57412 => ('-', true),
+                    57413 => ('+', true),
+                    57414 => (key::Enter, true),
+                    57415 => ('=', true),
+                    57417 => (key::Left, true),
+                    57418 => (key::Right, true),
+                    57419 => (key::Up, true),
+                    57420 => (key::Down, true),
+                    57421 => (key::PageUp, true),
+                    57422 => (key::PageDown, true),
+                    57423 => (key::Home, true),
+                    57424 => (key::End, true),
+                    57425 => (key::Insert, true),
+                    57426 => (key::Delete, true),
+                    cp => (canonicalize_keyed_control_char(char::from_u32(cp)?), false),
                 };
-                masked_key(
+                let mut key = masked_key(
                     key,
-                    Some(canonicalize_keyed_control_char(
-                        char::from_u32(params[0][1]).unwrap(),
-                    )),
-                )
+                    Some(canonicalize_keyed_control_char(char::from_u32(
+                        params[0][1],
+                    )?)),
+                );
+                key.modifiers.keypad = keypad;
+                // The associated text is what the key would insert, which may differ from the
+                // key itself, for example with a keyboard layout or an input method.
+                let text: Vec<char> = params[2]
+                    .iter()
+                    .take_while(|&&cp| cp != 0)
+                    .filter_map(|&cp| char::from_u32(cp))
+                    .collect();
+                if let Some((&first, rest)) = text.split_first() {
+                    if !key.modifiers.ctrl && !key.modifiers.alt && !keypad {
+                        key.modifiers.shift = false;
+                        key.codepoint = first;
+                        self.insert_front(rest.iter().copied().map(CharEvent::from_char));
+                    }
+                }
+                key
             }
             b'Z' => shift(key::Tab),
             b'I' => {
-                self.push_front(CharEvent::from_readline(ReadlineCmd::FocusIn));
+                if terminal_record_focus(true) {
+                    self.push_front(CharEvent::from_readline(ReadlineCmd::FocusIn));
+                }
                 return Some(Key::from_raw(key::Invalid));
             }
             b'O' => {
-                self.push_front(CharEvent::from_readline(ReadlineCmd::FocusOut));
+                if terminal_record_focus(false) {
+                    self.push_front(CharEvent::from_readline(ReadlineCmd::FocusOut));
+                }
                 return Some(Key::from_raw(key::Invalid));
             }
             _ => return None,
@@ -925,6 +1194,43 @@ pub trait InputEventQueuer {
         Some(key)
     }
 
+    /// Parse a device control string up to the string terminator, and record what it tells us
+    /// about the terminal.
+    fn parse_dcs(&mut self, buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+        loop {
+            let Some(b) = self.try_readb(buffer) else {
+                return;
+            };
+            if b == b'\\' && buffer.len() >= start + 2 && buffer[buffer.len() - 2] == 0x1b {
+                break;
+            }
+        }
+        if let Some(reply) = parse_dcs_reply(&buffer[start..buffer.len() - 2]) {
+            terminal_probe_record(reply);
+        }
+    }
+
+    /// Parse an operating system command up to the string terminator or BEL, and record what it
+    /// tells us about the terminal.
+    fn parse_osc(&mut self, buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+        let end = loop {
+            let Some(b) = self.try_readb(buffer) else {
+                return;
+            };
+            if b == 0x07 {
+                break buffer.len() - 1;
+            }
+            if b == b'\\' && buffer.len() >= start + 2 && buffer[buffer.len() - 2] == 0x1b {
+                break buffer.len() - 2;
+            }
+        };
+        for reply in parse_osc_reply(&buffer[start..end]) {
+            terminal_probe_record(reply);
+        }
+    }
+
     fn disable_mouse_tracking(&mut self) {
         // fish recognizes but does not actually support mouse reporting. We never turn it on, and
         // it's only ever enabled if a program we spawned enabled it and crashed or forgot to turn
@@ -951,7 +1257,7 @@ pub trait InputEventQueuer {
         }
         let modifiers = parse_mask(raw_mask.saturating_sub(1));
         #[rustfmt::skip]
-        let key = match code {
+        let mut key = match code {
             b' ' => Key{modifiers, codepoint: key::Space},
             b'A' | b'a' => Key{modifiers, codepoint: key::Up},
             b'B' | b'b' => Key{modifiers, codepoint: key::Down},
@@ -984,6 +1290,8 @@ pub trait InputEventQueuer {
             b'y' => Key{modifiers, codepoint: '9'},
             _ => return None,
         };
+        // Application keypad mode.
+        key.modifiers.keypad = matches!(code, b'X' | b'j'..=b'y');
         Some(key)
     }
 
@@ -1077,6 +1385,15 @@ pub trait InputEventQueuer {
     fn paste_push_char(&mut self, _b: u8) {}
     fn paste_commit(&mut self);
 
+    // Support for mouse tracking.
+    // Mouse reports are relative to the top left corner of the terminal. To relate them to our
+    // screen, we ask the terminal where the cursor is. Positions are zero-based.
+    fn mouse_position_set(&mut self, _x: usize, _y: usize) {}
+    fn cursor_position_awaited(&self) -> bool {
+        false
+    }
+    fn cursor_position_set(&mut self, _x: usize, _y: usize) {}
+
     /// Enqueue a character or a readline function to the queue of unread characters that
     /// readch will return before actually reading from fd 0.
     fn push_back(&mut self, ch: CharEvent) {
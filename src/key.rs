@@ -30,6 +30,12 @@ pub(crate) fn function_key(n: u32) -> char {
     assert!((1..=12).contains(&n));
     char::from_u32(u32::from(Invalid) + n).unwrap()
 }
+// Mouse buttons, only reported if mouse tracking is enabled.
+pub(crate) const MouseLeft: char = '\u{F51C}';
+pub(crate) const MouseMiddle: char = '\u{F51D}';
+pub(crate) const MouseRight: char = '\u{F51E}';
+pub(crate) const WheelUp: char = '\u{F51F}';
+pub(crate) const WheelDown: char = '\u{F520}';
 
 const KEY_NAMES: &[(char, &wstr)] = &[
     ('-', L!("minus")),
@@ -49,13 +55,31 @@ const KEY_NAMES: &[(char, &wstr)] = &[
     (Insert, L!("insert")),
     (Tab, L!("tab")),
     (Space, L!("space")),
+    (MouseLeft, L!("mouseleft")),
+    (MouseMiddle, L!("mousemiddle")),
+    (MouseRight, L!("mouseright")),
+    (WheelUp, L!("wheelup")),
+    (WheelDown, L!("wheeldown")),
 ];
 
+/// What happened to a key. Only terminals implementing the kitty keyboard protocol report
+/// anything but presses, and only if a binding asks for it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum KeyEventType {
+    #[default]
+    Press,
+    Repeat,
+    Release,
+}
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct Modifiers {
     pub ctrl: bool,
     pub alt: bool,
     pub shift: bool,
+    /// The key is on the numeric keypad.
+    pub keypad: bool,
+    pub event: KeyEventType,
 }
 
 impl Modifiers {
@@ -64,6 +88,8 @@ impl Modifiers {
             ctrl: false,
             alt: false,
             shift: false,
+            keypad: false,
+            event: KeyEventType::Press,
         }
     }
     pub(crate) const ALT: Self = {
@@ -272,6 +298,9 @@ pub(crate) fn parse_keys(value: &wstr) -> Result<Vec<Key>, WString> {
                     _ if modifier == "ctrl" || modifier == "c" => modifiers.ctrl = true,
                     _ if modifier == "alt" || modifier == "a" => modifiers.alt = true,
                     _ if modifier == "shift" => modifiers.shift = true,
+                    _ if modifier == "keypad" => modifiers.keypad = true,
+                    _ if modifier == "repeat" => modifiers.event = KeyEventType::Repeat,
+                    _ if modifier == "release" => modifiers.event = KeyEventType::Release,
                     _ => {
                         return Err(wgettext_fmt!(
                             "unknown modifier '%s' in '%s'",
@@ -350,7 +379,7 @@ pub(crate) fn canonicalize_raw_escapes(keys: Vec<Key>) -> Vec<Key> {
 
 impl Key {
     pub(crate) fn codepoint_text(&self) -> Option<char> {
-        if self.modifiers.is_some() {
+        if self.modifiers.is_some() || self.modifiers.event == KeyEventType::Release {
             return None;
         }
         let c = self.codepoint;
@@ -399,6 +428,9 @@ impl From<Key> for WString {
             });
         let mut res = name.unwrap_or_else(|| char_to_symbol(key.codepoint));
 
+        if key.modifiers.keypad {
+            res.insert_utfstr(0, L!("keypad-"));
+        }
         if key.modifiers.shift {
             res.insert_utfstr(0, L!("shift-"));
         }
@@ -408,6 +440,11 @@ impl From<Key> for WString {
         if key.modifiers.ctrl {
             res.insert_utfstr(0, L!("ctrl-"));
         }
+        match key.modifiers.event {
+            KeyEventType::Press => (),
+            KeyEventType::Repeat => res.insert_utfstr(0, L!("repeat-")),
+            KeyEventType::Release => res.insert_utfstr(0, L!("release-")),
+        }
 
         res
     }
@@ -0,0 +1,109 @@
+//! Recording and replaying key presses.
+//!
+//! `fish_key_reader --record FILE` writes one line per key, with tab-separated fields: the
+//! milliseconds since the previous key, the characters the terminal sent for it as escaped by fish,
+//! and the key name as a comment. For example:
+//!
+//! ```text
+//! 0	\cx	# ctrl-x
+//! 120	\ce	# ctrl-e
+//! ```
+//!
+//! `fish --replay-keys FILE` sends these characters to fish with the same delays, as if they were
+//! typed, so they go through the same decoding and bindings.
+
+use crate::common::{
+    escape_string, str2wcstring, unescape_string, wcs2osstring, wcs2string, EscapeFlags,
+    EscapeStringStyle, UnescapeStringStyle,
+};
+use crate::fds::make_autoclose_pipes;
+use crate::key::Key;
+use crate::wchar::prelude::*;
+use crate::wutil::fish_wcstoul;
+use libc::STDIN_FILENO;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+/// The first line of a recording.
+pub const KEY_RECORDING_HEADER: &str = "# fish key recording\n";
+
+/// A key press in a recording.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecordedKey {
+    /// How long after the previous key this one was pressed.
+    pub delay: Duration,
+    /// The characters the terminal sent.
+    pub seq: WString,
+}
+
+/// Return the line to record for `key`, which arrived as `seq` after `delay`.
+pub fn key_recording_line(delay: Duration, seq: &wstr, key: Key) -> WString {
+    // Without quotes, the escaped sequence contains no tabs.
+    let seq = escape_string(seq, EscapeStringStyle::Script(EscapeFlags::NO_QUOTED));
+    let delay = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+    sprintf!("%lu\t%ls\t# %ls\n", delay, seq, key)
+}
+
+/// Parse a recording. On failure, return the number of the first line that could not be parsed.
+pub fn parse_key_recording(contents: &wstr) -> Result<Vec<RecordedKey>, usize> {
+    let mut keys = vec![];
+    for (i, line) in contents.split('\n').enumerate() {
+        if line.is_empty() || line.char_at(0) == '#' {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let delay = fields
+            .next()
+            .and_then(|delay| fish_wcstoul(delay).ok())
+            .ok_or(i + 1)?;
+        let seq = fields
+            .next()
+            .and_then(|seq| unescape_string(seq, UnescapeStringStyle::default()))
+            .ok_or(i + 1)?;
+        if fields
+            .next()
+            .is_some_and(|comment| !comment.starts_with("#"))
+        {
+            return Err(i + 1);
+        }
+        keys.push(RecordedKey {
+            delay: Duration::from_millis(delay),
+            seq,
+        });
+    }
+    Ok(keys)
+}
+
+/// Read the recording at `path`, and arrange for its keys to arrive on stdin, in the background.
+/// Once all keys are sent, stdin reaches end-of-file.
+pub fn replay_keys(path: &wstr) -> Result<(), WString> {
+    let contents = std::fs::read(wcs2osstring(path)).map_err(|err| {
+        wgettext_fmt!(
+            "Could not read key recording '%ls': %s",
+            path,
+            err.to_string()
+        )
+    })?;
+    let keys = parse_key_recording(&str2wcstring(&contents))
+        .map_err(|line| wgettext_fmt!("%ls:%lu: Invalid key recording", path, line))?;
+    let pipes = make_autoclose_pipes()
+        .map_err(|err| wgettext_fmt!("Could not create pipe: %s", err.to_string()))?;
+    if unsafe { libc::dup2(pipes.read.as_raw_fd(), STDIN_FILENO) } < 0 {
+        return Err(wgettext_fmt!(
+            "Could not redirect input: %s",
+            std::io::Error::last_os_error().to_string()
+        ));
+    }
+    let mut input = File::from(pipes.write);
+    std::thread::spawn(move || {
+        for key in keys {
+            std::thread::sleep(key.delay);
+            if input.write_all(&wcs2string(&key.seq)).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
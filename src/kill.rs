@@ -2,85 +2,284 @@
 //!
 //! Works like the killring in emacs and readline. The killring is cut and paste with a memory of
 //! previous cuts.
+//!
+//! The killring may optionally be backed by a file, in which case it is shared between all
+//! sessions which use the same file. Changes made by other sessions are picked up through the
+//! universal notifier, and lazily before every yank. Rotating the killring only affects the
+//! current session.
 
+use nix::{fcntl::OFlag, sys::stat::Mode};
 use once_cell::sync::Lazy;
 use std::collections::VecDeque;
+use std::io::Write;
 use std::sync::Mutex;
 
+use crate::common::{
+    escape_string, str2wcstring, unescape_string, wcs2osstring, wcs2string, EscapeFlags,
+    EscapeStringStyle, UnescapeStringStyle,
+};
+use crate::fds::wopen_cloexec;
+use crate::flog::FLOG;
+use crate::path::path_get_config;
+use crate::universal_notifier::default_notifier;
 use crate::wchar::prelude::*;
+use crate::wutil::{file_id_for_path, wrename, wunlink, FileId, INVALID_FILE_ID};
+
+/// The name of the file backing a shared killring, inside the config directory.
+pub const KILL_RING_FILE_NAME: &wstr = L!("fish_killring");
+
+/// The killring file may contain what was cut from a command line, like passwords, so only the
+/// user may read it.
+const KILL_RING_FILE_MODE: Mode = Mode::from_bits_truncate(0o600);
 
-struct KillRing(VecDeque<WString>);
+/// The maximum number of entries we keep.
+const KILL_RING_MAX_ENTRIES: usize = 100;
+
+/// The maximum number of characters in all entries together. The most recent entry is always kept.
+const KILL_RING_MAX_CHARS: usize = 1024 * 1024;
+
+struct KillRing {
+    /// The entries in the order this session yanks them.
+    entries: VecDeque<WString>,
+    /// The entries in the order of the backing file, which does not know about our rotations.
+    shared_entries: VecDeque<WString>,
+    /// The file backing this killring, if it is shared.
+    path: Option<WString>,
+    /// The identity of that file the last time we read or wrote it.
+    last_file_id: FileId,
+}
 
 static KILL_RING: Lazy<Mutex<KillRing>> = Lazy::new(|| Mutex::new(KillRing::new()));
 
 impl KillRing {
     /// Create a new killring.
     fn new() -> Self {
-        Self(VecDeque::new())
+        Self {
+            entries: VecDeque::new(),
+            shared_entries: VecDeque::new(),
+            path: None,
+            last_file_id: INVALID_FILE_ID,
+        }
     }
 
     /// Return whether this killring is empty.
     #[cfg(test)]
     fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.entries.is_empty()
     }
 
     /// Add a string to the top of the killring.
     fn add(&mut self, new_entry: WString) {
-        if !new_entry.is_empty() {
-            self.0.push_front(new_entry);
+        if new_entry.is_empty() {
+            return;
+        }
+        for entries in [&mut self.entries, &mut self.shared_entries] {
+            entries.push_front(new_entry.clone());
+            Self::truncate(entries);
         }
     }
 
     /// Replace the specified string in the killring.
     pub fn replace(&mut self, old_entry: &wstr, new_entry: WString) {
-        if let Some(old_entry_idx) = self.0.iter().position(|entry| entry == old_entry) {
-            self.0.remove(old_entry_idx);
+        for entries in [&mut self.entries, &mut self.shared_entries] {
+            if let Some(old_entry_idx) = entries.iter().position(|entry| entry == old_entry) {
+                entries.remove(old_entry_idx);
+            }
         }
         if !new_entry.is_empty() {
             self.add(new_entry);
         }
     }
 
+    /// Drop the oldest entries beyond our limits.
+    fn truncate(entries: &mut VecDeque<WString>) {
+        let mut total_chars = 0;
+        let keep = entries
+            .iter()
+            .take(KILL_RING_MAX_ENTRIES)
+            .take_while(|entry| {
+                let fits = total_chars == 0 || total_chars + entry.len() <= KILL_RING_MAX_CHARS;
+                total_chars += entry.len();
+                fits
+            })
+            .count();
+        entries.truncate(keep);
+    }
+
     /// Paste from the killring.
     pub fn yank(&mut self) -> WString {
-        self.0.front().cloned().unwrap_or_default()
+        self.entries.front().cloned().unwrap_or_default()
     }
 
     /// Rotate the killring.
     pub fn yank_rotate(&mut self) -> WString {
-        self.0.rotate_left(1);
+        self.entries.rotate_left(1);
         self.yank()
     }
 
     /// Return a copy of the list of entries.
     pub fn entries(&self) -> Vec<WString> {
-        self.0.iter().cloned().collect()
+        self.entries.iter().cloned().collect()
+    }
+
+    /// Back this killring by the file at `path`, or stop sharing it if `path` is None.
+    /// Entries from this session which are not yet in the file are kept on top.
+    fn set_path(&mut self, path: Option<WString>) {
+        if self.path == path {
+            return;
+        }
+        self.path = path;
+        self.last_file_id = INVALID_FILE_ID;
+        if self.path.is_none() {
+            return;
+        }
+        let ours = std::mem::take(&mut self.entries);
+        self.shared_entries.clear();
+        self.sync();
+        for entry in ours.into_iter().rev() {
+            if !self.shared_entries.contains(&entry) {
+                self.add(entry);
+            }
+        }
+        self.save();
+    }
+
+    /// Re-read the backing file if another session changed it.
+    /// Return true if our entries were replaced.
+    fn sync(&mut self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let file_id = file_id_for_path(path);
+        if file_id == INVALID_FILE_ID || file_id == self.last_file_id {
+            return false;
+        }
+        let Ok(contents) = std::fs::read(wcs2osstring(path)) else {
+            return false;
+        };
+        FLOG!(reader, "Reloading killring from", path);
+        self.shared_entries = Self::parse(&str2wcstring(&contents));
+        Self::truncate(&mut self.shared_entries);
+        self.entries = self.shared_entries.clone();
+        self.last_file_id = file_id;
+        true
+    }
+
+    /// Write our entries to the backing file, if any, and tell other sessions about it.
+    fn save(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let tmp_path = sprintf!("%ls.tmp.%d", path, std::process::id());
+        let contents = Self::serialize(self.shared_entries.iter());
+        // Remove any leftover, so the file is created with our mode.
+        wunlink(&tmp_path);
+        let written = wopen_cloexec(
+            &tmp_path,
+            OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_EXCL,
+            KILL_RING_FILE_MODE,
+        )
+        .map_err(std::io::Error::from)
+        .and_then(|mut file| file.write_all(&wcs2string(&contents)));
+        if let Err(err) = written {
+            FLOG!(
+                reader,
+                "Unable to write killring file",
+                tmp_path,
+                err.to_string()
+            );
+            wunlink(&tmp_path);
+            return;
+        }
+        if wrename(&tmp_path, path) != 0 {
+            FLOG!(
+                reader,
+                "Unable to rename killring file",
+                tmp_path,
+                "to",
+                path
+            );
+            wunlink(&tmp_path);
+            return;
+        }
+        self.last_file_id = file_id_for_path(path);
+        default_notifier().post_notification();
+    }
+
+    /// Return the file representation of the given entries: one escaped entry per line.
+    fn serialize<'a>(entries: impl Iterator<Item = &'a WString>) -> WString {
+        let mut result = WString::new();
+        for entry in entries {
+            result.push_utfstr(&escape_string(
+                entry,
+                EscapeStringStyle::Script(EscapeFlags::NO_QUOTED),
+            ));
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Parse the file representation produced by [`Self::serialize`].
+    fn parse(contents: &wstr) -> VecDeque<WString> {
+        contents
+            .split('\n')
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| unescape_string(line, UnescapeStringStyle::default()))
+            .filter(|entry| !entry.is_empty())
+            .collect()
     }
 }
 
 /// Add a string to the top of the killring.
 pub fn kill_add(new_entry: WString) {
-    KILL_RING.lock().unwrap().add(new_entry)
+    let mut kr = KILL_RING.lock().unwrap();
+    kr.sync();
+    kr.add(new_entry);
+    kr.save();
 }
 
 /// Replace the specified string in the killring.
 pub fn kill_replace(old_entry: &wstr, new_entry: WString) {
-    KILL_RING.lock().unwrap().replace(old_entry, new_entry)
+    let mut kr = KILL_RING.lock().unwrap();
+    kr.sync();
+    kr.replace(old_entry, new_entry);
+    kr.save();
 }
 
-/// Rotate the killring.
+/// Rotate the killring. Other sessions sharing it are not affected.
 pub fn kill_yank_rotate() -> WString {
-    KILL_RING.lock().unwrap().yank_rotate()
+    let mut kr = KILL_RING.lock().unwrap();
+    kr.sync();
+    kr.yank_rotate()
 }
 
 /// Paste from the killring.
 pub fn kill_yank() -> WString {
-    KILL_RING.lock().unwrap().yank()
+    let mut kr = KILL_RING.lock().unwrap();
+    kr.sync();
+    kr.yank()
 }
 
 pub fn kill_entries() -> Vec<WString> {
-    KILL_RING.lock().unwrap().entries()
+    let mut kr = KILL_RING.lock().unwrap();
+    kr.sync();
+    kr.entries()
+}
+
+/// Pick up changes another session made to a shared killring.
+pub fn kill_sync() {
+    KILL_RING.lock().unwrap().sync();
+}
+
+/// Share the killring through the file in the config directory if `shared` is set, or keep it
+/// private to this session otherwise.
+pub fn kill_set_shared(shared: bool) {
+    let path = if shared {
+        path_get_config().map(|dir| dir + L!("/") + KILL_RING_FILE_NAME)
+    } else {
+        None
+    };
+    KILL_RING.lock().unwrap().set_path(path);
 }
 
 #[test]
@@ -108,3 +307,61 @@ fn test_killring() {
     assert!(kr.yank_rotate() == "a");
     assert!((kr.entries() == [L!("a"), L!("c"), L!("b"), L!("d")]));
 }
+
+#[test]
+fn test_killring_shared() {
+    use crate::common::charptr2wcstring;
+
+    let mut template = "/tmp/fish_test_killring.XXXXXX\0".as_bytes().to_vec();
+    let dir = charptr2wcstring(unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) });
+    let path = dir.clone() + L!("/") + KILL_RING_FILE_NAME;
+
+    let mut kr1 = KillRing::new();
+    let mut kr2 = KillRing::new();
+    kr1.add(WString::from_str("mine"));
+    kr1.set_path(Some(path.clone()));
+    kr2.set_path(Some(path.clone()));
+    assert!(kr2.entries() == [L!("mine")]);
+
+    // Entries with newlines and quotes survive the round trip.
+    kr2.add(WString::from_str("echo 'a\nb'"));
+    kr2.save();
+    assert!(kr1.sync());
+    assert!(kr1.entries() == [L!("echo 'a\nb'"), L!("mine")]);
+
+    // Nothing changed, so nothing to reload.
+    assert!(!kr1.sync());
+
+    // Rotating is private to the session, even once the rotated session saves.
+    assert!(kr1.yank_rotate() == "mine");
+    kr1.add(WString::from_str("new"));
+    kr1.save();
+    assert!(kr1.entries() == [L!("new"), L!("mine"), L!("echo 'a\nb'")]);
+    assert!(kr2.sync());
+    assert!(kr2.entries() == [L!("new"), L!("echo 'a\nb'"), L!("mine")]);
+
+    // Only the user may read the file.
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = std::fs::metadata(wcs2osstring(&path)).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+    wunlink(&path);
+    let _ = std::fs::remove_dir(wcs2osstring(&dir));
+}
+
+#[test]
+fn test_killring_limits() {
+    let mut kr = KillRing::new();
+    for i in 0..KILL_RING_MAX_ENTRIES + 10 {
+        kr.add(sprintf!("%d", i));
+    }
+    assert_eq!(kr.entries().len(), KILL_RING_MAX_ENTRIES);
+    assert!(kr.yank() == sprintf!("%d", KILL_RING_MAX_ENTRIES + 9));
+
+    // A huge entry pushes out the older ones, but is kept itself.
+    let huge = WString::from_chars(vec!['x'; KILL_RING_MAX_CHARS + 1]);
+    kr.add(huge.clone());
+    assert!(kr.entries() == [huge]);
+    kr.add(WString::from_str("small"));
+    assert_eq!(kr.entries().len(), 1);
+}
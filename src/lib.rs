@@ -34,6 +34,7 @@ pub mod ast;
 pub mod autoload;
 pub mod builtins;
 pub mod color;
+pub mod command_correction;
 pub mod complete;
 pub mod curses;
 pub mod editable_line;
@@ -60,6 +61,7 @@ pub mod input_common;
 pub mod io;
 pub mod job_group;
 pub mod key;
+pub mod key_recording;
 pub mod kill;
 #[allow(non_snake_case)]
 pub mod libc;
@@ -86,7 +88,9 @@ pub mod reader_history_search;
 pub mod redirection;
 pub mod screen;
 pub mod signal;
+pub mod terminal;
 pub mod termsize;
+pub mod theme;
 pub mod threads;
 pub mod timer;
 pub mod tinyexpr;
@@ -1,8 +1,9 @@
 // Generic output functions.
-use crate::color::RgbColor;
+use crate::color::{RgbColor, UnderlineStyle};
 use crate::common::{self, wcs2string_appending};
 use crate::curses::{self, tparm1, Term};
 use crate::env::EnvVar;
+use crate::terminal::{terminal_supports, TerminalFeature};
 use crate::threads::MainThread;
 use crate::wchar::prelude::*;
 use bitflags::bitflags;
@@ -111,6 +112,9 @@ pub struct Outputter {
     /// fd to output to, or -1 for none.
     fd: RawFd,
 
+    /// If the buffered contents are wrapped in a synchronized update, where it begins.
+    synchronized_start: Option<usize>,
+
     /// Foreground.
     last_color: RgbColor,
 
@@ -118,10 +122,13 @@ pub struct Outputter {
     last_color2: RgbColor,
 
     was_bold: bool,
-    was_underline: bool,
+    was_underline: Option<UnderlineStyle>,
+    was_underline_color: Option<RgbColor>,
     was_italics: bool,
     was_dim: bool,
     was_reverse: bool,
+    was_strikethrough: bool,
+    was_overline: bool,
 }
 
 impl Outputter {
@@ -132,13 +139,17 @@ impl Outputter {
             contents: Vec::new(),
             buffer_count: 0,
             fd,
+            synchronized_start: None,
             last_color: RgbColor::NORMAL,
             last_color2: RgbColor::NORMAL,
             was_bold: false,
-            was_underline: false,
+            was_underline: None,
+            was_underline_color: None,
             was_italics: false,
             was_dim: false,
             was_reverse: false,
+            was_strikethrough: false,
+            was_overline: false,
         }
     }
 
@@ -149,10 +160,13 @@ impl Outputter {
 
     fn reset_modes(&mut self) {
         self.was_bold = false;
-        self.was_underline = false;
+        self.was_underline = None;
+        self.was_underline_color = None;
         self.was_italics = false;
         self.was_dim = false;
         self.was_reverse = false;
+        self.was_strikethrough = false;
+        self.was_overline = false;
     }
 
     fn maybe_flush(&mut self) {
@@ -195,6 +209,43 @@ impl Outputter {
         true
     }
 
+    /// Write the sequence that underlines text in the given style. There is no terminfo capability
+    /// for this, so this always uses the colon-separated SGR parameters understood by kitty and
+    /// others. Exported for builtin_set_color's usage only.
+    pub fn write_underline_style(&mut self, style: UnderlineStyle) {
+        write!(self, "\x1B[4:{}m", style.sgr_param()).expect("Outputter::write should never fail");
+    }
+
+    /// Write the sequence that sets the underline color, or makes it follow the text color again if
+    /// `color` is None.
+    pub fn write_underline_color(&mut self, color: Option<RgbColor>) {
+        let result = match color {
+            None => write!(self, "\x1B[59m"),
+            Some(color)
+                if color.is_rgb() && get_color_support().contains(ColorSupport::TERM_24BIT) =>
+            {
+                let rgb = color.to_color24();
+                write!(self, "\x1B[58:2::{}:{}:{}m", rgb.r, rgb.g, rgb.b)
+            }
+            Some(color) => write!(self, "\x1B[58:5:{}m", index_for_color(color)),
+        };
+        result.expect("Outputter::write should never fail");
+    }
+
+    /// Write the sequence that enters or exits strikethrough mode.
+    pub fn write_strikethrough(&mut self, strikethrough: bool) {
+        self.tputs_bytes(if strikethrough {
+            b"\x1B[9m"
+        } else {
+            b"\x1B[29m"
+        });
+    }
+
+    /// Write the sequence that enters or exits overline mode.
+    pub fn write_overline(&mut self, overline: bool) {
+        self.tputs_bytes(if overline { b"\x1B[53m" } else { b"\x1B[55m" });
+    }
+
     /// Sets the fg and bg color. May be called as often as you like, since if the new color is the same
     /// as the previous, nothing will be written. Negative values for set_color will also be ignored.
     /// Since the terminfo string this function emits can potentially cause the screen to flicker, the
@@ -239,10 +290,18 @@ impl Outputter {
         let mut bg_set = false;
         let mut last_bg_set = false;
         let is_bold = fg.is_bold() || bg.is_bold();
-        let is_underline = fg.is_underline() || bg.is_underline();
+        let mut underline = fg.underline_style().or(bg.underline_style());
+        let mut underline_color = fg.underline_color().or(bg.underline_color());
+        if !terminal_supports(TerminalFeature::StyledUnderlines) {
+            // Other terminals may misread the colon-separated parameters, so only underline plainly.
+            underline = underline.map(|_| UnderlineStyle::Single);
+            underline_color = None;
+        }
         let is_italics = fg.is_italics() || bg.is_italics();
         let is_dim = fg.is_dim() || bg.is_dim();
         let is_reverse = fg.is_reverse() || bg.is_reverse();
+        let is_strikethrough = fg.is_strikethrough() || bg.is_strikethrough();
+        let is_overline = fg.is_overline() || bg.is_overline();
 
         if fg.is_reset() || bg.is_reset() {
             #[allow(unused_assignments)]
@@ -331,15 +390,34 @@ impl Outputter {
             }
         }
 
-        // Lastly, we set bold, underline, italics, dim, and reverse modes correctly.
+        // Lastly, we set bold, underline, italics, dim, reverse, strikethrough and overline modes
+        // correctly.
         if is_bold && !self.was_bold && !bg_set && self.tputs_if_some(enter_bold_mode) {
             self.was_bold = is_bold;
         }
 
-        if !self.was_underline && is_underline && self.tputs_if_some(enter_underline_mode) {
-            self.was_underline = is_underline;
-        } else if self.was_underline && !is_underline && self.tputs_if_some(exit_underline_mode) {
-            self.was_underline = is_underline;
+        if self.was_underline != underline {
+            match underline {
+                None => {
+                    if self.tputs_if_some(exit_underline_mode) {
+                        self.was_underline = None;
+                    }
+                }
+                Some(UnderlineStyle::Single) if self.was_underline.is_none() => {
+                    if self.tputs_if_some(enter_underline_mode) {
+                        self.was_underline = underline;
+                    }
+                }
+                Some(style) => {
+                    self.write_underline_style(style);
+                    self.was_underline = underline;
+                }
+            }
+        }
+
+        if self.was_underline_color != underline_color {
+            self.write_underline_color(underline_color);
+            self.was_underline_color = underline_color;
         }
 
         if self.was_italics && !is_italics && self.tputs_if_some(exit_italics_mode) {
@@ -361,6 +439,16 @@ impl Outputter {
                 self.was_reverse = is_reverse;
             }
         }
+
+        if self.was_strikethrough != is_strikethrough {
+            self.write_strikethrough(is_strikethrough);
+            self.was_strikethrough = is_strikethrough;
+        }
+
+        if self.was_overline != is_overline {
+            self.write_overline(is_overline);
+            self.was_overline = is_overline;
+        }
     }
 
     /// Write a wide character to the receiver.
@@ -406,8 +494,44 @@ impl Outputter {
         self.buffer_count -= 1;
         self.maybe_flush();
     }
+
+    /// Like begin_buffering(), but if the terminal supports it, also ask it to not draw anything
+    /// until the corresponding end_synchronized_update() call. This avoids flicker when repainting.
+    pub fn begin_synchronized_update(&mut self) {
+        let synchronize = self.fd >= 0 && terminal_supports(TerminalFeature::SynchronizedOutput);
+        self.begin_update(synchronize);
+    }
+
+    /// Like begin_buffering(), but if `synchronize` is set and this is the outermost update, wrap
+    /// the output in a synchronized update.
+    pub(crate) fn begin_update(&mut self, synchronize: bool) {
+        if synchronize && self.buffer_count == 0 {
+            self.synchronized_start = Some(self.contents.len());
+            self.contents.extend_from_slice(BEGIN_SYNCHRONIZED_UPDATE);
+        }
+        self.begin_buffering();
+    }
+
+    /// Balance a begin_synchronized_update() or begin_update() call.
+    pub fn end_synchronized_update(&mut self) {
+        if self.buffer_count == 1 {
+            if let Some(start) = self.synchronized_start.take() {
+                if self.contents.len() == start + BEGIN_SYNCHRONIZED_UPDATE.len() {
+                    // Nothing changed, so don't bother the terminal.
+                    self.contents.truncate(start);
+                } else {
+                    self.contents.extend_from_slice(END_SYNCHRONIZED_UPDATE);
+                }
+            }
+        }
+        self.end_buffering();
+    }
 }
 
+/// Set and reset DEC private mode 2026, which makes the terminal hold off on drawing.
+const BEGIN_SYNCHRONIZED_UPDATE: &[u8] = b"\x1b[?2026h";
+const END_SYNCHRONIZED_UPDATE: &[u8] = b"\x1b[?2026l";
+
 /// Outputter implements Write, so it may be used as the receiver of the write! macro.
 /// Only ASCII data should be written this way: do NOT assume that the receiver is UTF-8.
 impl Write for Outputter {
@@ -494,16 +618,22 @@ pub fn best_color(candidates: &[RgbColor], support: ColorSupport) -> RgbColor {
 #[allow(clippy::collapsible_else_if)]
 pub fn parse_color(var: &EnvVar, is_background: bool) -> RgbColor {
     let mut is_bold = false;
-    let mut is_underline = false;
+    let mut underline = None;
+    let mut underline_color = RgbColor::NONE;
     let mut is_italics = false;
     let mut is_dim = false;
     let mut is_reverse = false;
+    let mut is_strikethrough = false;
+    let mut is_overline = false;
 
     let mut candidates: Vec<RgbColor> = Vec::new();
 
     let prefix = L!("--background=");
+    let underline_prefix = L!("--underline=");
+    let underline_color_prefix = L!("--underline-color=");
 
     let mut next_is_background = false;
+    let mut next_is_underline_color = false;
     let mut color_name = WString::new();
     for next in var.as_list() {
         color_name.clear();
@@ -527,10 +657,27 @@ pub fn parse_color(var: &EnvVar, is_background: bool) -> RgbColor {
                 color_name = next.slice_from(2).to_owned();
             }
         } else {
-            if next == "--bold" || next == "-o" {
+            if next_is_underline_color {
+                next_is_underline_color = false;
+                underline_color = RgbColor::from_wstr(next).unwrap_or(RgbColor::NONE);
+            } else if next == "--bold" || next == "-o" {
                 is_bold = true;
             } else if next == "--underline" || next == "-u" {
-                is_underline = true;
+                underline = Some(UnderlineStyle::Single);
+            } else if next.starts_with(underline_prefix) {
+                // Look for something like "--underline=curly".
+                underline =
+                    UnderlineStyle::from_name(next.slice_from(underline_prefix.char_count()));
+            } else if next == "--underline-color" {
+                next_is_underline_color = true;
+            } else if next.starts_with(underline_color_prefix) {
+                underline_color =
+                    RgbColor::from_wstr(next.slice_from(underline_color_prefix.char_count()))
+                        .unwrap_or(RgbColor::NONE);
+            } else if next == "--strikethrough" || next == "-s" {
+                is_strikethrough = true;
+            } else if next == "--overline" {
+                is_overline = true;
             } else if next == "--italics" || next == "-i" {
                 is_italics = true;
             } else if next == "--dim" || next == "-d" {
@@ -555,9 +702,12 @@ pub fn parse_color(var: &EnvVar, is_background: bool) -> RgbColor {
         result = RgbColor::NORMAL;
     }
     result.set_bold(is_bold);
-    result.set_underline(is_underline);
+    result.set_underline_style(underline);
+    result.set_underline_color(underline_color);
     result.set_italics(is_italics);
     result.set_dim(is_dim);
     result.set_reverse(is_reverse);
+    result.set_strikethrough(is_strikethrough);
+    result.set_overline(is_overline);
     result
 }
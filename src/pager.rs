@@ -28,6 +28,8 @@ pub struct PageRendering {
     pub cols: usize,
     pub row_start: usize,
     pub row_end: usize,
+    /// The width of each column, including the spacer after it.
+    pub column_widths: Vec<usize>,
     pub selected_completion_idx: Option<usize>,
     pub screen_data: ScreenData,
 
@@ -416,6 +418,10 @@ impl Pager {
         assert!(row_stop >= row_start);
         rendering.row_start = row_start;
         rendering.row_end = row_stop;
+        rendering.column_widths = width_by_column[..cols]
+            .iter()
+            .map(|width| width + PAGER_SPACER_STRING.len())
+            .collect();
 
         let rows = divide_round_up(lst.len(), cols);
 
@@ -521,6 +527,7 @@ impl Pager {
                 );
             }
 
+            let link_start = line_data.len();
             comp_remaining -= print_max(
                 prefix,
                 prefix_col,
@@ -544,6 +551,9 @@ impl Pager {
                 i + 1 < c.comp.len(),
                 &mut line_data,
             );
+            if let Some(url) = c.links.get(i).cloned().flatten() {
+                line_data.add_hyperlink(link_start..line_data.len(), url);
+            }
         }
 
         let mut desc_remaining = width - comp_width + comp_remaining;
@@ -587,9 +597,19 @@ impl Pager {
 
     // Sets the set of completions.
     pub fn set_completions(&mut self, raw_completions: &[Completion], enable_refilter: bool) {
+        self.set_completions_with_links(raw_completions, |_| None, enable_refilter);
+    }
+
+    // Sets the set of completions, showing those for which `link` returns a URL as hyperlinks.
+    pub fn set_completions_with_links(
+        &mut self,
+        raw_completions: &[Completion],
+        link: impl Fn(&Completion) -> Option<WString>,
+        enable_refilter: bool,
+    ) {
         self.selected_completion_idx = None;
         // Get completion infos out of it.
-        self.unfiltered_completion_infos = process_completions_into_infos(raw_completions);
+        self.unfiltered_completion_infos = process_completions_into_infos(raw_completions, link);
 
         // Maybe join them.
         if self.prefix == "-" {
@@ -837,6 +857,33 @@ impl Pager {
         self.selected_completion_idx = new_index;
     }
 
+    /// Return the index of the completion drawn at the given line and column of the rendering.
+    pub fn completion_at(
+        &self,
+        rendering: &PageRendering,
+        line: usize,
+        column: usize,
+    ) -> Option<usize> {
+        let line = if self.search_field_shown {
+            line.checked_sub(1)?
+        } else {
+            line
+        };
+        let row = rendering.row_start + line;
+        if row >= rendering.row_end {
+            return None;
+        }
+        let mut column_end = 0;
+        for (col, width) in rendering.column_widths.iter().enumerate() {
+            column_end += width;
+            if column < column_end {
+                let idx = col * rendering.rows + row;
+                return (idx < self.completion_infos.len()).then_some(idx);
+            }
+        }
+        None
+    }
+
     // Indicates the row and column for the given rendering. Returns -1 if no selection.
     pub fn get_selected_row(&self, rendering: &PageRendering) -> Option<usize> {
         if rendering.rows == 0 {
@@ -1006,6 +1053,8 @@ impl Pager {
 pub struct PagerComp {
     /// The list of all completion strings this entry applies to.
     pub comp: Vec<WString>,
+    /// The hyperlink of each completion string, if it names a file.
+    pub links: Vec<Option<WString>>,
     /// The description.
     pub desc: WString,
     /// The representative completion.
@@ -1170,13 +1219,17 @@ fn join_completions(comps: &mut Vec<PagerComp>) {
                 let new_comp = comps.remove(i);
                 let prior_comp = &mut comps[prev_idx_plus_one - 1];
                 prior_comp.comp.extend(new_comp.comp);
+                prior_comp.links.extend(new_comp.links);
             }
         }
     }
 }
 
 /// Generate a list of comp_t structures from a list of completions.
-fn process_completions_into_infos(lst: &[Completion]) -> Vec<PagerComp> {
+fn process_completions_into_infos(
+    lst: &[Completion],
+    link: impl Fn(&Completion) -> Option<WString>,
+) -> Vec<PagerComp> {
     // Make the list of the correct size up-front.
     let mut result = Vec::with_capacity(lst.len());
     for (i, comp) in lst.iter().enumerate() {
@@ -1190,6 +1243,7 @@ fn process_completions_into_infos(lst: &[Completion]) -> Vec<PagerComp> {
                 EscapeFlags::NO_PRINTABLES | EscapeFlags::NO_QUOTED | EscapeFlags::SYMBOLIC,
             ),
         ));
+        comp_info.links.push(link(comp));
         if comp.replaces_line()
             // HACK We want to render a full shell command, with syntax highlighting.  Above we
             // escape nonprintables, which might make the rendered command longer than the original
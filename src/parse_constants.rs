@@ -91,7 +91,7 @@ pub enum ParseKeyword {
 }
 
 // Statement decorations like 'command' or 'exec'.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum StatementDecoration {
     none,
     command,
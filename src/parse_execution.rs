@@ -9,6 +9,7 @@ use crate::builtins::shared::{
     STATUS_EXPAND_ERROR, STATUS_ILLEGAL_CMD, STATUS_INVALID_ARGS, STATUS_NOT_EXECUTABLE,
     STATUS_UNMATCHED_WILDCARD,
 };
+use crate::command_correction::command_correction;
 use crate::common::{
     escape, scoped_push_replacer, should_suppress_stderr_for_tests, truncate_at_nul,
     valid_var_name, ScopeGuard, ScopeGuarding,
@@ -38,8 +39,8 @@ use crate::parser_keywords::parser_keywords_is_subcommand;
 use crate::path::{path_as_implicit_cd, path_try_get_path};
 use crate::pointer::ConstPointer;
 use crate::proc::{
-    get_job_control_mode, job_reap, no_exec, ConcreteAssignment, Job, JobControl, JobProperties,
-    JobRef, Process, ProcessList, ProcessType,
+    get_job_control_mode, is_interactive_session, job_reap, no_exec, ConcreteAssignment, Job,
+    JobControl, JobProperties, JobRef, Process, ProcessList, ProcessType,
 };
 use crate::reader::fish_is_unwinding_for_exit;
 use crate::redirection::{RedirectionMode, RedirectionSpec, RedirectionSpecList};
@@ -51,7 +52,7 @@ use crate::wchar::{wstr, WString, L};
 use crate::wchar_ext::WExt;
 use crate::wcstringutil::count_newlines;
 use crate::wildcard::wildcard_match;
-use crate::wutil::{wgettext, wgettext_maybe_fmt};
+use crate::wutil::{wgettext, wgettext_fmt, wgettext_maybe_fmt};
 use libc::{c_int, ENOTDIR, EXIT_SUCCESS, STDERR_FILENO, STDOUT_FILENO};
 use std::cell::RefCell;
 use std::io::ErrorKind;
@@ -377,6 +378,14 @@ impl<'a> ParseExecutionContext {
 
         let mut error = WString::new();
 
+        // If this was likely a typo at the command line, tell the user what they meant.
+        // Only do this for commands typed at the prompt, as it lists $PATH.
+        let correction = if self.is_typed_at_prompt(ctx) && !should_suppress_stderr_for_tests() {
+            command_correction(cmd, ctx.parser().vars())
+        } else {
+            None
+        };
+
         // Redirect to stderr
         let mut io = IoChain::new();
         let mut list = RedirectionSpecList::new();
@@ -393,6 +402,12 @@ impl<'a> ParseExecutionContext {
                 buffer.push(' ');
                 buffer.push_utfstr(&escape(arg));
             }
+            // Print the hint after the handler's message, to the same place.
+            if let Some(correction) = &correction {
+                let hint = wgettext_fmt!("fish: Did you mean '%ls'?", escape(correction));
+                buffer.push_str("; builtin echo -- ");
+                buffer.push_utfstr(&escape(&hint));
+            }
             let parser = ctx.parser();
             let prev_statuses = parser.get_last_statuses();
 
@@ -408,6 +423,10 @@ impl<'a> ParseExecutionContext {
                 error.push(' ');
                 error.push_utfstr(&escape(&event_args[0]));
             }
+            if let Some(correction) = &correction {
+                error.push('\n');
+                error.push_utfstr(&wgettext_fmt!("Did you mean '%ls'?", escape(correction)));
+            }
         }
 
         if cmd.as_char_slice().first() == Some(&'{' /*}*/) {
@@ -425,6 +444,17 @@ impl<'a> ParseExecutionContext {
         )
     }
 
+    /// Return whether the command being executed was typed at the prompt, rather than coming from
+    /// a function, a sourced file or an event handler.
+    fn is_typed_at_prompt(&self, ctx: &OperationContext<'_>) -> bool {
+        let parser = ctx.parser();
+        is_interactive_session()
+            && parser.is_interactive()
+            && parser.blocks().iter().all(|b| {
+                !b.is_function_call() && !matches!(b.typ(), BlockType::source | BlockType::event)
+            })
+    }
+
     // Utilities
     fn node_source(&self, node: &dyn ast::Node) -> WString {
         // todo!("maybe don't copy")
@@ -16,6 +16,7 @@ use crate::fds::{open_dir, BEST_O_SEARCH};
 use crate::global_safety::RelaxedAtomicBool;
 use crate::io::IoChain;
 use crate::job_group::MaybeJobId;
+use crate::nix::isatty;
 use crate::operation_context::{OperationContext, EXPANSION_LIMIT_DEFAULT};
 use crate::parse_constants::{
     ParseError, ParseErrorList, ParseTreeFlags, FISH_MAX_EVAL_DEPTH, FISH_MAX_STACK_DEPTH,
@@ -25,6 +26,7 @@ use crate::parse_execution::{EndExecutionReason, ParseExecutionContext};
 use crate::parse_tree::{parse_source, ParsedSourceRef};
 use crate::proc::{job_reap, JobGroupRef, JobList, JobRef, ProcStatus};
 use crate::signal::{signal_check_cancel, signal_clear_cancel, Signal};
+use crate::terminal::{file_url, hyperlink, terminal_supports, TerminalFeature};
 use crate::threads::{assert_is_main_thread, MainThread};
 use crate::util::get_time;
 use crate::wait_handle::WaitHandleStore;
@@ -989,11 +991,11 @@ impl Parser {
             if which_line > 0 {
                 wgettext_fmt!(
                     "%ls (line %lu): ",
-                    user_presentable_path(&filename, self.vars()),
+                    user_presentable_file_link(&filename, self.vars()),
                     which_line
                 )
             } else {
-                sprintf!("%ls: ", user_presentable_path(&filename, self.vars()))
+                sprintf!("%ls: ", user_presentable_file_link(&filename, self.vars()))
             }
         } else {
             L!("fish: ").to_owned()
@@ -1097,6 +1099,21 @@ fn user_presentable_path(path: &wstr, vars: &dyn Environment) -> WString {
     replace_home_directory_with_tilde(path, vars)
 }
 
+// Like user_presentable_path, but for error messages that are written straight to stderr: if that
+// is a terminal that supports it, make the path a hyperlink to the file.
+// Text that goes through a builtin's streams may end up in a file or a variable, so it must not use
+// this.
+fn user_presentable_file_link(path: &wstr, vars: &dyn Environment) -> WString {
+    let presentable = user_presentable_path(path, vars);
+    if !path.starts_with('/')
+        || !terminal_supports(TerminalFeature::Hyperlinks)
+        || !isatty(libc::STDERR_FILENO)
+    {
+        return presentable;
+    }
+    hyperlink(&file_url(path, vars), &presentable)
+}
+
 /// Print profiling information to the specified stream.
 fn print_profile(items: &[ProfileItem], out: RawFd) {
     for (idx, item) in items.iter().enumerate() {
@@ -42,16 +42,17 @@ use crate::ast::{self, Ast, Category, Traversal};
 use crate::builtins::shared::STATUS_CMD_OK;
 use crate::color::RgbColor;
 use crate::common::{
-    escape, escape_string, exit_without_destructors, get_ellipsis_char, get_obfuscation_read_char,
-    redirect_tty_output, scoped_push_replacer, scoped_push_replacer_ctx, shell_modes, str2wcstring,
-    wcs2string, write_loop, EscapeFlags, EscapeStringStyle, ScopeGuard, ScopeGuarding,
-    PROGRAM_NAME, UTF8_BOM_WCHAR,
+    escape, escape_string, exit_without_destructors, fish_reserved_codepoint, get_ellipsis_char,
+    get_obfuscation_read_char, redirect_tty_output, scoped_push_replacer, scoped_push_replacer_ctx,
+    shell_modes, str2wcstring, unescape_string, wcs2string, write_loop, EscapeFlags,
+    EscapeStringStyle, ScopeGuard, ScopeGuarding, UnescapeFlags, UnescapeStringStyle, PROGRAM_NAME,
+    UTF8_BOM_WCHAR,
 };
 use crate::complete::{
     complete, complete_load, sort_and_prioritize, CompleteFlags, Completion, CompletionList,
     CompletionRequestOptions,
 };
-use crate::editable_line::{Edit, EditableLine};
+use crate::editable_line::{DraftRing, Edit, EditableLine};
 use crate::env::{EnvMode, Environment, Statuses};
 use crate::exec::exec_subshell;
 use crate::expand::{expand_string, expand_tilde, ExpandFlags, ExpandResultCode};
@@ -63,18 +64,20 @@ use crate::flog::{FLOG, FLOGF};
 use crate::future::IsSomeAnd;
 use crate::global_safety::RelaxedAtomicBool;
 use crate::highlight::{
-    autosuggest_validate_from_history, highlight_shell, HighlightRole, HighlightSpec,
+    autosuggest_validate_from_history, command_correction_with_cache, highlight_shell_with_cache,
+    HighlightCache, HighlightRole, HighlightSpec, MatchingPairs,
 };
 use crate::history::{
     history_session_id, in_private_mode, History, HistorySearch, PersistenceMode, SearchDirection,
     SearchType,
 };
 use crate::input::init_input;
-use crate::input::Inputter;
+use crate::input::{input_sequence_hints, Inputter};
 use crate::input_common::terminal_protocols_disable_ifn;
 use crate::input_common::IS_TMUX;
 use crate::input_common::{terminal_protocols_enable_ifn, CharEvent, CharInputStyle, ReadlineCmd};
 use crate::io::IoChain;
+use crate::key::KEY_SEPARATOR;
 use crate::kill::{kill_add, kill_replace, kill_yank, kill_yank_rotate};
 use crate::libc::MB_CUR_MAX;
 use crate::nix::isatty;
@@ -94,16 +97,21 @@ use crate::parse_util::{
     parse_util_token_extent,
 };
 use crate::parser::{BlockType, EvalRes, Parser, ParserRef};
+use crate::path::path_apply_working_directory;
 use crate::proc::{
     have_proc_stat, hup_jobs, is_interactive_session, job_reap, jobs_requiring_warning_on_exit,
     print_exit_warning_for_jobs, proc_update_jiffies,
 };
 use crate::reader_history_search::{smartcase_flags, ReaderHistorySearch, SearchMode};
-use crate::screen::{screen_clear, screen_force_clear_to_end, Screen};
+use crate::screen::{screen_clear, screen_force_clear_to_end, Screen, ScreenElement};
 use crate::signal::{
     signal_check_cancel, signal_clear_cancel, signal_reset_handlers, signal_set_handlers,
     signal_set_handlers_once,
 };
+use crate::terminal::{
+    file_url, terminal_notify, terminal_report_working_directory, terminal_supports,
+    TerminalFeature,
+};
 use crate::termsize::{termsize_invalidate_tty, termsize_last, termsize_update};
 use crate::threads::{
     assert_is_background_thread, assert_is_main_thread, iothread_service_main_with_timeout,
@@ -116,11 +124,11 @@ use crate::tokenizer::{
 };
 use crate::wchar::prelude::*;
 use crate::wcstringutil::{
-    count_preceding_backslashes, join_strings, string_prefixes_string,
+    bool_from_string, count_preceding_backslashes, join_strings, string_prefixes_string,
     string_prefixes_string_case_insensitive, StringFuzzyMatch,
 };
 use crate::wildcard::wildcard_has;
-use crate::wutil::{perror, write_to_fd};
+use crate::wutil::{fish_wcstoi, lwstat, normalize_path, perror, wbasename, write_to_fd};
 use crate::{abbrs, event, function, history};
 
 /// A description of where fish is in the process of exiting.
@@ -451,6 +459,27 @@ struct LayoutData {
     right_prompt_buff: WString,
 }
 
+/// A paste which must be confirmed before the command line is executed.
+struct UnconfirmedPaste {
+    /// Where the paste was inserted into the command line.
+    range: Range<usize>,
+    /// The pasted text, to detect if the command line changed around it.
+    text: WString,
+    /// Whether we have already shown the warning, so the next execute runs it.
+    warned: bool,
+}
+
+/// The likely correction for an unknown command in the command line.
+#[derive(Eq, PartialEq)]
+struct CommandCorrection {
+    /// The command line it was found for.
+    text: WString,
+    /// Where the unknown command is.
+    range: Range<usize>,
+    /// The command it should be.
+    correction: WString,
+}
+
 #[derive(Clone, Copy, Eq, PartialEq)]
 enum EditableLineTag {
     Commandline,
@@ -535,6 +564,19 @@ pub struct ReaderData {
     did_warn_for_bg_jobs: bool,
     /// The current contents of the top item in the kill ring.
     kill_item: WString,
+    /// Command lines that were discarded without being executed.
+    drafts: DraftRing,
+    /// While a bracketed paste is being inserted, the cursor position and command line length
+    /// before the insertion.
+    paste_start: Option<(usize, usize)>,
+    /// The last paste, if $fish_confirm_paste asks us to confirm it before executing.
+    unconfirmed_paste: Option<UnconfirmedPaste>,
+    /// Where the mouse was clicked, while we wait for the terminal to report the cursor position.
+    mouse_click: Option<(usize, usize)>,
+    /// When and which pager entry was last clicked, to detect double clicks.
+    last_pager_click: Option<(Instant, usize)>,
+    /// Whether the pager shows how a pending key sequence may continue.
+    sequence_hints_shown: bool,
 
     /// A flag which may be set to force re-execing all prompts and re-rendering.
     /// This may come about when a color like $fish_color... has changed.
@@ -550,6 +592,13 @@ pub struct ReaderData {
     in_flight_highlight_request: WString,
     in_flight_autosuggest_request: WString,
 
+    /// The results of filesystem checks from highlighting earlier versions of the command line.
+    highlight_cache: Arc<Mutex<HighlightCache>>,
+    /// The brackets, quotes and block keywords that belong together, from the last highlighting.
+    matching_pairs: MatchingPairs,
+    /// The correction for the unknown command at or before the cursor, from the last highlighting.
+    command_correction: Option<CommandCorrection>,
+
     rls: Option<ReadlineLoopState>,
 }
 
@@ -999,11 +1048,15 @@ pub fn reader_status_count() -> u64 {
 // Name of the variable that tells how long it took, in milliseconds, for the previous
 // interactive command to complete.
 const ENV_CMD_DURATION: &wstr = L!("CMD_DURATION");
+const ENV_NOTIFY_THRESHOLD: &wstr = L!("fish_notify_threshold_ms");
 
 /// Maximum length of prefix string when printing completion list. Longer prefixes will be
 /// ellipsized.
 const PREFIX_MAX_LEN: usize = 9;
 
+/// Two clicks on the same pager entry within this interval accept it.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
 /// A simple prompt for reading shell commands that does not rely on fish specific commands, meaning
 /// it will work even if fish is not installed. This is used by read_i.
 const DEFAULT_PROMPT: &wstr = L!("echo -n \"$USER@$hostname $PWD \"'> '");
@@ -1109,12 +1162,21 @@ impl ReaderData {
             exit_loop_requested: Default::default(),
             did_warn_for_bg_jobs: Default::default(),
             kill_item: Default::default(),
+            drafts: Default::default(),
+            paste_start: None,
+            unconfirmed_paste: None,
+            mouse_click: None,
+            last_pager_click: None,
+            sequence_hints_shown: false,
             force_exec_prompt_and_repaint: Default::default(),
             last_jump_target: Default::default(),
             last_jump_direction: JumpDirection::Forward,
             last_jump_precision: JumpPrecision::To,
             in_flight_highlight_request: Default::default(),
             in_flight_autosuggest_request: Default::default(),
+            highlight_cache: Default::default(),
+            matching_pairs: Default::default(),
+            command_correction: None,
             rls: None,
         }))
     }
@@ -1424,20 +1486,46 @@ impl ReaderData {
         self.paint_layout(reason);
     }
 
+    /// Paint the last rendered layout, sending it to the terminal in one go.
+    fn paint_layout(&mut self, reason: &wstr) {
+        Outputter::stdoutput()
+            .borrow_mut()
+            .begin_synchronized_update();
+        self.paint_layout_unsynchronized(reason);
+        Outputter::stdoutput()
+            .borrow_mut()
+            .end_synchronized_update();
+        // The screen recorded the tty timestamps before we flushed; record them again so our own
+        // output is not mistaken for something else writing to the terminal.
+        self.screen.save_status();
+    }
+
     /// Paint the last rendered layout.
     /// `reason` is used in FLOG to explain why.
-    fn paint_layout(&mut self, reason: &wstr) {
+    fn paint_layout_unsynchronized(&mut self, reason: &wstr) {
         FLOGF!(reader_render, "Repainting from %ls", reason);
         let data = &self.rendered_layout;
         let cmd_line = &self.command_line;
 
-        let full_line = if self.conf.in_silent_mode {
+        let mut full_line = if self.conf.in_silent_mode {
             wstr::from_char_slice(&[get_obfuscation_read_char()]).repeat(cmd_line.len())
         } else {
             // Combine the command and autosuggestion into one string.
             combine_command_and_autosuggestion(cmd_line.text(), &self.autosuggestion.text)
         };
 
+        // Offer the correction for an unknown command where an autosuggestion would go, if there
+        // is none. It is accepted with correct-command.
+        if let Some(correction) = self.command_correction.as_ref().filter(|c| {
+            !self.conf.in_silent_mode && c.text == cmd_line.text() && self.autosuggestion.is_empty()
+        }) {
+            full_line.push_str("  ");
+            full_line.push_utfstr(&wgettext_fmt!(
+                "Did you mean '%ls'?",
+                escape(&correction.correction)
+            ));
+        }
+
         // Copy the colors and extend them with autosuggestion color.
         let mut colors = data.colors.clone();
 
@@ -1454,6 +1542,29 @@ impl ReaderData {
             }
         }
 
+        // Highlight the partner of the bracket, quote or block keyword at the cursor.
+        // These are found while highlighting, so they may be for an older command line.
+        if !self.conf.in_silent_mode
+            && self.conf.highlight_ok
+            && self.matching_pairs.text == cmd_line.text()
+        {
+            for range in self.matching_pairs.at(data.position) {
+                let end = std::cmp::min(range.end(), colors.len());
+                for color in &mut colors[range.start().min(end)..end] {
+                    color.background = HighlightRole::matching_pair;
+                }
+            }
+        }
+
+        // Highlight a paste which still needs to be confirmed.
+        if let Some(range) = self.unconfirmed_paste_range() {
+            let paste_color = HighlightSpec::with_fg_bg(HighlightRole::paste, HighlightRole::paste);
+            let end = std::cmp::min(range.end, colors.len());
+            for color in &mut colors[range.start.min(end)..end] {
+                *color = paste_color;
+            }
+        }
+
         // Apply any selection.
         if let Some(selection) = data.selection {
             let selection_color =
@@ -1611,6 +1722,9 @@ impl ReaderData {
         .to_owned();
         if self.command_line_has_transient_edit {
             self.command_line.undo();
+        } else {
+            // We are about to replace what the user typed; remember it.
+            self.drafts.add(self.command_line.text());
         }
         if self.history_search.by_token() {
             self.replace_current_token(new_text);
@@ -1813,6 +1927,9 @@ impl ReaderData {
 
         zelf.history_search.reset();
 
+        // The commands we ran may have changed the files that highlighting saw.
+        zelf.highlight_cache.lock().unwrap().clear();
+
         // It may happen that a command we ran when job control was disabled nevertheless stole the tty
         // from us. In that case when we read from our fd, it will trigger SIGTTIN. So just
         // unconditionally reclaim the tty. See #9181.
@@ -2194,6 +2311,7 @@ impl ReaderData {
                 if self.command_line.is_empty() {
                     return;
                 }
+                self.drafts.add(self.command_line.text());
                 self.push_edit(
                     EditableLineTag::Commandline,
                     Edit::new(0..self.command_line.len(), L!("").to_owned()),
@@ -2202,6 +2320,110 @@ impl ReaderData {
                 // Post fish_cancel.
                 event::fire_generic(self.parser(), L!("fish_cancel").to_owned(), vec![]);
             }
+            rl::RestoreDraft => {
+                // Consecutive invocations cycle through older drafts, like yank-pop.
+                if self.rls().last_cmd != Some(rl::RestoreDraft) {
+                    self.drafts.reset_cycle();
+                }
+                if let Some(draft) = self.drafts.next(self.command_line.text()) {
+                    let draft = draft.to_owned();
+                    let pos = draft.len();
+                    self.clear_pager();
+                    self.set_command_line_and_position(EditableLineTag::Commandline, draft, pos);
+                    self.suppress_autosuggestion = true;
+                }
+            }
+            rl::BeginMacro => {
+                let register = self.inputter.function_pop_arg();
+                let success = self.inputter.macro_begin(register);
+                self.inputter.function_set_status(success);
+            }
+            rl::EndMacro => {
+                let success = self.inputter.macro_end();
+                self.inputter.function_set_status(success);
+            }
+            rl::PlayMacro => {
+                let count = self.inputter.function_pop_repeat_count();
+                let register = self.inputter.function_pop_arg();
+                let success = self.inputter.macro_play(register, count);
+                self.inputter.function_set_status(success);
+            }
+            rl::CorrectCommand => {
+                let success = self.correct_command();
+                self.inputter.function_set_status(success);
+            }
+            rl::MouseClick => {
+                // We can only tell what was clicked once we know where the terminal put us.
+                self.mouse_click = self.inputter.query_cursor_position();
+                self.inputter
+                    .function_set_status(self.mouse_click.is_some());
+            }
+            rl::CursorPositionReport => {
+                if let (Some(click), Some(cursor)) = (
+                    self.mouse_click.take(),
+                    self.inputter.take_cursor_position(),
+                ) {
+                    self.handle_mouse_click(click, cursor);
+                }
+            }
+            rl::ShowSequenceHints => {
+                let prefix = self.inputter.sequence_hint_prefix().to_owned();
+                if prefix.is_empty() {
+                    // The sequence is complete or abandoned.
+                    if self.sequence_hints_shown {
+                        self.sequence_hints_shown = false;
+                        self.clear_pager();
+                        self.layout_and_repaint(L!("sequence hints"));
+                    }
+                    return;
+                }
+                // Don't replace completions or the history pager.
+                if !self.pager.is_empty() {
+                    return;
+                }
+                let hints: Vec<Completion> = input_sequence_hints(self.vars(), &prefix)
+                    .into_iter()
+                    .map(|(keys, description)| {
+                        Completion::new(
+                            keys,
+                            description,
+                            StringFuzzyMatch::exact_match(),
+                            CompleteFlags::DONT_ESCAPE,
+                        )
+                    })
+                    .collect();
+                if hints.is_empty() {
+                    return;
+                }
+                let mut keys: Vec<WString> = prefix.into_iter().map(WString::from).collect();
+                keys.push(WString::new());
+                self.pager.set_prefix(
+                    &join_strings(&keys, KEY_SEPARATOR),
+                    /*highlight=*/ false,
+                );
+                self.pager.set_completions(&hints, false);
+                self.sequence_hints_shown = true;
+                self.layout_and_repaint(L!("sequence hints"));
+            }
+            rl::ScrollUp | rl::ScrollDown => {
+                let up = c == rl::ScrollUp;
+                if !self.pager.is_empty() {
+                    self.select_completion_in_direction(
+                        if up {
+                            SelectionMotion::PageNorth
+                        } else {
+                            SelectionMotion::PageSouth
+                        },
+                        false,
+                    );
+                } else {
+                    self.handle_readline_command(if up {
+                        rl::HistorySearchBackward
+                    } else {
+                        rl::HistorySearchForward
+                    });
+                }
+            }
             rl::Cancel => {
                 // If we last inserted a completion, undo it.
                 // This doesn't apply if the completion was selected via the pager
@@ -3151,6 +3373,25 @@ impl ReaderData {
             rl::FocusOut => {
                 event::fire_generic(self.parser(), L!("fish_focus_out").to_owned(), vec![]);
             }
+            rl::BeginPaste => {
+                self.paste_start = Some((self.command_line.position(), self.command_line.len()));
+            }
+            rl::EndPaste => {
+                let Some((start, old_len)) = self.paste_start.take() else {
+                    return;
+                };
+                let end = start + self.command_line.len().saturating_sub(old_len);
+                if end > self.command_line.len() {
+                    return;
+                }
+                let text = self.command_line.text()[start..end].to_owned();
+                self.unconfirmed_paste = paste_needs_confirmation(self.parser().vars(), &text)
+                    .then_some(UnconfirmedPaste {
+                        range: start..end,
+                        text,
+                        warned: false,
+                    });
+            }
             rl::ClearScreenAndRepaint => {
                 self.parser().libdata_mut().pods.is_repaint = true;
                 let clear = screen_clear();
@@ -3209,8 +3450,9 @@ impl ReaderData {
             return true;
         }
 
-        // Delete any autosuggestion.
+        // Delete any autosuggestion, and the correction shown in its place.
         self.autosuggestion.clear();
+        self.command_correction = None;
 
         // The user may have hit return with pager contents, but while not navigating them.
         // Clear the pager in that event.
@@ -3245,6 +3487,18 @@ impl ReaderData {
             return true;
         }
 
+        // Ask before running a paste, if requested. The next execute runs it.
+        if self.unconfirmed_paste_range().is_some() {
+            let paste = self.unconfirmed_paste.as_mut().unwrap();
+            if !paste.warned {
+                paste.warned = true;
+                let warning = describe_paste(&paste.text);
+                self.print_above_prompt(&warning);
+                return true;
+            }
+        }
+        self.unconfirmed_paste = None;
+
         // Expand the command line in preparation for execution.
         // to_exec is the command to execute; the command line itself has the command for history.
         let test_res = self.expand_for_execute();
@@ -3303,6 +3557,87 @@ impl ReaderData {
         self.command_line_has_transient_edit = false;
     }
 
+    /// Handle a mouse click at the given terminal position, given where the terminal reported the
+    /// cursor. Clicking the command line moves the cursor there. Clicking a pager entry selects it,
+    /// and clicking it again accepts it.
+    fn handle_mouse_click(&mut self, click: (usize, usize), cursor: (usize, usize)) {
+        let (x, y) = click;
+        match self.screen.element_at(cursor.1, y, x) {
+            Some(ScreenElement::Commandline(offset)) => {
+                self.clear_pager();
+                let offset = offset.min(self.command_line.len());
+                self.update_buff_pos(EditableLineTag::Commandline, Some(offset));
+            }
+            Some(ScreenElement::Pager { line, column }) => {
+                let Some(idx) =
+                    self.pager
+                        .completion_at(&self.current_page_rendering, line, column)
+                else {
+                    return;
+                };
+                let now = Instant::now();
+                let double_click = self.last_pager_click.is_some_and(|(time, last_idx)| {
+                    last_idx == idx && now.duration_since(time) < DOUBLE_CLICK_INTERVAL
+                });
+                if double_click {
+                    self.last_pager_click = None;
+                    self.clear_pager();
+                    return;
+                }
+                self.last_pager_click = Some((now, idx));
+                self.pager.set_selected_completion_index(Some(idx));
+                self.pager_selection_changed();
+            }
+            None => (),
+        }
+    }
+
+    /// Replace the unknown command at or before the cursor with its most likely correction.
+    /// Return false if there is nothing to correct.
+    fn correct_command(&mut self) -> bool {
+        // Corrections are found while highlighting, which lists $PATH in the background. Give it a
+        // moment if it is still busy with this command line.
+        if self.in_flight_highlight_request == self.command_line.text() {
+            self.wait_for_highlighting(HIGHLIGHT_TIMEOUT_FOR_EXECUTION);
+        }
+        if self
+            .command_correction
+            .as_ref()
+            .is_none_or(|c| c.text != self.command_line.text())
+        {
+            return false;
+        }
+        let CommandCorrection {
+            range, correction, ..
+        } = self.command_correction.take().unwrap();
+        self.push_edit(EditableLineTag::Commandline, Edit::new(range, correction));
+        true
+    }
+
+    /// Return the range of the paste awaiting confirmation, if it is still in the command line.
+    fn unconfirmed_paste_range(&self) -> Option<Range<usize>> {
+        let paste = self.unconfirmed_paste.as_ref()?;
+        let text = self.command_line.text();
+        (paste.range.end <= text.len() && text[paste.range.clone()] == paste.text)
+            .then(|| paste.range.clone())
+    }
+
+    /// Print a message below the command line, and draw a fresh prompt below it.
+    fn print_above_prompt(&mut self, msg: &wstr) {
+        self.update_buff_pos(EditableLineTag::Commandline, Some(self.command_line.len()));
+        self.layout_and_repaint(L!("message"));
+        let mut out = WString::new();
+        if !self.screen.cursor_is_wrapped_to_own_line() {
+            out.push('\n');
+        }
+        out.push_utfstr(msg);
+        out.push('\n');
+        let _ = write_to_fd(&wcs2string(&out), STDOUT_FILENO);
+        self.screen
+            .reset_abandoning_line(usize::try_from(termsize_last().width).unwrap());
+        self.layout_and_repaint(L!("message"));
+    }
+
     fn get_selection(&self) -> Option<Range<usize>> {
         let selection = self.selection?;
         let start = selection.start;
@@ -3691,6 +4026,9 @@ fn reader_interactive_init(parser: &Parser) {
         .set_one(L!("_"), EnvMode::GLOBAL, L!("fish").to_owned());
 
     IS_TMUX.store(parser.vars().get_unless_empty(L!("TMUX")).is_some());
+
+    // We might have inherited our working directory from another tab, so tell the terminal.
+    terminal_report_working_directory(parser.vars());
 }
 
 /// Destroy data for interactive use.
@@ -4181,6 +4519,8 @@ impl ReaderData {
 #[derive(Default)]
 struct HighlightResult {
     colors: Vec<HighlightSpec>,
+    matching_pairs: MatchingPairs,
+    command_correction: Option<(Range<usize>, WString)>,
     text: WString,
 }
 
@@ -4190,8 +4530,10 @@ fn get_highlight_performer(
     parser: &Parser,
     el: &EditableLine,
     io_ok: bool,
+    cache: &Arc<Mutex<HighlightCache>>,
 ) -> impl FnOnce() -> HighlightResult {
     let vars = parser.vars().snapshot();
+    let cache = Arc::clone(cache);
     let generation_count = read_generation_count();
     let position = el.position();
     let text = el.text().to_owned();
@@ -4201,8 +4543,30 @@ fn get_highlight_performer(
         }
         let ctx = get_bg_context(&vars, generation_count);
         let mut colors = vec![];
-        highlight_shell(&text, &mut colors, &ctx, io_ok, Some(position));
-        HighlightResult { colors, text }
+        let mut matching_pairs = MatchingPairs::default();
+        highlight_shell_with_cache(
+            &text,
+            &mut colors,
+            &mut matching_pairs,
+            &ctx,
+            io_ok,
+            Some(position),
+            &cache,
+        );
+        // This lists $PATH, so it is only done with I/O.
+        let command_correction = if io_ok && !ctx.check_cancel() {
+            find_command_correction(&text, position, &colors, |cmd| {
+                command_correction_with_cache(cmd, ctx.vars(), &cache)
+            })
+        } else {
+            None
+        };
+        HighlightResult {
+            colors,
+            matching_pairs,
+            command_correction,
+            text,
+        }
     }
 }
 
@@ -4212,7 +4576,24 @@ impl ReaderData {
         self.in_flight_highlight_request.clear();
         if result.text == self.command_line.text() {
             assert_eq!(result.colors.len(), self.command_line.len());
-            if self.is_repaint_needed(Some(&result.colors)) {
+            // Pairs for an older command line were not shown.
+            let new_pairs = self.matching_pairs.text != result.text
+                && !result
+                    .matching_pairs
+                    .at(self.command_line.position())
+                    .is_empty();
+            self.matching_pairs = result.matching_pairs;
+            let command_correction =
+                result
+                    .command_correction
+                    .map(|(range, correction)| CommandCorrection {
+                        text: result.text,
+                        range,
+                        correction,
+                    });
+            let new_correction = self.command_correction != command_correction;
+            self.command_correction = command_correction;
+            if self.is_repaint_needed(Some(&result.colors)) || new_pairs || new_correction {
                 self.command_line.set_colors(result.colors);
                 self.layout_and_repaint(L!("highlight"));
             }
@@ -4232,8 +4613,12 @@ impl ReaderData {
         self.in_flight_highlight_request = self.command_line.text().to_owned();
 
         FLOG!(reader_render, "Highlighting");
-        let highlight_performer =
-            get_highlight_performer(self.parser(), &self.command_line, /*io_ok=*/ true);
+        let highlight_performer = get_highlight_performer(
+            self.parser(),
+            &self.command_line,
+            /*io_ok=*/ true,
+            &self.highlight_cache,
+        );
         let canary = Rc::downgrade(&self.canary);
         let completion = move |zelf: &mut Self, result| {
             if canary.upgrade().is_none() {
@@ -4244,6 +4629,28 @@ impl ReaderData {
         debounce_highlighting().perform_with_completion(highlight_performer, completion);
     }
 
+    /// Wait for the in-flight highlight request to complete, but not longer than `timeout`.
+    /// Return whether it completed.
+    fn wait_for_highlighting(&mut self, timeout: Duration) -> bool {
+        let mut now = Instant::now();
+        let deadline = now + timeout;
+        while now < deadline {
+            let timeout = deadline - now;
+            iothread_service_main_with_timeout(self, timeout);
+
+            // Note iothread_service_main_with_timeout will reentrantly modify us,
+            // by invoking a completion.
+            if self.in_flight_highlight_request.is_empty() {
+                break;
+            }
+            now = Instant::now();
+        }
+
+        // If our in_flight_highlight_request is now empty, it means it completed and we highlighted
+        // successfully.
+        self.in_flight_highlight_request.is_empty()
+    }
+
     /// Finish up any outstanding syntax highlighting, before execution.
     /// This plays some tricks to not block on I/O for too long.
     fn finish_highlighting_before_exec(&mut self) {
@@ -4263,29 +4670,17 @@ impl ReaderData {
         } else if self.in_flight_highlight_request == self.command_line.text() {
             // The user hit return while our in-flight highlight request was still processing the text.
             // Wait for its completion to run, but not forever.
-            let mut now = Instant::now();
-            let deadline = now + HIGHLIGHT_TIMEOUT_FOR_EXECUTION;
-            while now < deadline {
-                let timeout = deadline - now;
-                iothread_service_main_with_timeout(self, timeout);
-
-                // Note iothread_service_main_with_timeout will reentrantly modify us,
-                // by invoking a completion.
-                if self.in_flight_highlight_request.is_empty() {
-                    break;
-                }
-                now = Instant::now();
-            }
-
-            // If our in_flight_highlight_request is now empty, it means it completed and we highlighted
-            // successfully.
-            current_highlight_ok = self.in_flight_highlight_request.is_empty();
+            current_highlight_ok = self.wait_for_highlighting(HIGHLIGHT_TIMEOUT_FOR_EXECUTION);
         }
 
         if !current_highlight_ok {
             // We need to do a quick highlight without I/O.
-            let highlight_no_io =
-                get_highlight_performer(self.parser(), &self.command_line, /*io_ok=*/ false);
+            let highlight_no_io = get_highlight_performer(
+                self.parser(),
+                &self.command_line,
+                /*io_ok=*/ false,
+                &self.highlight_cache,
+            );
             self.highlight_complete(highlight_no_io());
         }
     }
@@ -4742,6 +5137,14 @@ fn command_ends_history_search(c: ReadlineCmd) -> bool {
             | rl::ForceRepaint
             | rl::FocusIn
             | rl::FocusOut
+            | rl::BeginMacro
+            | rl::EndMacro
+            | rl::PlayMacro
+            | rl::ScrollUp
+            | rl::ScrollDown
+            | rl::ShowSequenceHints
+            | rl::BeginPaste
+            | rl::EndPaste
     )
 }
 
@@ -4832,6 +5235,7 @@ fn reader_run_command(parser: &Parser, cmd: &wstr) -> EvalRes {
             EnvMode::UNEXPORT,
             duration.as_millis().to_wstring(),
         );
+        notify_if_slow(parser, cmd, duration);
     }
 
     term_steal();
@@ -4854,6 +5258,44 @@ fn reader_run_command(parser: &Parser, cmd: &wstr) -> EvalRes {
     eval_res
 }
 
+/// Show a desktop notification for a command that took at least $fish_notify_threshold_ms, so the
+/// user notices it finished while doing something else.
+fn notify_if_slow(parser: &Parser, cmd: &wstr, duration: Duration) {
+    let Some(threshold) = parser
+        .vars()
+        .get_unless_empty(ENV_NOTIFY_THRESHOLD)
+        .and_then(|var| fish_wcstoi(&var.as_string()).ok())
+    else {
+        return;
+    };
+    if threshold <= 0 || duration < Duration::from_millis(threshold as u64) {
+        return;
+    }
+
+    let status = parser.get_last_status();
+    let title = if status == 0 {
+        wgettext!("Command finished").to_owned()
+    } else {
+        wgettext_fmt!("Command failed with status %d", status)
+    };
+    // Only the first line, and nothing the terminal could mistake for the end of the sequence.
+    let first_line: WString = cmd
+        .chars()
+        .take_while(|&c| c != '\n')
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let secs = duration.as_secs();
+    let took = if secs < 60 {
+        sprintf!("%lus", secs)
+    } else {
+        sprintf!("%lum %lus", secs / 60, secs % 60)
+    };
+    // The focus query toggles focus reporting, so make sure it is not ours to keep on. The next
+    // readline turns it back on.
+    terminal_protocols_disable_ifn();
+    terminal_notify(&title, &sprintf!("%ls (%ls)", first_line, took));
+}
+
 fn reader_shell_test(parser: &Parser, bstr: &wstr) -> Result<(), ParserTestErrorBits> {
     let mut errors = vec![];
     let res = parse_util_detect_errors(bstr, Some(&mut errors), /*accept_incomplete=*/ true);
@@ -4976,6 +5418,162 @@ impl ReaderData {
     }
 }
 
+/// Find the last command which starts at or before the cursor. If `colors` from highlighting mark it
+/// as unknown and `correct` has a likely correction, return the command's range and the correction.
+pub fn find_command_correction(
+    text: &wstr,
+    cursor: usize,
+    colors: &[HighlightSpec],
+    correct: impl FnOnce(&wstr) -> Option<WString>,
+) -> Option<(Range<usize>, WString)> {
+    use crate::ast::Node;
+    let ast_flags = ParseTreeFlags::CONTINUE_AFTER_ERROR
+        | ParseTreeFlags::ACCEPT_INCOMPLETE_TOKENS
+        | ParseTreeFlags::LEAVE_UNTERMINATED;
+    let ast = Ast::parse(text, ast_flags, None);
+    let mut found = None;
+    for node in Traversal::new(ast.top()) {
+        let Some(stmt) = node.as_decorated_statement() else {
+            continue;
+        };
+        let Some(range) = stmt.command.try_source_range() else {
+            continue;
+        };
+        if range.start() <= cursor {
+            found = Some(range.start()..range.end());
+        }
+    }
+    let range = found?;
+    if colors.get(range.start).map(|color| color.foreground) != Some(HighlightRole::error) {
+        return None;
+    }
+    correct(&text[range.clone()]).map(|correction| (range, correction))
+}
+
+/// If completion `c` of a token whose unescaped text is `token` names an existing file, return the
+/// URL of that file.
+fn completion_file_link(
+    token: &wstr,
+    c: &Completion,
+    working_directory: &wstr,
+    vars: &dyn Environment,
+) -> Option<WString> {
+    let mut path = if c.replaces_token() {
+        c.completion.clone()
+    } else {
+        token.to_owned() + &c.completion[..]
+    };
+    if path.is_empty() {
+        return None;
+    }
+    expand_tilde(&mut path, vars);
+    let path = normalize_path(
+        &path_apply_working_directory(&path, working_directory),
+        true,
+    );
+    lwstat(&path).is_ok().then(|| file_url(&path, vars))
+}
+
+/// Pastes at least this long must be confirmed, even if they are a single line.
+const LARGE_PASTE_CHARS: usize = 1000;
+
+/// Return whether a paste of `text` must be confirmed before it is executed.
+/// This is opt-in via $fish_confirm_paste, and applies to pastes that are large, span multiple
+/// lines, contain control characters or run sudo.
+pub fn paste_needs_confirmation(vars: &dyn Environment, text: &wstr) -> bool {
+    let enabled = vars
+        .get_unless_empty(L!("fish_confirm_paste"))
+        .is_some_and(|v| bool_from_string(&v.as_string()));
+    enabled
+        && (text.len() >= LARGE_PASTE_CHARS
+            || text.contains('\n')
+            || text.chars().any(is_suspicious_paste_char)
+            || paste_runs_sudo(text))
+}
+
+/// Control characters other than newline and tab have no business in a paste, but may hide text
+/// on the terminal or be interpreted by it.
+fn is_suspicious_paste_char(c: char) -> bool {
+    (c.is_control() && c != '\n' && c != '\t') || fish_reserved_codepoint(c)
+}
+
+/// Return whether any command in the pasted text is sudo, including behind "command" or "env", and
+/// in command substitutions.
+pub fn paste_runs_sudo(text: &wstr) -> bool {
+    use crate::ast::{List, Node};
+    let is_sudo = |token: &wstr| {
+        unescape_string(token, UnescapeStringStyle::default())
+            .is_some_and(|cmd| wbasename(&cmd) == "sudo")
+    };
+    let ast = Ast::parse(
+        text,
+        ParseTreeFlags::CONTINUE_AFTER_ERROR
+            | ParseTreeFlags::ACCEPT_INCOMPLETE_TOKENS
+            | ParseTreeFlags::LEAVE_UNTERMINATED,
+        None,
+    );
+    for node in Traversal::new(ast.top()) {
+        if let Some(stmt) = node.as_decorated_statement() {
+            let Some(cmd) = stmt.command.try_source(text) else {
+                continue;
+            };
+            // Like "env FOO=bar sudo cmd", where sudo comes after the options and assignments.
+            let runs_sudo = is_sudo(cmd)
+                || (cmd == "env"
+                    && stmt
+                        .args_or_redirs
+                        .iter()
+                        .filter(|arg| arg.is_argument())
+                        .filter_map(|arg| arg.argument().try_source(text))
+                        .find(|arg| !arg.starts_with('-') && !arg.contains('='))
+                        .is_some_and(is_sudo));
+            if runs_sudo {
+                return true;
+            }
+            continue;
+        }
+        if node.as_argument().is_none() && node.as_variable_assignment().is_none() {
+            continue;
+        }
+        let Some(token) = node.try_source(text) else {
+            continue;
+        };
+        let mut cursor = 0;
+        while let MaybeParentheses::CommandSubstitution(parens) =
+            parse_util_locate_cmdsubst_range(token, &mut cursor, true, None, None)
+        {
+            if paste_runs_sudo(&token[parens.command()]) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Return the message shown before executing a paste.
+pub fn describe_paste(text: &wstr) -> WString {
+    use crate::ast::{List, Node};
+
+    let ast = Ast::parse(text, ParseTreeFlags::empty(), None);
+    let jobs = ast.top().as_job_list().map_or(0, |jobs| jobs.count());
+    let mut msg = wgettext_fmt!("The pasted text contains %lu command(s).", jobs);
+    if text.len() >= LARGE_PASTE_CHARS {
+        msg.push(' ');
+        msg.push_utfstr(&wgettext_fmt!("It is %lu characters long.", text.len()));
+    }
+    if text.chars().any(is_suspicious_paste_char) {
+        msg.push(' ');
+        msg.push_utfstr(wgettext!("It contains control characters."));
+    }
+    if paste_runs_sudo(text) {
+        msg.push(' ');
+        msg.push_utfstr(wgettext!("It runs sudo."));
+    }
+    msg.push(' ');
+    msg.push_utfstr(wgettext!("Press Enter again to run it."));
+    msg
+}
+
 /// Check if we should exit the reader loop.
 /// Return true if we should exit.
 pub fn check_exit_loop_maybe_warning(data: Option<&mut ReaderData>) -> bool {
@@ -5580,6 +6178,17 @@ impl ReaderData {
             }
         }
 
+        // Completions which don't replace the token are appended to what it says now.
+        let mut link_base = if use_prefix && will_replace_token {
+            WString::new()
+        } else {
+            unescape_string(&tok, UnescapeStringStyle::Script(UnescapeFlags::INCOMPLETE))
+                .unwrap_or_default()
+        };
+        if use_prefix {
+            link_base.push_utfstr(&common_prefix);
+        }
+
         // Print the completion list.
         let mut prefix = WString::new();
         if will_replace_token || !all_matches_exact_or_prefix {
@@ -5596,9 +6205,19 @@ impl ReaderData {
             prefix.push_utfstr(&full[full.len() - PREFIX_MAX_LEN..]);
         }
 
-        // Update the pager data.
+        // Update the pager data. File names are shown as hyperlinks, if the terminal supports them.
         self.pager.set_prefix(&prefix, true);
-        self.pager.set_completions(&surviving_completions, true);
+        if terminal_supports(TerminalFeature::Hyperlinks) {
+            let vars = self.parser_ref.vars();
+            let working_directory = vars.get_pwd_slash();
+            self.pager.set_completions_with_links(
+                &surviving_completions,
+                |c| completion_file_link(&link_base, c, &working_directory, vars),
+                true,
+            );
+        } else {
+            self.pager.set_completions(&surviving_completions, true);
+        }
         // Modify the command line to reflect the new pager.
         self.pager_selection_changed();
         false
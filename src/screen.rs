@@ -12,6 +12,7 @@ use std::cell::RefCell;
 use std::collections::LinkedList;
 use std::ffi::{CStr, CString};
 use std::io::Write;
+use std::ops::Range;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Mutex;
 
@@ -32,6 +33,7 @@ use crate::global_safety::RelaxedAtomicBool;
 use crate::highlight::HighlightColorResolver;
 use crate::highlight::HighlightSpec;
 use crate::output::Outputter;
+use crate::terminal::hyperlink_start;
 use crate::termsize::{termsize_last, Termsize};
 use crate::wchar::prelude::*;
 use crate::wcstringutil::string_prefixes_string;
@@ -49,6 +51,8 @@ pub struct Line {
     pub text: Vec<HighlightedChar>,
     pub is_soft_wrapped: bool,
     pub indentation: usize,
+    /// The ranges of characters which are hyperlinks, with their URLs.
+    hyperlinks: Vec<(Range<usize>, WString)>,
 }
 
 impl Line {
@@ -59,6 +63,7 @@ impl Line {
     /// Clear the line's contents.
     fn clear(&mut self) {
         self.text.clear();
+        self.hyperlinks.clear();
     }
 
     /// Append a single character `txt` to the line with color `c`.
@@ -91,8 +96,29 @@ impl Line {
         self.text[idx].highlight
     }
 
+    /// Make the characters in `range` a hyperlink to `url`.
+    pub fn add_hyperlink(&mut self, range: Range<usize>, url: WString) {
+        if !range.is_empty() {
+            self.hyperlinks.push((range, url));
+        }
+    }
+
+    /// Return the URL of the hyperlink at a char index.
+    pub fn hyperlink_at(&self, idx: usize) -> Option<&wstr> {
+        self.hyperlinks
+            .iter()
+            .find(|(range, _)| range.contains(&idx))
+            .map(|(_, url)| url.as_utfstr())
+    }
+
     /// Append the contents of `line` to this line.
     pub fn append_line(&mut self, line: &Line) {
+        let offset = self.text.len();
+        self.hyperlinks.extend(
+            line.hyperlinks
+                .iter()
+                .map(|(range, url)| (range.start + offset..range.end + offset, url.clone())),
+        );
         self.text.extend_from_slice(&line.text);
     }
 
@@ -108,6 +134,15 @@ impl Line {
     }
 }
 
+/// Something drawn on the screen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScreenElement {
+    /// The given offset in the command line.
+    Commandline(usize),
+    /// The given line and column of the pager.
+    Pager { line: usize, column: usize },
+}
+
 /// Where the cursor is in (x, y) coordinates.
 #[derive(Clone, Copy, Default)]
 pub struct Cursor {
@@ -200,6 +235,10 @@ pub struct Screen {
     /// is used when resizing the window larger: if the cursor jumps to the line above, we need to
     /// remember to clear the subsequent lines.
     actual_lines_before_reset: usize,
+    /// Where each character of the command line and its end were drawn, for mouse clicks.
+    commandline_cursors: Vec<Cursor>,
+    /// The line at which the pager starts.
+    pager_line: usize,
     /// These status buffers are used to check if any output has occurred other than from fish's
     /// main loop, in which case we need to redraw.
     prev_buff_1: libc::stat,
@@ -219,6 +258,8 @@ impl Screen {
             need_clear_lines: Default::default(),
             need_clear_screen: Default::default(),
             actual_lines_before_reset: Default::default(),
+            commandline_cursors: Default::default(),
+            pager_line: Default::default(),
             prev_buff_1: unsafe { std::mem::zeroed() },
             prev_buff_2: unsafe { std::mem::zeroed() },
         }
@@ -315,12 +356,16 @@ impl Screen {
         let effective_commandline = explicit_command_line.to_owned() + &layout.autosuggestion[..];
 
         // Output the command line.
+        self.commandline_cursors.clear();
         let mut i = 0;
         while i < effective_commandline.len() {
             // Grab the current cursor's x,y position if this character matches the cursor's offset.
             if !cursor_is_within_pager && i == cursor_pos {
                 cursor_arr = self.desired.cursor;
             }
+            if i <= explicit_len {
+                self.commandline_cursors.push(self.desired.cursor);
+            }
             self.desired_append_char(
                 effective_commandline.as_char_slice()[i],
                 colors[i],
@@ -335,6 +380,9 @@ impl Screen {
         if !cursor_is_within_pager && i == cursor_pos {
             cursor_arr = self.desired.cursor;
         }
+        if i <= explicit_len {
+            self.commandline_cursors.push(self.desired.cursor);
+        }
 
         let full_line_count = self.desired.cursor.y + 1;
 
@@ -361,12 +409,40 @@ impl Screen {
 
         pager.update_rendering(page_rendering);
         // Append pager_data (none if empty).
+        self.pager_line = self.desired.line_count();
         self.desired.append_lines(&page_rendering.screen_data);
 
         self.update(&layout.left_prompt, &layout.right_prompt, vars);
         self.save_status();
     }
 
+    /// Return what is drawn at the given terminal row and column, given the row where the terminal
+    /// reported our cursor. All of these are zero-based.
+    pub fn element_at(
+        &self,
+        cursor_row: usize,
+        row: usize,
+        column: usize,
+    ) -> Option<ScreenElement> {
+        // Our lines start at the first line of the command line.
+        let line = (row + self.actual.cursor.y).checked_sub(cursor_row)?;
+        if line >= self.pager_line {
+            return (line < self.actual.line_count()).then_some(ScreenElement::Pager {
+                line: line - self.pager_line,
+                column,
+            });
+        }
+        // Take the last offset on this line that is not right of the click. If the click was in the
+        // prompt, take the first offset on the line.
+        let mut offset = None;
+        for (i, cursor) in self.commandline_cursors.iter().enumerate() {
+            if cursor.y == line && (cursor.x <= column || offset.is_none()) {
+                offset = Some(i);
+            }
+        }
+        offset.map(ScreenElement::Commandline)
+    }
+
     /// Resets the screen buffer's internal knowledge about the contents of the screen,
     /// optionally repainting the prompt as well.
     /// This function assumes that the current line is still valid.
@@ -952,6 +1028,7 @@ impl Screen {
             }
 
             // Now actually output stuff.
+            let mut hyperlink: Option<WString> = None;
             loop {
                 let done = j >= o_line(&zelf, i).len();
                 // Clear the screen if we have not done so yet.
@@ -974,6 +1051,12 @@ impl Screen {
 
                 zelf.handle_soft_wrap(current_width, i);
                 zelf.r#move(current_width, i);
+                let url = o_line(&zelf, i).hyperlink_at(j);
+                if url != hyperlink.as_deref() {
+                    let url = url.map(|url| url.to_owned());
+                    zelf.write_str(&hyperlink_start(url.as_deref().unwrap_or(L!(""))));
+                    hyperlink = url;
+                }
                 let color = o_line(&zelf, i).color_at(j);
                 set_color(&mut zelf, color);
                 let ch = o_line(&zelf, i).char_at(j);
@@ -982,6 +1065,9 @@ impl Screen {
                 current_width += width;
                 j += 1;
             }
+            if hyperlink.is_some() {
+                zelf.write_str(&hyperlink_start(L!("")));
+            }
 
             let mut clear_remainder = false;
             // Clear the remainder of the line if we need to clear and if we didn't write to the end of
@@ -1605,8 +1691,11 @@ fn line_shared_prefix(a: &Line, b: &Line) -> usize {
         let ac = a.char_at(idx);
         let bc = b.char_at(idx);
 
-        // We're done if the text or colors are different.
-        if ac != bc || a.color_at(idx) != b.color_at(idx) {
+        // We're done if the text, colors or hyperlinks are different.
+        if ac != bc
+            || a.color_at(idx) != b.color_at(idx)
+            || a.hyperlink_at(idx) != b.hyperlink_at(idx)
+        {
             if idx > 0 {
                 let mut c = None;
                 // Possible combining mark, go back until we hit _two_ printable characters or idx
@@ -0,0 +1,484 @@
+//! What the terminal supports.
+//!
+//! Rather than guessing from $TERM and similar variables, we ask the terminal when we first take
+//! control of it. The answers arrive asynchronously and are parsed along with keyboard input. The
+//! last query is for the primary device attributes, which every terminal answers, so once that
+//! answer arrives, any query that has not been answered is not supported. In case it never arrives,
+//! we stop waiting after a few seconds.
+
+use crate::color::{set_term16_palette_color, Color24};
+use crate::common::{escape_string, str2wcstring, wcs2string, EscapeStringStyle};
+use crate::env::Environment;
+use crate::env_dispatch::env_dispatch_terminal_probed;
+use crate::flog::{FLOG, FLOGF};
+use crate::nix::isatty;
+use crate::wchar::prelude::*;
+use crate::wutil::write_to_fd;
+use libc::{STDIN_FILENO, STDOUT_FILENO};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A feature the terminal may support.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TerminalFeature {
+    /// The kitty keyboard protocol.
+    KittyKeyboard,
+    /// Synchronized output, DEC private mode 2026.
+    SynchronizedOutput,
+    /// 24-bit colors.
+    Truecolor,
+    /// Hyperlinks via OSC 8.
+    Hyperlinks,
+    /// Setting the clipboard via OSC 52.
+    Clipboard,
+    /// Underline styles like curly, and underline colors.
+    StyledUnderlines,
+}
+
+impl TerminalFeature {
+    pub const ALL: [TerminalFeature; 6] = [
+        TerminalFeature::KittyKeyboard,
+        TerminalFeature::SynchronizedOutput,
+        TerminalFeature::Truecolor,
+        TerminalFeature::Hyperlinks,
+        TerminalFeature::Clipboard,
+        TerminalFeature::StyledUnderlines,
+    ];
+
+    pub fn name(self) -> &'static wstr {
+        match self {
+            TerminalFeature::KittyKeyboard => L!("kitty-keyboard"),
+            TerminalFeature::SynchronizedOutput => L!("synchronized-output"),
+            TerminalFeature::Truecolor => L!("truecolor"),
+            TerminalFeature::Hyperlinks => L!("hyperlinks"),
+            TerminalFeature::Clipboard => L!("clipboard"),
+            TerminalFeature::StyledUnderlines => L!("styled-underlines"),
+        }
+    }
+
+    pub fn from_name(name: &wstr) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| feature.name() == name)
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// How far we got with asking the terminal.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ProbeState {
+    /// We have not taken control of a terminal.
+    #[default]
+    NotSent,
+    /// We are waiting for answers.
+    Pending,
+    /// The terminal answered all it is going to.
+    Done,
+}
+
+/// What we know about the terminal.
+#[derive(Clone, Debug, Default)]
+pub struct TerminalInfo {
+    pub probe: ProbeState,
+    /// The name and version reported by XTVERSION, like "kitty(0.35.2)".
+    pub name: Option<WString>,
+    /// The background color reported via OSC 11.
+    pub background: Option<Color24>,
+    features: u8,
+    /// When we stop waiting for answers.
+    probe_deadline: Option<Instant>,
+}
+
+impl TerminalInfo {
+    pub fn supports(&self, feature: TerminalFeature) -> bool {
+        self.features & feature.bit() != 0
+    }
+
+    /// Remember what the terminal told us. Palette colors are kept by the color module instead.
+    pub fn record(&mut self, reply: ProbeReply) {
+        match reply {
+            ProbeReply::Feature(feature) => self.features |= feature.bit(),
+            ProbeReply::Name(name) => {
+                if HYPERLINK_TERMINALS
+                    .iter()
+                    .any(|terminal| name.starts_with(*terminal))
+                {
+                    self.features |= TerminalFeature::Hyperlinks.bit();
+                }
+                self.name = Some(name);
+            }
+            ProbeReply::Background(color) => self.background = Some(color),
+            ProbeReply::PaletteColor(..) => (),
+        }
+    }
+
+    /// Describe the terminal for `status terminal`: its name, and for each feature whether it is
+    /// supported. Return None if we never asked the terminal.
+    pub fn describe(&self) -> Option<WString> {
+        if self.probe == ProbeState::NotSent {
+            return None;
+        }
+        let max_len = TerminalFeature::ALL
+            .iter()
+            .map(|feature| feature.name().len())
+            .max()
+            .unwrap();
+        let mut result = WString::new();
+        if let Some(name) = &self.name {
+            result.push_utfstr(&sprintf!("%-*ls%ls\n", max_len + 1, L!("name"), name));
+        }
+        for feature in TerminalFeature::ALL {
+            let supported = if self.supports(feature) {
+                L!("yes")
+            } else if self.probe == ProbeState::Pending {
+                L!("unknown")
+            } else {
+                L!("no")
+            };
+            result.push_utfstr(&sprintf!(
+                "%-*ls%ls\n",
+                max_len + 1,
+                feature.name(),
+                supported
+            ));
+        }
+        Some(result)
+    }
+}
+
+/// Something the terminal told us in answer to one of our queries.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProbeReply {
+    /// The terminal supports a feature.
+    Feature(TerminalFeature),
+    /// The name and version reported by XTVERSION.
+    Name(WString),
+    /// The background color reported via OSC 11.
+    Background(Color24),
+    /// The actual color of one of the 16 named colors, reported via OSC 4.
+    PaletteColor(u8, Color24),
+}
+
+/// Parse a DECRPM reply to our DECRQM query, given the mode and its value.
+/// Values 1 through 3 mean that the terminal knows the mode.
+pub fn parse_decrpm_reply(mode: u32, value: u32) -> Option<ProbeReply> {
+    (mode == 2026 && matches!(value, 1..=3))
+        .then_some(ProbeReply::Feature(TerminalFeature::SynchronizedOutput))
+}
+
+/// Parse the payload of a device control string, without the introducer and the terminator.
+pub fn parse_dcs_reply(payload: &[u8]) -> Option<ProbeReply> {
+    if let Some(name) = payload.strip_prefix(b">|") {
+        // XTVERSION
+        return Some(ProbeReply::Name(str2wcstring(name)));
+    }
+    // XTGETTCAP, with the hex-encoded capability name before the '='.
+    let capability = payload.strip_prefix(b"1+r")?;
+    let name = capability.split(|&b| b == b'=').next().unwrap();
+    let feature = if name.eq_ignore_ascii_case(b"524742") {
+        TerminalFeature::Truecolor
+    } else if name.eq_ignore_ascii_case(b"4d73") {
+        TerminalFeature::Clipboard
+    } else if name.eq_ignore_ascii_case(b"536d756c78") {
+        TerminalFeature::StyledUnderlines
+    } else {
+        return None;
+    };
+    Some(ProbeReply::Feature(feature))
+}
+
+/// Parse the payload of an operating system command, without the introducer and the terminator.
+pub fn parse_osc_reply(payload: &[u8]) -> Vec<ProbeReply> {
+    let mut replies = vec![];
+    if let Some(color) = payload.strip_prefix(b"11;").and_then(parse_x11_color) {
+        replies.push(ProbeReply::Background(color));
+    } else if let Some(pairs) = payload.strip_prefix(b"4;") {
+        // Terminals answer with one "4;index;color" per color, but allow several pairs anyway.
+        let mut fields = pairs.split(|&b| b == b';');
+        while let (Some(idx), Some(spec)) = (fields.next(), fields.next()) {
+            let idx = std::str::from_utf8(idx)
+                .ok()
+                .and_then(|s| s.parse::<u8>().ok());
+            if let (Some(idx @ 0..=15), Some(color)) = (idx, parse_x11_color(spec)) {
+                replies.push(ProbeReply::PaletteColor(idx, color));
+            }
+        }
+    }
+    replies
+}
+
+static TERMINAL_INFO: Mutex<TerminalInfo> = Mutex::new(TerminalInfo {
+    probe: ProbeState::NotSent,
+    name: None,
+    background: None,
+    features: 0,
+    probe_deadline: None,
+});
+
+/// Return what we know about the terminal.
+pub fn terminal_info() -> TerminalInfo {
+    TERMINAL_INFO.lock().unwrap().clone()
+}
+
+/// Return whether the terminal is known to support the given feature.
+pub fn terminal_supports(feature: TerminalFeature) -> bool {
+    TERMINAL_INFO.lock().unwrap().supports(feature)
+}
+
+/// The queries we send, each of which is answered by supporting terminals only, except for the last.
+const PROBE_QUERIES: &str = concat!(
+    "\x1b[>0q",                // XTVERSION
+    "\x1b[?u",                 // kitty keyboard protocol flags
+    "\x1b[?2026$p",            // DECRQM for synchronized output
+    "\x1bP+q524742\x1b\\",     // XTGETTCAP for RGB, meaning truecolor
+    "\x1bP+q4d73\x1b\\",       // XTGETTCAP for Ms, meaning OSC 52
+    "\x1bP+q536d756c78\x1b\\", // XTGETTCAP for Smulx, meaning styled underlines
+    "\x1b]11;?\x1b\\",         // OSC 11, the background color
+    // OSC 4, the actual colors of the 16 named colors
+    "\x1b]4;0;?;1;?;2;?;3;?;4;?;5;?;6;?;7;?;8;?;9;?;10;?;11;?;12;?;13;?;14;?;15;?\x1b\\",
+    "\x1b[c", // primary device attributes
+);
+
+/// Terminals that support OSC 8 hyperlinks, which cannot be queried, by their XTVERSION name.
+const HYPERLINK_TERMINALS: &[&str] = &["WezTerm", "contour", "foot", "ghostty", "iTerm2", "kitty"];
+
+/// How long we wait for the terminal to answer before assuming it never will.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Ask the terminal what it supports, unless we already did.
+/// If our input is not a terminal, no answer can arrive, so we only pretend to ask.
+pub(crate) fn terminal_probe_send_ifn() {
+    let mut info = TERMINAL_INFO.lock().unwrap();
+    if info.probe != ProbeState::NotSent {
+        return;
+    }
+    info.probe = ProbeState::Pending;
+    if !isatty(STDIN_FILENO) {
+        FLOG!(
+            term_support,
+            "Not probing terminal features, stdin is not a tty"
+        );
+        info.probe_deadline = Some(Instant::now());
+        return;
+    }
+    FLOG!(term_support, "Probing terminal features");
+    info.probe_deadline = Some(Instant::now() + PROBE_TIMEOUT);
+    let _ = write_to_fd(PROBE_QUERIES.as_bytes(), STDOUT_FILENO);
+}
+
+/// Return whether we are waiting for the terminal to answer.
+pub(crate) fn terminal_probe_is_pending() -> bool {
+    let info = TERMINAL_INFO.lock().unwrap();
+    info.probe == ProbeState::Pending
+        && info
+            .probe_deadline
+            .is_some_and(|deadline| Instant::now() < deadline)
+}
+
+/// Return how long we are going to wait for the terminal to answer, or None if we are not waiting.
+pub(crate) fn terminal_probe_time_left() -> Option<Duration> {
+    let info = TERMINAL_INFO.lock().unwrap();
+    if info.probe != ProbeState::Pending {
+        return None;
+    }
+    let deadline = info.probe_deadline?;
+    Some(deadline.saturating_duration_since(Instant::now()))
+}
+
+/// Stop waiting for the terminal if it took too long to answer. Whatever it did not answer so far
+/// is considered unsupported.
+pub(crate) fn terminal_probe_check_timeout() {
+    if terminal_probe_time_left() == Some(Duration::ZERO) {
+        FLOG!(term_support, "Terminal did not answer in time");
+        terminal_probe_finish();
+    }
+}
+
+/// Record what the terminal told us.
+pub(crate) fn terminal_probe_record(reply: ProbeReply) {
+    match &reply {
+        ProbeReply::Feature(feature) => FLOG!(term_support, "Terminal supports", feature.name()),
+        ProbeReply::Name(name) => FLOG!(term_support, "Terminal identifies as", name),
+        ProbeReply::Background(color) => FLOGF!(
+            term_support,
+            "Terminal background is #%02x%02x%02x",
+            color.r,
+            color.g,
+            color.b
+        ),
+        &ProbeReply::PaletteColor(idx, color) => {
+            FLOGF!(
+                term_support,
+                "Terminal color %u is #%02x%02x%02x",
+                idx,
+                color.r,
+                color.g,
+                color.b
+            );
+            set_term16_palette_color(idx, color);
+        }
+    }
+    TERMINAL_INFO.lock().unwrap().record(reply);
+}
+
+/// Parse a color in the X11 format terminals use to report colors, like "rgb:ffff/8080/0000".
+/// Each component has between one and four hex digits.
+pub fn parse_x11_color(spec: &[u8]) -> Option<Color24> {
+    let spec = spec.strip_prefix(b"rgb:")?;
+    let mut components = spec.split(|&b| b == b'/').map(|component| {
+        if component.is_empty() || component.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(std::str::from_utf8(component).ok()?, 16).ok()?;
+        let max = (1 << (4 * component.len())) - 1;
+        // Scale to 8 bits, rounding to nearest.
+        u8::try_from((value * 255 + max / 2) / max).ok()
+    });
+    let color = Color24 {
+        r: components.next()??,
+        g: components.next()??,
+        b: components.next()??,
+    };
+    components.next().is_none().then_some(color)
+}
+
+/// Record that the terminal answered all queries, and act on what we learned.
+fn terminal_probe_finish() {
+    {
+        let mut info = TERMINAL_INFO.lock().unwrap();
+        if info.probe != ProbeState::Pending {
+            return;
+        }
+        info.probe = ProbeState::Done;
+    }
+    FLOG!(term_support, "Terminal probe done");
+    env_dispatch_terminal_probed();
+}
+
+/// Return a file URL for the given absolute path on this host, as used by OSC 7 and OSC 8.
+pub fn file_url(path: &wstr, vars: &dyn Environment) -> WString {
+    let hostname = vars
+        .get(L!("hostname"))
+        .map(|var| var.as_string())
+        .unwrap_or_default();
+    sprintf!(
+        "file://%ls%ls",
+        hostname,
+        escape_string(path, EscapeStringStyle::Url)
+    )
+}
+
+/// Return the sequence that starts a hyperlink to `url` via OSC 8, or ends the current hyperlink if
+/// `url` is empty.
+pub fn hyperlink_start(url: &wstr) -> WString {
+    sprintf!("\x1b]8;;%ls\x1b\\", url)
+}
+
+/// Return `text` marked up as a hyperlink to `url`.
+pub fn hyperlink(url: &wstr, text: &wstr) -> WString {
+    let mut result = hyperlink_start(url);
+    result.push_utfstr(text);
+    result.push_utfstr(&hyperlink_start(L!("")));
+    result
+}
+
+/// Return the sequence that makes a terminal with the given XTVERSION name show a desktop
+/// notification. Where the protocol allows, the terminal only shows it while not focused.
+pub fn notification_sequence(terminal_name: Option<&wstr>, title: &wstr, body: &wstr) -> WString {
+    let is = |prefix: &str| terminal_name.is_some_and(|name| name.starts_with(prefix));
+    if is("kitty") {
+        // OSC 99, with the title and the body as separate chunks of the same notification.
+        sprintf!(
+            "\x1b]99;i=fish:d=0:o=unfocused;%ls\x1b\\\x1b]99;i=fish:p=body;%ls\x1b\\",
+            title,
+            body
+        )
+    } else if is("foot") || is("ghostty") {
+        // OSC 777, originally from urxvt.
+        sprintf!("\x1b]777;notify;%ls;%ls\x1b\\", title, body)
+    } else {
+        // OSC 9, originally from iTerm2 and understood by most others.
+        sprintf!("\x1b]9;%ls: %ls\x1b\\", title, body)
+    }
+}
+
+/// Turns on focus reporting, which makes terminals report the current focus right away, turns it
+/// off again and asks for the primary device attributes, whose answer marks the end of the reply.
+const FOCUS_QUERY: &[u8] = b"\x1b[?1004h\x1b[?1004l\x1b[c";
+
+/// What the terminal told us about its focus, and the notification that waits for it.
+#[derive(Debug, Default)]
+pub(crate) struct FocusState {
+    /// Whether the terminal has focus, if it reported it since the last query.
+    pub focused: Option<bool>,
+    /// The notification to show unless the terminal turns out to have focus.
+    pub pending_notification: Option<WString>,
+}
+
+impl FocusState {
+    /// Forget the focus and hold back `notification` until the terminal answered the focus query.
+    pub fn query(&mut self, notification: WString) {
+        self.focused = None;
+        self.pending_notification = Some(notification);
+    }
+
+    /// Remember a focus report. Return whether it is news, rather than the answer to our query.
+    pub fn record(&mut self, focused: bool) -> bool {
+        self.focused = Some(focused);
+        self.pending_notification.is_none()
+    }
+
+    /// The terminal answered the focus query. Return the pending notification unless the terminal
+    /// reported having focus.
+    pub fn finish_query(&mut self) -> Option<WString> {
+        let notification = self.pending_notification.take()?;
+        (self.focused != Some(true)).then_some(notification)
+    }
+}
+
+static FOCUS_STATE: Mutex<FocusState> = Mutex::new(FocusState {
+    focused: None,
+    pending_notification: None,
+});
+
+/// Record a focus report (CSI I or CSI O). Return whether it should be handled as a focus change,
+/// which it is not if it only answers our focus query.
+pub(crate) fn terminal_record_focus(focused: bool) -> bool {
+    FOCUS_STATE.lock().unwrap().record(focused)
+}
+
+/// Record the answer to the primary device attributes query, which ends both the probe and the
+/// focus query.
+pub(crate) fn terminal_primary_device_attributes() {
+    terminal_probe_finish();
+    let notification = FOCUS_STATE.lock().unwrap().finish_query();
+    if let Some(sequence) = notification {
+        let _ = write_to_fd(&wcs2string(&sequence), STDOUT_FILENO);
+    }
+}
+
+/// Show a desktop notification via the terminal, unless the terminal has focus. The notification
+/// is sent once the terminal answered whether it has focus; terminals that do not tell always get it.
+pub(crate) fn terminal_notify(title: &wstr, body: &wstr) {
+    let name = TERMINAL_INFO.lock().unwrap().name.clone();
+    let sequence = notification_sequence(name.as_deref(), title, body);
+    FOCUS_STATE.lock().unwrap().query(sequence);
+    let _ = write_to_fd(FOCUS_QUERY, STDOUT_FILENO);
+}
+
+/// Tell the terminal our working directory via OSC 7, so it can open new tabs and windows there.
+/// This can be turned off by setting $fish_report_working_directory to 0.
+pub(crate) fn terminal_report_working_directory(vars: &dyn Environment) {
+    if vars
+        .get(L!("fish_report_working_directory"))
+        .is_some_and(|var| var.as_string() == L!("0"))
+    {
+        return;
+    }
+    let Some(pwd) = vars.get_unless_empty(L!("PWD")) else {
+        return;
+    };
+    let sequence = sprintf!("\x1b]7;%ls\x07", file_url(&pwd.as_string(), vars));
+    let _ = write_to_fd(&wcs2string(&sequence), STDOUT_FILENO);
+}
@@ -1,6 +1,7 @@
 // Support for exposing the terminal size.
 use crate::common::assert_sync;
 use crate::env::{EnvMode, EnvVar, Environment};
+use crate::event;
 use crate::flog::FLOG;
 use crate::parser::Parser;
 use crate::wchar::prelude::*;
@@ -165,7 +166,7 @@ impl TermsizeContainer {
     }
 
     /// If our termsize is stale, update it, using `parser` to fire any events that may be
-    /// registered for COLUMNS and LINES.
+    /// registered for COLUMNS and LINES, and the fish_resize event.
     /// This requires a shared reference so it can work from a static.
     /// Return the updated termsize.
     pub fn updating(&self, parser: &Parser) -> Termsize {
@@ -193,6 +194,11 @@ impl TermsizeContainer {
         // Announce any updates.
         if new_size != prev_size {
             self.set_columns_lines_vars(new_size, parser);
+            event::fire_generic(
+                parser,
+                L!("fish_resize").to_owned(),
+                vec![new_size.width.to_wstring(), new_size.height.to_wstring()],
+            );
         }
         new_size
     }
@@ -0,0 +1,68 @@
+use crate::abbrs::{self, with_abbrs_mut, Abbreviation};
+use crate::command_correction::command_correction;
+use crate::common::ScopeGuard;
+use crate::function;
+use crate::io::IoChain;
+use crate::parser::Parser;
+use crate::tests::prelude::*;
+use crate::wchar::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+#[serial]
+fn test_command_correction() {
+    let _cleanup = test_init();
+    let dir = "test/command_correction_test";
+    std::fs::create_dir_all(dir).unwrap();
+    for (name, mode) in [
+        ("git", 0o755),
+        ("gta", 0o755),
+        ("corrq", 0o755),
+        ("corrs", 0o755),
+        ("corrnoexec", 0o644),
+    ] {
+        let path = format!("{dir}/{name}");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+    let mut vars = TestEnvironment::default();
+    vars.vars
+        .insert(L!("PATH").to_owned(), WString::from_str(dir));
+
+    let correct = |typed: &wstr| command_correction(typed, &vars);
+
+    // Swapped characters are closer than unrelated ones.
+    assert_eq!(correct(L!("gti")), Some(L!("git").to_owned()));
+    // Ties go to the name that sorts first.
+    assert_eq!(correct(L!("corrw")), Some(L!("corrq").to_owned()));
+    // Only executables are offered.
+    assert_eq!(correct(L!("corrnoexce")), None);
+    // Names that are too far off are not offered.
+    assert_eq!(correct(L!("xyzzy")), None);
+    // Paths are not corrected.
+    assert_eq!(correct(L!("./gti")), None);
+
+    // Builtins, functions and abbreviations are offered too.
+    assert_eq!(correct(L!("ecoh")), Some(L!("echo").to_owned()));
+    Parser::principal_parser().eval(L!("function corr_test_func; end"), &IoChain::new());
+    with_abbrs_mut(|abbrset| {
+        abbrset.add(Abbreviation::new(
+            L!("corrabbr").to_owned(),
+            L!("corrabbr").to_owned(),
+            L!("echo").to_owned(),
+            abbrs::Position::Command,
+            false,
+        ));
+    });
+    let _remove_test_commands = ScopeGuard::new((), |_| {
+        function::remove(L!("corr_test_func"));
+        with_abbrs_mut(|abbrset| {
+            abbrset.erase(L!("corrabbr"));
+        });
+    });
+    assert_eq!(
+        correct(L!("corr_test_fucn")),
+        Some(L!("corr_test_func").to_owned())
+    );
+    assert_eq!(correct(L!("corrabbt")), Some(L!("corrabbr").to_owned()));
+}
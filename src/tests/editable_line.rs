@@ -1,5 +1,5 @@
 use crate::{
-    editable_line::{Edit, EditableLine},
+    editable_line::{DraftRing, Edit, EditableLine},
     wchar::prelude::*,
 };
 
@@ -71,3 +71,45 @@ fn test_undo_group() {
     line.undo();
     assert_eq!(line.text(), "a");
 }
+
+#[test]
+fn test_draft_ring() {
+    let mut drafts = DraftRing::default();
+    assert!(drafts.next(L!("")).is_none());
+
+    drafts.add(L!(""));
+    assert!(drafts.is_empty());
+
+    drafts.add(L!("echo a"));
+    drafts.add(L!("echo b"));
+    drafts.add(L!("echo c"));
+    assert_eq!(drafts.next(L!("")), Some(L!("echo c")));
+    assert_eq!(drafts.next(L!("")), Some(L!("echo b")));
+    assert_eq!(drafts.next(L!("")), Some(L!("echo a")));
+    assert_eq!(drafts.next(L!("")), Some(L!("echo c")));
+
+    // Adding a duplicate moves it to the front and restarts the cycle.
+    drafts.add(L!("echo a"));
+    assert_eq!(drafts.len(), 3);
+    assert_eq!(drafts.next(L!("")), Some(L!("echo a")));
+    assert_eq!(drafts.next(L!("")), Some(L!("echo c")));
+    drafts.reset_cycle();
+    assert_eq!(drafts.next(L!("")), Some(L!("echo a")));
+}
+
+#[test]
+fn test_draft_ring_saves_current_line() {
+    let mut drafts = DraftRing::default();
+    // Without other drafts, the current line stays.
+    assert!(drafts.next(L!("echo new")).is_none());
+    assert_eq!(drafts.len(), 1);
+
+    drafts.add(L!("echo old"));
+    assert_eq!(drafts.next(L!("echo new")), Some(L!("echo old")));
+    assert_eq!(drafts.len(), 2);
+    // While cycling, the restored draft is not added again, and cycling brings back the line
+    // that was replaced.
+    assert_eq!(drafts.next(L!("echo old")), Some(L!("echo new")));
+    assert_eq!(drafts.next(L!("echo new")), Some(L!("echo old")));
+    assert_eq!(drafts.len(), 2);
+}
@@ -1,15 +1,22 @@
+use crate::abbrs::{self, with_abbrs_mut, Abbreviation};
 use crate::common::ScopeGuard;
 use crate::env::EnvMode;
+use crate::function;
 use crate::future_feature_flags::{self, FeatureFlag};
+use crate::io::IoChain;
 use crate::parser::Parser;
 use crate::tests::prelude::*;
 use crate::wchar::prelude::*;
 use crate::{
     env::EnvStack,
-    highlight::{highlight_shell, is_potential_path, HighlightRole, HighlightSpec, PathFlags},
+    highlight::{
+        highlight_shell, highlight_shell_with_cache, is_potential_path, HighlightCache,
+        HighlightRole, HighlightSpec, MatchingPairs, PathFlags,
+    },
     operation_context::{OperationContext, EXPANSION_LIMIT_BACKGROUND, EXPANSION_LIMIT_DEFAULT},
 };
 use libc::PATH_MAX;
+use std::sync::Mutex;
 
 // Helper to return a string whose length greatly exceeds PATH_MAX.
 fn get_overlong_path() -> String {
@@ -272,7 +279,7 @@ fn test_highlighting() {
     });
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("./foo", param_valid_path),
         ("&", fg(HighlightRole::statement_terminator)),
     );
@@ -286,11 +293,11 @@ fn test_highlighting() {
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("foo&bar", fg(HighlightRole::param)),
         ("foo", fg(HighlightRole::param), ns),
         ("&", fg(HighlightRole::statement_terminator)),
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("&>", fg(HighlightRole::redirection)),
     );
 
@@ -298,7 +305,7 @@ fn test_highlighting() {
         ("if command", fg(HighlightRole::keyword)),
         ("ls", fg(HighlightRole::command)),
         ("; ", fg(HighlightRole::statement_terminator)),
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("abc", fg(HighlightRole::param)),
         ("; ", fg(HighlightRole::statement_terminator)),
         ("/bin/definitely_not_a_command", fg(HighlightRole::error)),
@@ -308,30 +315,30 @@ fn test_highlighting() {
 
     // Verify that cd shows errors for non-directories.
     validate!(
-        ("cd", fg(HighlightRole::command)),
+        ("cd", fg(HighlightRole::builtin)),
         ("dir", param_valid_path),
     );
 
     validate!(
-        ("cd", fg(HighlightRole::command)),
+        ("cd", fg(HighlightRole::builtin)),
         ("foo", fg(HighlightRole::error)),
     );
 
     validate!(
-        ("cd", fg(HighlightRole::command)),
+        ("cd", fg(HighlightRole::builtin)),
         ("--help", fg(HighlightRole::option)),
         ("-h", fg(HighlightRole::option)),
         ("definitely_not_a_directory", fg(HighlightRole::error)),
     );
 
     validate!(
-        ("cd", fg(HighlightRole::command)),
+        ("cd", fg(HighlightRole::builtin)),
         ("dir-in-cdpath", param_valid_path),
     );
 
     // Command substitutions.
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("param1", fg(HighlightRole::param)),
         ("-l", fg(HighlightRole::option)),
         ("--", fg(HighlightRole::option)),
@@ -347,33 +354,33 @@ fn test_highlighting() {
         ("cat", fg(HighlightRole::command)),
     );
     validate!(
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         ("$(", fg(HighlightRole::operat)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         (")", fg(HighlightRole::operat)),
     );
     validate!(
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         ("\"before", fg(HighlightRole::quote)),
         ("$(", fg(HighlightRole::operat)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         ("param1", fg(HighlightRole::param)),
         (")", fg(HighlightRole::operat)),
         ("after\"", fg(HighlightRole::quote)),
         ("param2", fg(HighlightRole::param)),
     );
     validate!(
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         ("\"", fg(HighlightRole::error)),
         ("unclosed quote", fg(HighlightRole::quote)),
         ("$(", fg(HighlightRole::operat)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         (")", fg(HighlightRole::operat)),
     );
 
     // Redirections substitutions.
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("param1", fg(HighlightRole::param)),
         // Input redirection.
         ("<", fg(HighlightRole::redirection)),
@@ -401,7 +408,7 @@ fn test_highlighting() {
         // Output redirection containing a command substitution.
         ("4>", fg(HighlightRole::redirection)),
         ("(", fg(HighlightRole::operat)),
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("test/somewhere", fg(HighlightRole::param)),
         (")", fg(HighlightRole::operat)),
         // Just another param.
@@ -415,7 +422,7 @@ fn test_highlighting() {
         ("set-by-for-1", fg(HighlightRole::param)),
         ("set-by-for-2", fg(HighlightRole::param)),
         (";", fg(HighlightRole::statement_terminator)),
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         (">", fg(HighlightRole::redirection)),
         ("$x", fg(HighlightRole::redirection)),
         (";", fg(HighlightRole::statement_terminator)),
@@ -423,11 +430,11 @@ fn test_highlighting() {
     );
 
     validate!(
-        ("set", fg(HighlightRole::command)),
+        ("set", fg(HighlightRole::builtin)),
         ("x", fg(HighlightRole::param)),
         ("set-by-set", fg(HighlightRole::param)),
         (";", fg(HighlightRole::statement_terminator)),
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         (">", fg(HighlightRole::redirection)),
         ("$x", fg(HighlightRole::redirection)),
         ("2>", fg(HighlightRole::redirection)),
@@ -440,7 +447,7 @@ fn test_highlighting() {
         ("x", fg(HighlightRole::param), ns),
         ("=", fg(HighlightRole::operat), ns),
         ("set-by-variable-override", fg(HighlightRole::param), ns),
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         (">", fg(HighlightRole::redirection)),
         ("$x", fg(HighlightRole::redirection)),
     );
@@ -453,26 +460,27 @@ fn test_highlighting() {
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("'", fg(HighlightRole::error)),
         ("single_quote", fg(HighlightRole::quote)),
         ("$stuff", fg(HighlightRole::quote)),
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("\"", fg(HighlightRole::error)),
         ("double_quote", fg(HighlightRole::quote)),
-        ("$stuff", fg(HighlightRole::operat)),
+        ("$stuff", fg(HighlightRole::unset_variable)),
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
-        ("$foo", fg(HighlightRole::operat)),
+        ("echo", fg(HighlightRole::builtin)),
+        ("$foo", fg(HighlightRole::unset_variable)),
         ("\"", fg(HighlightRole::quote)),
-        ("$bar", fg(HighlightRole::operat)),
+        ("$bar", fg(HighlightRole::unset_variable)),
         ("\"", fg(HighlightRole::quote)),
-        ("$baz[", fg(HighlightRole::operat)),
+        ("$baz", fg(HighlightRole::unset_variable)),
+        ("[", fg(HighlightRole::operat), ns),
         ("1 2..3", fg(HighlightRole::param)),
         ("]", fg(HighlightRole::operat)),
     );
@@ -487,8 +495,9 @@ fn test_highlighting() {
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
-        ("$$foo[", fg(HighlightRole::operat)),
+        ("echo", fg(HighlightRole::builtin)),
+        ("$$foo", fg(HighlightRole::unset_variable)),
+        ("[", fg(HighlightRole::operat), ns),
         ("1", fg(HighlightRole::param)),
         ("][", fg(HighlightRole::operat)),
         ("2", fg(HighlightRole::param)),
@@ -514,34 +523,34 @@ fn test_highlighting() {
 
     validate!(
         ("if", fg(HighlightRole::keyword)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         ("&&", fg(HighlightRole::operat)),
-        ("false", fg(HighlightRole::command)),
+        ("false", fg(HighlightRole::builtin)),
         (";", fg(HighlightRole::statement_terminator)),
         ("or", fg(HighlightRole::operat)),
-        ("false", fg(HighlightRole::command)),
+        ("false", fg(HighlightRole::builtin)),
         ("||", fg(HighlightRole::operat)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         (";", fg(HighlightRole::statement_terminator)),
         ("and", fg(HighlightRole::operat)),
         ("not", fg(HighlightRole::operat)),
         ("!", fg(HighlightRole::operat)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         (";", fg(HighlightRole::statement_terminator)),
         ("end", fg(HighlightRole::keyword)),
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("%self", fg(HighlightRole::operat)),
         ("not%self", fg(HighlightRole::param)),
         ("self%not", fg(HighlightRole::param)),
     );
 
     validate!(
-        ("false", fg(HighlightRole::command)),
+        ("false", fg(HighlightRole::builtin)),
         ("&|", fg(HighlightRole::statement_terminator)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
     );
 
     validate!(
@@ -553,25 +562,25 @@ fn test_highlighting() {
         ("VAL1", fg(HighlightRole::param), ns),
         ("VAR", fg(HighlightRole::param)),
         ("=", fg(HighlightRole::operat), ns),
-        ("false", fg(HighlightRole::command)),
+        ("false", fg(HighlightRole::builtin)),
         ("|&", fg(HighlightRole::error)),
-        ("true", fg(HighlightRole::command)),
+        ("true", fg(HighlightRole::builtin)),
         ("stuff", fg(HighlightRole::param)),
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)), // (
+        ("echo", fg(HighlightRole::builtin)), // (
         (")", fg(HighlightRole::error)),
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("stuff", fg(HighlightRole::param)),
         ("# comment", fg(HighlightRole::comment)),
     );
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("--", fg(HighlightRole::option)),
         ("-s", fg(HighlightRole::param)),
     );
@@ -590,7 +599,7 @@ fn test_highlighting() {
 
     // Highlighting works across escaped line breaks (#8444).
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("$FISH_\\\n", fg(HighlightRole::operat)),
         ("VERSION", fg(HighlightRole::operat), ns),
     );
@@ -620,15 +629,15 @@ fn test_highlighting() {
     validate!(("\"$EMPTY_VARIABLE\"", fg(HighlightRole::error)));
 
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("\\UFDFD", fg(HighlightRole::escape)),
     );
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("\\U10FFFF", fg(HighlightRole::escape)),
     );
     validate!(
-        ("echo", fg(HighlightRole::command)),
+        ("echo", fg(HighlightRole::builtin)),
         ("\\U110000", fg(HighlightRole::error)),
     );
 
@@ -636,4 +645,267 @@ fn test_highlighting() {
         (">", fg(HighlightRole::error)),
         ("echo", fg(HighlightRole::error)),
     );
+
+    // Commands are colored by what kind of command they are.
+    Parser::principal_parser().eval(L!("function hl_test_function; end"), &IoChain::new());
+    with_abbrs_mut(|abbrset| {
+        abbrset.add(Abbreviation::new(
+            L!("hl_test_abbr").to_owned(),
+            L!("hl_test_abbr").to_owned(),
+            L!("echo").to_owned(),
+            abbrs::Position::Command,
+            false,
+        ));
+        abbrset.add(Abbreviation::new(
+            L!("hl_test_anywhere").to_owned(),
+            L!("hl_test_anywhere").to_owned(),
+            L!("echo").to_owned(),
+            abbrs::Position::Anywhere,
+            false,
+        ));
+    });
+    let _remove_test_commands = ScopeGuard::new((), |_| {
+        function::remove(L!("hl_test_function"));
+        with_abbrs_mut(|abbrset| {
+            abbrset.erase(L!("hl_test_abbr"));
+            abbrset.erase(L!("hl_test_anywhere"));
+        });
+    });
+
+    validate!(
+        ("hl_test_function", fg(HighlightRole::function)),
+        ("hl_test_anywhere", fg(HighlightRole::abbreviation)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("hl_test_abbr", fg(HighlightRole::abbreviation)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("builtin", fg(HighlightRole::keyword)),
+        ("echo", fg(HighlightRole::builtin)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("/bin/cat", fg(HighlightRole::command)),
+    );
+
+    // Unset variables stand out, unless the commandline sets them first.
+    validate!(
+        ("echo", fg(HighlightRole::builtin)),
+        ("$PATH", fg(HighlightRole::operat)),
+        ("$PAHT", fg(HighlightRole::unset_variable)),
+        ("\"", fg(HighlightRole::quote)),
+        ("$PAHT", fg(HighlightRole::unset_variable)),
+        ("\"", fg(HighlightRole::quote)),
+        ("$status", fg(HighlightRole::operat)),
+    );
+
+    validate!(
+        ("if", fg(HighlightRole::keyword)),
+        ("true", fg(HighlightRole::builtin)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("set", fg(HighlightRole::builtin)),
+        ("-l", fg(HighlightRole::option)),
+        ("hl_test_var", fg(HighlightRole::param)),
+        ("1", fg(HighlightRole::param)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("end", fg(HighlightRole::keyword)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("echo", fg(HighlightRole::builtin)),
+        ("$hl_test_var", fg(HighlightRole::operat)),
+    );
+
+    validate!(
+        ("function", fg(HighlightRole::keyword)),
+        ("hl_test_other", fg(HighlightRole::param)),
+        ("-a", fg(HighlightRole::option)),
+        ("hl_test_arg", fg(HighlightRole::param)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("argparse", fg(HighlightRole::builtin)),
+        ("h/help", fg(HighlightRole::param)),
+        ("--", fg(HighlightRole::option)),
+        ("$argv", fg(HighlightRole::operat)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("echo", fg(HighlightRole::builtin)),
+        ("$hl_test_arg", fg(HighlightRole::operat)),
+        ("$_flag_help", fg(HighlightRole::operat)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("end", fg(HighlightRole::keyword)),
+    );
+
+    // Function bodies run later, when variables set elsewhere may be there.
+    validate!(
+        ("function", fg(HighlightRole::keyword)),
+        ("hl_test_foo", fg(HighlightRole::param)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("echo", fg(HighlightRole::builtin)),
+        ("$__hl_test_foo_state", fg(HighlightRole::operat)),
+        ("(", fg(HighlightRole::operat)),
+        ("echo", fg(HighlightRole::builtin)),
+        ("$__hl_test_foo_state", fg(HighlightRole::operat)),
+        (")", fg(HighlightRole::operat)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("end", fg(HighlightRole::keyword)),
+        (";", fg(HighlightRole::statement_terminator)),
+        ("echo", fg(HighlightRole::builtin)),
+        ("$__hl_test_foo_state", fg(HighlightRole::unset_variable)),
+    );
+}
+
+#[test]
+#[serial]
+fn test_highlight_matching_pairs() {
+    let _cleanup = test_init();
+    let pairs = |src: &str, cursor: usize| -> Vec<(usize, usize)> {
+        MatchingPairs::new(&WString::from_str(src))
+            .at(cursor)
+            .into_iter()
+            .map(|range| (range.start(), range.end()))
+            .collect()
+    };
+
+    // Brackets, at the cursor or just before it.
+    assert_eq!(pairs("echo (foo [1])", 5), [(5, 6), (13, 14)]);
+    assert_eq!(pairs("echo (foo [1])", 14), [(5, 6), (13, 14)]);
+    assert_eq!(pairs("echo (foo [1])", 11), [(10, 11), (12, 13)]);
+
+    // Quotes, and brackets in quotes.
+    assert_eq!(pairs("echo 'a(b' \"c$(d)\"", 5), [(5, 6), (9, 10)]);
+    assert!(pairs("echo 'a(b' \"c$(d)\"", 7).is_empty());
+    assert_eq!(pairs("echo 'a(b' \"c$(d)\"", 11), [(11, 12), (17, 18)]);
+    assert_eq!(pairs("echo 'a(b' \"c$(d)\"", 14), [(14, 15), (16, 17)]);
+    assert!(pairs("echo # (", 7).is_empty());
+
+    // Block keywords.
+    let if_else = "if true; echo; else if false; else; end";
+    let if_else_keywords = [(0, 2), (15, 19), (20, 22), (30, 34), (36, 39)];
+    assert_eq!(pairs(if_else, 0), if_else_keywords);
+    assert_eq!(pairs(if_else, 31), if_else_keywords);
+    assert_eq!(pairs(if_else, 39), if_else_keywords);
+    assert!(pairs(if_else, 3).is_empty());
+    assert_eq!(pairs("begin; for x in a; end; end", 7), [(7, 10), (19, 22)]);
+    assert_eq!(pairs("begin; for x in a; end; end", 24), [(0, 5), (24, 27)]);
+    assert_eq!(pairs("echo (begin; end)", 6), [(6, 11), (13, 16)]);
+    assert_eq!(
+        pairs("echo (if true; end) (begin; end)", 22),
+        [(21, 26), (28, 31)]
+    );
+
+    // Slices and brace expansions, and several command substitutions in double quotes.
+    assert_eq!(pairs("echo $a[1..2] {b,c}", 7), [(7, 8), (12, 13)]);
+    assert_eq!(pairs("echo $a[1..2] {b,c}", 19), [(14, 15), (18, 19)]);
+    assert_eq!(pairs("echo \"$(a)b$(c)\"", 5), [(5, 6), (15, 16)]);
+    assert_eq!(pairs("echo \"$(a)b$(c)\"", 12), [(12, 13), (14, 15)]);
+    assert!(pairs("while true", 0).is_empty());
+}
+
+#[test]
+#[serial]
+fn test_highlight_cache() {
+    let _cleanup = test_init();
+    pushd("test/fish_highlight_cache_test/");
+    let _popd = ScopeGuard::new((), |_| popd());
+    std::fs::write("foo", []).unwrap();
+
+    let vars = Parser::principal_parser().vars();
+    let ctx = OperationContext::background(vars, EXPANSION_LIMIT_BACKGROUND);
+    let cache = Mutex::new(HighlightCache::default());
+    let src = L!("echo foo");
+    let foo_is_valid_path = |cache: Option<&Mutex<HighlightCache>>| {
+        let mut colors = vec![];
+        match cache {
+            Some(cache) => highlight_shell_with_cache(
+                src,
+                &mut colors,
+                &mut MatchingPairs::default(),
+                &ctx,
+                true,
+                None,
+                cache,
+            ),
+            None => highlight_shell(src, &mut colors, &ctx, true, None),
+        }
+        colors[5].valid_path
+    };
+
+    assert!(foo_is_valid_path(Some(&cache)));
+    std::fs::remove_file("foo").unwrap();
+    assert!(!foo_is_valid_path(None));
+    // The earlier result is reused until the cache is cleared.
+    assert!(foo_is_valid_path(Some(&cache)));
+    cache.lock().unwrap().clear();
+    assert!(!foo_is_valid_path(Some(&cache)));
+
+    // Defining a function invalidates what we know about commands.
+    let command_role = || {
+        let mut colors = vec![];
+        highlight_shell_with_cache(
+            L!("hl_cache_test_function"),
+            &mut colors,
+            &mut MatchingPairs::default(),
+            &ctx,
+            true,
+            None,
+            &cache,
+        );
+        colors[0].foreground
+    };
+    assert_eq!(command_role(), HighlightRole::error);
+    Parser::principal_parser().eval(L!("function hl_cache_test_function; end"), &IoChain::new());
+    assert_eq!(command_role(), HighlightRole::function);
+    function::remove(L!("hl_cache_test_function"));
+    assert_eq!(command_role(), HighlightRole::error);
+
+    // So does setting a variable.
+    let variable_role = |src: &wstr, pos: usize| {
+        let mut colors = vec![];
+        highlight_shell_with_cache(
+            src,
+            &mut colors,
+            &mut MatchingPairs::default(),
+            &ctx,
+            true,
+            None,
+            &cache,
+        );
+        let mut uncached = vec![];
+        highlight_shell(src, &mut uncached, &ctx, true, None);
+        // Reusing the colors of unchanged statements gives the same result as starting over.
+        assert_eq!(colors, uncached);
+        colors[pos].foreground
+    };
+    let src = L!("echo $hl_cache_test_var");
+    assert_eq!(variable_role(src, 5), HighlightRole::unset_variable);
+    vars.set_one(L!("hl_cache_test_var"), EnvMode::GLOBAL, L!("1").to_owned());
+    assert_eq!(variable_role(src, 5), HighlightRole::param);
+    vars.remove(L!("hl_cache_test_var"), EnvMode::GLOBAL);
+    assert_eq!(variable_role(src, 5), HighlightRole::unset_variable);
+
+    // An unchanged statement is colored anew if the statements before it define other variables.
+    let src = L!("set x 1; echo $x");
+    assert_eq!(variable_role(src, 14), HighlightRole::param);
+    let src = L!("set y 1; echo $x");
+    assert_eq!(variable_role(src, 14), HighlightRole::unset_variable);
+    let src = L!("set x 1; echo $x; echo $x");
+    assert_eq!(variable_role(src, 23), HighlightRole::param);
+
+    // Reused statements keep their matching pairs, moved to where they are now.
+    let pairs = |src: &wstr, cursor: usize| -> Vec<(usize, usize)> {
+        let mut matching_pairs = MatchingPairs::default();
+        highlight_shell_with_cache(
+            src,
+            &mut vec![],
+            &mut matching_pairs,
+            &ctx,
+            true,
+            None,
+            &cache,
+        );
+        assert_eq!(matching_pairs.text, src);
+        matching_pairs
+            .at(cursor)
+            .into_iter()
+            .map(|range| (range.start(), range.end()))
+            .collect()
+    };
+    let src = L!("begin; echo (a); end");
+    assert_eq!(pairs(src, 12), [(12, 13), (14, 15)]);
+    let src = L!("true; begin; echo (a); end");
+    assert_eq!(pairs(src, 18), [(18, 19), (20, 21)]);
+    assert_eq!(pairs(src, 6), [(6, 11), (23, 26)]);
 }
@@ -1,6 +1,9 @@
-use crate::input::{input_mappings, Inputter, KeyNameStyle, DEFAULT_BIND_MODE};
+use crate::input::{
+    input_macro_commands, input_mappings, InputMappingOptions, InputMappingSet, Inputter,
+    KeyNameStyle, DEFAULT_BIND_MODE, LAST_MACRO_REGISTER,
+};
 use crate::input_common::{CharEvent, ReadlineCmd};
-use crate::key::Key;
+use crate::key::{canonicalize_raw_escapes, ctrl, Key, KeyEventType};
 use crate::parser::Parser;
 use crate::tests::prelude::*;
 use crate::wchar::prelude::*;
@@ -55,3 +58,270 @@ fn test_input() {
         panic!("Expected to read char down_line");
     }
 }
+
+#[test]
+#[serial]
+fn test_input_macro() {
+    let _cleanup = test_init();
+    use crate::env::EnvStack;
+    let parser = Parser::new(Rc::new(EnvStack::new()), false);
+    let mut input = Inputter::new(parser, libc::STDIN_FILENO);
+
+    assert!(!input.macro_begin('1'));
+    assert!(input.macro_begin('a'));
+    input.queue_char(CharEvent::from_readline(ReadlineCmd::UpLine));
+    input.queue_char(CharEvent::Command(L!("echo hello").to_owned()));
+    input.queue_char(CharEvent::from_readline(ReadlineCmd::EndMacro));
+    assert_eq!(input.read_char().get_readline(), ReadlineCmd::UpLine);
+    assert!(matches!(input.read_char(), CharEvent::Command(_)));
+    assert_eq!(input.read_char().get_readline(), ReadlineCmd::EndMacro);
+    assert!(input.macro_end());
+    assert!(!input.macro_end());
+
+    // The macro commands themselves are not part of the recording.
+    let Ok(commands) = input_macro_commands('a') else {
+        panic!("Expected the macro to be bindable");
+    };
+    assert_eq!(
+        commands,
+        vec![L!("up-line").to_owned(), L!("echo hello").to_owned()]
+    );
+
+    assert!(input_macro_commands('b').is_err());
+
+    // Replaying returns the same events again, as often as we like. The last recorded macro is
+    // also in the '@' register.
+    assert!(!input.macro_play('b', 1));
+    assert!(input.macro_play('a', 1));
+    assert!(input.macro_play(LAST_MACRO_REGISTER, 2));
+    for _ in 0..3 {
+        assert_eq!(input.read_char().get_readline(), ReadlineCmd::UpLine);
+        let CharEvent::Command(cmd) = input.read_char() else {
+            panic!("Expected a command");
+        };
+        assert_eq!(cmd, L!("echo hello"));
+    }
+
+    // The register of play-macro may be preceded by a repeat count, which is capped.
+    let play_binding = vec![Key::from_raw('z')];
+    input_mappings().add1(
+        play_binding.clone(),
+        KeyNameStyle::Plain,
+        L!("play-macro").to_owned(),
+        DEFAULT_BIND_MODE.to_owned(),
+        None,
+        true,
+    );
+    for (keys, count) in [("za", 1), ("z12a", 12), ("z0a", 1), ("z12345a", 999)] {
+        for c in keys.chars() {
+            input.queue_char(CharEvent::from_key(Key::from_raw(c)));
+        }
+        assert_eq!(input.read_char().get_readline(), ReadlineCmd::PlayMacro);
+        assert_eq!(input.function_pop_repeat_count(), count);
+        assert_eq!(input.function_pop_arg(), 'a');
+    }
+    input_mappings().erase(&play_binding, DEFAULT_BIND_MODE, true);
+}
+
+#[test]
+#[serial]
+fn test_input_sequence_continuations() {
+    let _cleanup = test_init();
+    let default_mode = || DEFAULT_BIND_MODE.to_owned();
+    let mut mappings = InputMappingSet::default();
+    let ctrl_x = ctrl('x');
+    mappings.add1(
+        vec![ctrl_x, ctrl('e')],
+        KeyNameStyle::Plain,
+        L!("edit_command_buffer").to_owned(),
+        default_mode(),
+        None,
+        false,
+    );
+    mappings.add(
+        vec![ctrl_x, ctrl('e')],
+        KeyNameStyle::Plain,
+        vec![L!("true").to_owned()],
+        default_mode(),
+        InputMappingOptions {
+            description: Some(L!("Edit").to_owned()),
+            ..Default::default()
+        },
+        true,
+    );
+    mappings.add1(
+        vec![ctrl_x, Key::from_raw('a'), Key::from_raw('b')],
+        KeyNameStyle::Plain,
+        L!("up-line").to_owned(),
+        default_mode(),
+        None,
+        false,
+    );
+    mappings.add1(
+        vec![ctrl_x],
+        KeyNameStyle::Plain,
+        L!("fish_clipboard_copy").to_owned(),
+        default_mode(),
+        None,
+        false,
+    );
+
+    let mut hints = mappings.continuations(&[ctrl_x], DEFAULT_BIND_MODE);
+    hints.sort();
+    assert_eq!(
+        hints,
+        vec![
+            (L!("a,b").to_owned(), L!("up-line").to_owned()),
+            (L!("ctrl-e").to_owned(), L!("Edit").to_owned()),
+        ]
+    );
+    assert!(mappings.continuations(&[ctrl_x], L!("insert")).is_empty());
+}
+
+#[test]
+#[serial]
+fn test_kitty_keyboard_flags() {
+    let mut mappings = InputMappingSet::default();
+    let mode = || DEFAULT_BIND_MODE.to_owned();
+    let mut release = Key::from_raw('a');
+    release.modifiers.event = KeyEventType::Release;
+    let mut keypad = Key::from_raw('5');
+    keypad.modifiers.keypad = true;
+
+    // Plain keys need nothing beyond the flags we always ask for.
+    mappings.add1(
+        vec![Key::from_raw('a')],
+        KeyNameStyle::Plain,
+        L!("foo").to_owned(),
+        mode(),
+        None,
+        true,
+    );
+    assert_eq!(mappings.kitty_keyboard_flags(), 0);
+
+    mappings.add1(
+        vec![release],
+        KeyNameStyle::Plain,
+        L!("bar").to_owned(),
+        mode(),
+        None,
+        true,
+    );
+    mappings.add1(
+        vec![keypad],
+        KeyNameStyle::Plain,
+        L!("baz").to_owned(),
+        L!("insert").to_owned(),
+        None,
+        false,
+    );
+    let release_flags = mappings.kitty_keyboard_flags();
+    assert_ne!(release_flags, 0);
+
+    // Erasing a binding drops what only it needed.
+    assert!(mappings.erase(&[release], &mode(), true));
+    let keypad_flags = mappings.kitty_keyboard_flags();
+    assert_ne!(keypad_flags, 0);
+    assert_ne!(keypad_flags, release_flags);
+
+    mappings.clear(Some(L!("insert")), false);
+    assert_eq!(mappings.kitty_keyboard_flags(), 0);
+}
+
+#[test]
+fn test_key_recording() {
+    use crate::key_recording::{key_recording_line, parse_key_recording, RecordedKey};
+    use std::time::Duration;
+
+    let mut recording = WString::from_str("# fish key recording\n");
+    recording.push_utfstr(&key_recording_line(Duration::ZERO, L!("\x18"), ctrl('x')));
+    recording.push_utfstr(&key_recording_line(
+        Duration::from_millis(120),
+        L!(" "),
+        Key::from_raw(' '),
+    ));
+    recording.push_utfstr(&key_recording_line(
+        Duration::from_millis(5),
+        L!("\x1b[A"),
+        Key::from_raw(crate::key::Up),
+    ));
+    assert_eq!(
+        parse_key_recording(&recording),
+        Ok(vec![
+            RecordedKey {
+                delay: Duration::ZERO,
+                seq: L!("\x18").to_owned(),
+            },
+            RecordedKey {
+                delay: Duration::from_millis(120),
+                seq: L!(" ").to_owned(),
+            },
+            RecordedKey {
+                delay: Duration::from_millis(5),
+                seq: L!("\x1b[A").to_owned(),
+            },
+        ])
+    );
+    assert_eq!(parse_key_recording(L!("0\t\\cx\n\nsoon\t\\cx\n")), Err(3));
+}
+
+#[test]
+#[serial]
+fn test_input_binding_commands() {
+    let _cleanup = test_init();
+    let mut mappings = InputMappingSet::default();
+    let commands = |mappings: &InputMappingSet, key: Key, raw: &str, mode: &wstr| {
+        mappings.binding_commands(key, &WString::from_str(raw), mode)
+    };
+    let cmd = |cmd: &str| Some(vec![WString::from_str(cmd)]);
+
+    mappings.add1(
+        vec![ctrl('x')],
+        KeyNameStyle::Plain,
+        L!("preset-command").to_owned(),
+        DEFAULT_BIND_MODE.to_owned(),
+        None,
+        false,
+    );
+    assert_eq!(
+        commands(&mappings, ctrl('x'), "\x18", DEFAULT_BIND_MODE),
+        cmd("preset-command")
+    );
+
+    // User bindings shadow preset ones.
+    mappings.add1(
+        vec![ctrl('x')],
+        KeyNameStyle::Plain,
+        L!("user-command").to_owned(),
+        DEFAULT_BIND_MODE.to_owned(),
+        None,
+        true,
+    );
+    assert_eq!(
+        commands(&mappings, ctrl('x'), "\x18", DEFAULT_BIND_MODE),
+        cmd("user-command")
+    );
+
+    // Other modes and other keys have no binding.
+    assert_eq!(commands(&mappings, ctrl('x'), "\x18", L!("insert")), None);
+    assert_eq!(
+        commands(&mappings, ctrl('y'), "\x19", DEFAULT_BIND_MODE),
+        None
+    );
+
+    // Raw escape sequences match what the terminal sent.
+    mappings.add1(
+        canonicalize_raw_escapes("\x1b[A".chars().map(Key::from_raw).collect()),
+        KeyNameStyle::RawEscapeSequence,
+        L!("up-line").to_owned(),
+        DEFAULT_BIND_MODE.to_owned(),
+        None,
+        true,
+    );
+    let up = Key::from_raw(crate::key::Up);
+    assert_eq!(
+        commands(&mappings, up, "\x1b[A", DEFAULT_BIND_MODE),
+        cmd("up-line")
+    );
+    assert_eq!(commands(&mappings, up, "\x1bOA", DEFAULT_BIND_MODE), None);
+}
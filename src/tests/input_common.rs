@@ -1,4 +1,10 @@
+use crate::fds::make_autoclose_pipes;
 use crate::input_common::{CharEvent, InputEventQueue, InputEventQueuer, ReadlineCmd};
+use crate::key::Key;
+use crate::tests::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsRawFd;
 
 #[test]
 fn test_push_front_back() {
@@ -57,3 +63,17 @@ fn test_insert_front() {
     assert_eq!(queue.try_pop().unwrap().get_char(), 'a');
     assert_eq!(queue.try_pop().unwrap().get_char(), 'b');
 }
+
+#[test]
+#[serial]
+fn test_kitty_modifier_keys_are_skipped() {
+    let _cleanup = test_init();
+    let pipes = make_autoclose_pipes().unwrap();
+    let mut queue = InputEventQueue::new(pipes.read.as_raw_fd());
+    // Left shift, ISO level 3 shift (AltGr) and ISO level 5 shift, then a plain "a".
+    File::from(pipes.write)
+        .write_all(b"\x1b[57441u\x1b[57453u\x1b[57454ua")
+        .unwrap();
+    let evt = queue.readch();
+    assert_eq!(evt.get_key().unwrap().key, Key::from_raw('a'));
+}
@@ -1,4 +1,4 @@
-use crate::key::{self, ctrl, function_key, parse_keys, Key};
+use crate::key::{self, ctrl, function_key, parse_keys, Key, KeyEventType};
 use crate::wchar::prelude::*;
 
 #[test]
@@ -18,3 +18,21 @@ fn test_parse_key() {
     );
     assert!(parse_keys(L!("F1")).is_err());
 }
+
+#[test]
+fn test_parse_kitty_key() {
+    let mut release = ctrl('x');
+    release.modifiers.event = KeyEventType::Release;
+    assert_eq!(parse_keys(L!("release-ctrl-x")), Ok(vec![release]));
+    assert_eq!(WString::from(release), L!("release-ctrl-x"));
+
+    let mut keypad = Key::from_raw('5');
+    keypad.modifiers.keypad = true;
+    assert_eq!(parse_keys(L!("keypad-5")), Ok(vec![keypad]));
+    assert_eq!(WString::from(keypad), L!("keypad-5"));
+    assert_eq!(keypad.codepoint_text(), Some('5'));
+
+    let mut repeat = Key::from_raw(key::Backspace);
+    repeat.modifiers.event = KeyEventType::Repeat;
+    assert_eq!(parse_keys(L!("repeat-backspace")), Ok(vec![repeat]));
+}
@@ -1,4 +1,5 @@
 mod abbrs;
+mod command_correction;
 mod common;
 mod complete;
 mod debounce;
@@ -13,6 +14,7 @@ mod history;
 mod input;
 mod input_common;
 mod key;
+mod output;
 mod pager;
 mod parse_util;
 mod parser;
@@ -21,7 +23,9 @@ mod redirection;
 mod screen;
 mod std;
 mod string_escape;
+mod terminal;
 mod termsize;
+mod theme;
 mod threads;
 mod tokenizer;
 mod topic_monitor;
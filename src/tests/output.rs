@@ -0,0 +1,54 @@
+use crate::output::Outputter;
+use crate::wchar::prelude::*;
+
+#[test]
+fn test_synchronized_update() {
+    // Writes in between are wrapped in the sequences that set and reset mode 2026.
+    let mut outp = Outputter::new_buffering();
+    outp.write_wstr(L!("before"));
+    outp.begin_update(true);
+    outp.write_wstr(L!("abc"));
+    outp.end_synchronized_update();
+    assert_eq!(outp.contents(), b"before\x1b[?2026habc\x1b[?2026l");
+
+    // Only the outermost update is wrapped.
+    let mut outp = Outputter::new_buffering();
+    outp.begin_update(true);
+    outp.write_wstr(L!("a"));
+    outp.begin_update(true);
+    outp.write_wstr(L!("b"));
+    outp.end_synchronized_update();
+    outp.write_wstr(L!("c"));
+    outp.end_synchronized_update();
+    assert_eq!(outp.contents(), b"\x1b[?2026habc\x1b[?2026l");
+
+    // An update inside plain buffering is not wrapped either.
+    let mut outp = Outputter::new_buffering();
+    outp.begin_buffering();
+    outp.begin_update(true);
+    outp.write_wstr(L!("abc"));
+    outp.end_synchronized_update();
+    outp.end_buffering();
+    assert_eq!(outp.contents(), b"abc");
+
+    // An empty update is dropped.
+    let mut outp = Outputter::new_buffering();
+    outp.write_wstr(L!("before"));
+    outp.begin_update(true);
+    outp.end_synchronized_update();
+    assert_eq!(outp.contents(), b"before");
+
+    // Without terminal support, the update is plain buffering.
+    let mut outp = Outputter::new_buffering();
+    outp.begin_update(false);
+    outp.write_wstr(L!("abc"));
+    outp.end_synchronized_update();
+    assert_eq!(outp.contents(), b"abc");
+
+    // Buffering outputters never wrap, since they do not write to the terminal.
+    let mut outp = Outputter::new_buffering();
+    outp.begin_synchronized_update();
+    outp.write_wstr(L!("abc"));
+    outp.end_synchronized_update();
+    assert_eq!(outp.contents(), b"abc");
+}
@@ -195,3 +195,74 @@ fn test_pager_layout() {
     validate!(&mut pager, 17, L!("abcdefghijklmnop…"));
     validate!(&mut pager, 16, L!("abcdefghijklmno…"));
 }
+
+#[test]
+#[serial]
+fn test_pager_completion_at() {
+    let _cleanup = test_init();
+    // As in test_pager_navigation, 19 completions of width 10 are laid out in 5 columns of 4 rows,
+    // each 12 wide including the spacer.
+    let completions: Vec<_> = (0..19)
+        .map(|_| {
+            Completion::new(
+                L!("abcdefghij").to_owned(),
+                "".into(),
+                StringFuzzyMatch::exact_match(),
+                CompleteFlags::default(),
+            )
+        })
+        .collect();
+    let mut pager = Pager::default();
+    pager.set_completions(&completions, true);
+    pager.set_term_size(&Termsize::defaults());
+    let render = pager.render();
+    assert_eq!(render.rows, 4);
+    assert_eq!(render.cols, 5);
+
+    assert_eq!(pager.completion_at(&render, 0, 0), Some(0));
+    assert_eq!(pager.completion_at(&render, 1, 11), Some(1));
+    assert_eq!(pager.completion_at(&render, 0, 12), Some(4));
+    assert_eq!(pager.completion_at(&render, 2, 50), Some(18));
+    // The last column is not full.
+    assert_eq!(pager.completion_at(&render, 3, 50), None);
+    // Right of the last column, and below the last row.
+    assert_eq!(pager.completion_at(&render, 0, 70), None);
+    assert_eq!(pager.completion_at(&render, 4, 0), None);
+}
+
+#[test]
+#[serial]
+fn test_pager_hyperlinks() {
+    let _cleanup = test_init();
+    let completions: Vec<_> = ["alpha.txt", "beta"]
+        .into_iter()
+        .map(|comp| {
+            Completion::new(
+                WString::from_str(comp),
+                "".into(),
+                StringFuzzyMatch::exact_match(),
+                CompleteFlags::default(),
+            )
+        })
+        .collect();
+    let mut pager = Pager::default();
+    pager.set_prefix(L!("src/"), true);
+    pager.set_completions_with_links(
+        &completions,
+        |c| (c.completion == "alpha.txt").then(|| L!("file:///src/alpha.txt").to_owned()),
+        true,
+    );
+    pager.set_term_size(&Termsize::defaults());
+    let render = pager.render();
+    assert_eq!(render.screen_data.line_count(), 1);
+    let line = render.screen_data.line(0);
+
+    // The link covers the prefix and the completion, but not the spacing after it.
+    let link = Some(L!("file:///src/alpha.txt"));
+    assert_eq!(line.hyperlink_at(0), link);
+    assert_eq!(line.hyperlink_at(12), link);
+    assert_eq!(line.hyperlink_at(13), None);
+    // Completions without a link are plain text.
+    let beta = (0..line.len()).find(|&i| line.char_at(i) == 'b').unwrap();
+    assert_eq!(line.hyperlink_at(beta), None);
+}
@@ -1,5 +1,11 @@
 use crate::complete::CompleteFlags;
-use crate::reader::{combine_command_and_autosuggestion, completion_apply_to_command_line};
+use crate::highlight::{HighlightRole, HighlightSpec};
+use crate::reader::{
+    combine_command_and_autosuggestion, completion_apply_to_command_line, describe_paste,
+    find_command_correction, paste_needs_confirmation, paste_runs_sudo,
+};
+use crate::tests::env::TestEnvironment;
+use crate::tests::prelude::*;
 use crate::wchar::prelude::*;
 
 #[test]
@@ -165,3 +171,107 @@ fn test_completion_insertions() {
     // See #6130
     validate!(": (:^ ''", "", CompleteFlags::default(), false, ": (: ^''");
 }
+
+#[test]
+fn test_paste_needs_confirmation() {
+    let mut vars = TestEnvironment::default();
+    let multiline = L!("echo a\necho b");
+    // Confirming pastes is opt-in.
+    assert!(!paste_needs_confirmation(&vars, multiline));
+    vars.vars
+        .insert(L!("fish_confirm_paste").to_owned(), L!("0").to_owned());
+    assert!(!paste_needs_confirmation(&vars, multiline));
+
+    vars.vars
+        .insert(L!("fish_confirm_paste").to_owned(), L!("1").to_owned());
+    assert!(paste_needs_confirmation(&vars, multiline));
+    assert!(!paste_needs_confirmation(&vars, L!("echo a\tb")));
+    assert!(paste_needs_confirmation(&vars, L!("echo a\x1b[2K")));
+    assert!(paste_needs_confirmation(&vars, L!("sudo make install")));
+
+    // Large pastes, even on a single line.
+    let mut large = L!("echo ").to_owned();
+    large.push_utfstr(&WString::from_chars(vec!['a'; 994]));
+    assert!(!paste_needs_confirmation(&vars, &large));
+    large.push('a');
+    assert!(paste_needs_confirmation(&vars, &large));
+}
+
+#[test]
+fn test_paste_runs_sudo() {
+    assert!(paste_runs_sudo(L!("sudo make install")));
+    assert!(paste_runs_sudo(L!("echo a; and /usr/bin/sudo true")));
+    assert!(paste_runs_sudo(L!("'sudo' true")));
+    assert!(!paste_runs_sudo(L!("echo sudo")));
+    assert!(!paste_runs_sudo(L!("sudoedit file")));
+
+    // Behind decorations and env.
+    assert!(paste_runs_sudo(L!("command sudo true")));
+    assert!(paste_runs_sudo(L!("exec sudo true")));
+    assert!(paste_runs_sudo(L!("env FOO=bar -i sudo true")));
+    assert!(!paste_runs_sudo(L!("env FOO=sudo true")));
+
+    // In command substitutions and blocks.
+    assert!(paste_runs_sudo(L!("echo (sudo cat /etc/shadow)")));
+    assert!(paste_runs_sudo(L!("echo \"$(echo (sudo id))\"")));
+    assert!(paste_runs_sudo(L!("set x (sudo id)")));
+    assert!(paste_runs_sudo(L!("begin; if true; sudo id; end")));
+}
+
+#[test]
+#[serial]
+fn test_describe_paste() {
+    let _cleanup = test_init();
+    assert_eq!(
+        describe_paste(L!("echo a\necho b; echo c")),
+        "The pasted text contains 3 command(s). Press Enter again to run it."
+    );
+    assert_eq!(
+        describe_paste(L!("sudo id\x07")),
+        concat!(
+            "The pasted text contains 1 command(s). It contains control characters. ",
+            "It runs sudo. Press Enter again to run it."
+        )
+    );
+    let mut large = L!("echo ").to_owned();
+    large.push_utfstr(&WString::from_chars(vec!['a'; 1995]));
+    assert_eq!(
+        describe_paste(&large),
+        concat!(
+            "The pasted text contains 1 command(s). It is 2000 characters long. ",
+            "Press Enter again to run it."
+        )
+    );
+}
+
+#[test]
+fn test_find_command_correction() {
+    let text = L!("echo hi; gti st");
+    let mut colors = vec![HighlightSpec::with_fg(HighlightRole::builtin); text.len()];
+    let correct = |cmd: &wstr| {
+        assert_eq!(cmd, "gti");
+        Some(L!("git").to_owned())
+    };
+
+    // Only commands that highlighting marks as unknown are corrected.
+    assert_eq!(
+        find_command_correction(text, text.len(), &colors, correct),
+        None
+    );
+    colors[9..12].fill(HighlightSpec::with_fg(HighlightRole::error));
+    assert_eq!(
+        find_command_correction(text, text.len(), &colors, correct),
+        Some((9..12, L!("git").to_owned()))
+    );
+    assert_eq!(
+        find_command_correction(text, 9, &colors, correct),
+        Some((9..12, L!("git").to_owned()))
+    );
+    // The command at or before the cursor is the one corrected.
+    assert_eq!(find_command_correction(text, 4, &colors, correct), None);
+    // Without a likely correction, there is nothing to offer.
+    assert_eq!(
+        find_command_correction(text, text.len(), &colors, |_| None),
+        None
+    );
+}
@@ -52,6 +52,17 @@ fn test_escape_string() {
     );
 }
 
+#[test]
+fn test_escape_json() {
+    let json = |input| escape_string(input, EscapeStringStyle::Json);
+    assert_eq!(json(L!("ctrl-x")), L!("\"ctrl-x\""));
+    assert_eq!(json(L!("say \"hi\"\\")), L!("\"say \\\"hi\\\"\\\\\""));
+    assert_eq!(json(L!("\x1b[A\n")), L!("\"\\u001b[A\\n\""));
+    assert_eq!(json(L!("ü")), L!("\"ü\""));
+    // Invalid UTF-8 is decoded to private use characters, which JSON cannot represent.
+    assert_eq!(json(&str2wcstring(b"a\xffb")), L!("\"a\\ufffdb\""));
+}
+
 #[test]
 pub fn test_unescape_sane() {
     const TEST_CASES: &[(&wstr, &wstr)] = &[
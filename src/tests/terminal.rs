@@ -0,0 +1,174 @@
+use crate::color::Color24;
+use crate::terminal::{
+    notification_sequence, parse_dcs_reply, parse_decrpm_reply, parse_osc_reply, parse_x11_color,
+    FocusState, ProbeReply, ProbeState, TerminalFeature, TerminalInfo,
+};
+use crate::wchar::prelude::*;
+
+#[test]
+fn test_notification_sequence() {
+    assert_eq!(
+        notification_sequence(Some(L!("kitty(0.35.2)")), L!("Done"), L!("make")),
+        L!("\x1b]99;i=fish:d=0:o=unfocused;Done\x1b\\\x1b]99;i=fish:p=body;make\x1b\\")
+    );
+    assert_eq!(
+        notification_sequence(Some(L!("foot(1.18.1)")), L!("Done"), L!("make")),
+        L!("\x1b]777;notify;Done;make\x1b\\")
+    );
+    assert_eq!(
+        notification_sequence(None, L!("Done"), L!("make")),
+        L!("\x1b]9;Done: make\x1b\\")
+    );
+}
+
+#[test]
+fn test_focus_state() {
+    let mut focus = FocusState::default();
+    // No query, nothing to show.
+    assert_eq!(focus.finish_query(), None);
+
+    // Focus reports without a query are focus changes.
+    assert!(focus.record(false));
+
+    // The terminal reports having focus, which only answers the query.
+    focus.query(L!("note").to_owned());
+    assert!(!focus.record(true));
+    assert_eq!(focus.finish_query(), None);
+
+    // A report from before the query does not count.
+    focus.query(L!("note").to_owned());
+    assert_eq!(focus.finish_query(), Some(L!("note").to_owned()));
+
+    // The terminal reports not having focus.
+    focus.query(L!("note").to_owned());
+    assert!(!focus.record(false));
+    assert_eq!(focus.finish_query(), Some(L!("note").to_owned()));
+    // Later answers do not show it again.
+    assert_eq!(focus.finish_query(), None);
+}
+
+#[test]
+fn test_parse_x11_color() {
+    assert_eq!(
+        parse_x11_color(b"rgb:ffff/8080/0000"),
+        Some(Color24 {
+            r: 0xff,
+            g: 0x80,
+            b: 0x00
+        })
+    );
+    assert_eq!(
+        parse_x11_color(b"rgb:f/8/0"),
+        Some(Color24 {
+            r: 0xff,
+            g: 0x88,
+            b: 0x00
+        })
+    );
+    assert_eq!(parse_x11_color(b"rgb:ffff/8080"), None);
+    assert_eq!(parse_x11_color(b"rgb:ffff/8080/0000/0000"), None);
+    assert_eq!(parse_x11_color(b"rgb:fffff/8080/0000"), None);
+    assert_eq!(parse_x11_color(b"#ff8000"), None);
+}
+
+#[test]
+fn test_parse_probe_replies() {
+    assert_eq!(
+        parse_decrpm_reply(2026, 2),
+        Some(ProbeReply::Feature(TerminalFeature::SynchronizedOutput))
+    );
+    // Not recognized, or permanently reset.
+    assert_eq!(parse_decrpm_reply(2026, 0), None);
+    assert_eq!(parse_decrpm_reply(2026, 4), None);
+    assert_eq!(parse_decrpm_reply(2004, 1), None);
+
+    assert_eq!(
+        parse_dcs_reply(b">|kitty(0.35.2)"),
+        Some(ProbeReply::Name(L!("kitty(0.35.2)").to_owned()))
+    );
+    assert_eq!(
+        parse_dcs_reply(b"1+r524742=382f382f38"),
+        Some(ProbeReply::Feature(TerminalFeature::Truecolor))
+    );
+    assert_eq!(
+        parse_dcs_reply(b"1+r4D73=5c45"),
+        Some(ProbeReply::Feature(TerminalFeature::Clipboard))
+    );
+    assert_eq!(
+        parse_dcs_reply(b"1+r536d756c78=5c45"),
+        Some(ProbeReply::Feature(TerminalFeature::StyledUnderlines))
+    );
+    // Unknown capabilities are answered with "0+r".
+    assert_eq!(parse_dcs_reply(b"0+r524742"), None);
+    assert_eq!(parse_dcs_reply(b"1+r6b6579"), None);
+
+    let orange = Color24 {
+        r: 0xff,
+        g: 0x80,
+        b: 0x00,
+    };
+    assert_eq!(
+        parse_osc_reply(b"11;rgb:ffff/8080/0000"),
+        vec![ProbeReply::Background(orange)]
+    );
+    assert_eq!(
+        parse_osc_reply(b"4;1;rgb:ffff/8080/0000;16;rgb:0/0/0;2;bogus;3;rgb:ff/80/00"),
+        vec![
+            ProbeReply::PaletteColor(1, orange),
+            ProbeReply::PaletteColor(3, orange)
+        ]
+    );
+    assert!(parse_osc_reply(b"11;?").is_empty());
+    assert!(parse_osc_reply(b"10;rgb:ffff/8080/0000").is_empty());
+}
+
+#[test]
+fn test_terminal_info_record() {
+    let mut info = TerminalInfo::default();
+    assert_eq!(info.describe(), None);
+
+    info.probe = ProbeState::Pending;
+    info.record(parse_dcs_reply(b">|kitty(0.35.2)").unwrap());
+    info.record(parse_decrpm_reply(2026, 2).unwrap());
+    info.record(parse_dcs_reply(b"1+r524742=382f382f38").unwrap());
+    assert_eq!(info.name.as_deref(), Some(L!("kitty(0.35.2)")));
+    // Hyperlink support is known from the name.
+    assert!(info.supports(TerminalFeature::Hyperlinks));
+    assert!(info.supports(TerminalFeature::SynchronizedOutput));
+    assert!(info.supports(TerminalFeature::Truecolor));
+    assert!(!info.supports(TerminalFeature::KittyKeyboard));
+    assert_eq!(
+        info.describe().unwrap(),
+        L!(concat!(
+            "name                kitty(0.35.2)\n",
+            "kitty-keyboard      unknown\n",
+            "synchronized-output yes\n",
+            "truecolor           yes\n",
+            "hyperlinks          yes\n",
+            "clipboard           unknown\n",
+            "styled-underlines   unknown\n",
+        ))
+    );
+
+    // Once the terminal answered everything, what it did not answer is unsupported.
+    info.probe = ProbeState::Done;
+    info.record(ProbeReply::Background(Color24 { r: 0, g: 0, b: 0 }));
+    assert_eq!(info.background, Some(Color24 { r: 0, g: 0, b: 0 }));
+    assert_eq!(
+        info.describe().unwrap(),
+        L!(concat!(
+            "name                kitty(0.35.2)\n",
+            "kitty-keyboard      no\n",
+            "synchronized-output yes\n",
+            "truecolor           yes\n",
+            "hyperlinks          yes\n",
+            "clipboard           no\n",
+            "styled-underlines   no\n",
+        ))
+    );
+
+    // A terminal without a known name does not get hyperlinks.
+    let mut info = TerminalInfo::default();
+    info.record(ProbeReply::Name(L!("xterm(390)").to_owned()));
+    assert!(!info.supports(TerminalFeature::Hyperlinks));
+}
@@ -1,4 +1,5 @@
 use crate::env::{EnvMode, Environment};
+use crate::io::IoChain;
 use crate::parser::Parser;
 use crate::termsize::*;
 use crate::tests::prelude::*;
@@ -40,10 +41,16 @@ fn test_termsize() {
     assert_eq!(ts.last(), Termsize::defaults());
 
     // Ok now we tell it to update.
+    parser.eval(
+        L!("function on_resize --on-event fish_resize; set -g resized $argv; end"),
+        &IoChain::new(),
+    );
     ts.updating(parser);
     assert_eq!(ts.last(), Termsize::new(42, 84));
     assert_eq!(vars.get(L!("COLUMNS")).unwrap().as_string(), "42");
     assert_eq!(vars.get(L!("LINES")).unwrap().as_string(), "84");
+    assert_eq!(vars.get(L!("resized")).unwrap().as_string(), "42 84");
+    parser.eval(L!("functions --erase on_resize"), &IoChain::new());
 
     // Wow someone set COLUMNS and LINES to a weird value.
     // Now the tty's termsize doesn't matter.
@@ -0,0 +1,72 @@
+use crate::color::Color24;
+use crate::theme::{Theme, ThemeVariant};
+use crate::wchar::prelude::*;
+
+#[test]
+fn test_theme_parse() {
+    let (theme, warnings) = Theme::parse(
+        L!("test").to_owned(),
+        L!("# name: 'Test'\n\
+            fish_color_normal normal\n\
+            fish_color_command blue '--bold'\n\
+            \n\
+            [light]\n\
+            fish_color_command 005\n\
+            [dark]\n\
+            fish_color_comment brblack -i\n"),
+    );
+    assert!(warnings.is_empty());
+    assert!(theme.has_variants());
+    let strs = |v: &[&str]| v.iter().map(|s| WString::from_str(s)).collect::<Vec<_>>();
+    assert_eq!(
+        theme.colors(ThemeVariant::Light),
+        vec![
+            (L!("fish_color_normal").to_owned(), strs(&["normal"])),
+            (L!("fish_color_command").to_owned(), strs(&["005"])),
+        ]
+    );
+    assert_eq!(
+        theme.colors(ThemeVariant::Dark),
+        vec![
+            (L!("fish_color_normal").to_owned(), strs(&["normal"])),
+            (
+                L!("fish_color_command").to_owned(),
+                strs(&["blue", "--bold"])
+            ),
+            (
+                L!("fish_color_comment").to_owned(),
+                strs(&["brblack", "-i"])
+            ),
+        ]
+    );
+
+    // Lines that are not understood are skipped with a warning.
+    let (theme, warnings) = Theme::parse(
+        L!("odd").to_owned(),
+        L!("fish_color_normal normal\n\
+            PATH /bin\n\
+            fish_color_command nocolor\n\
+            [blue]\n\
+            fish_color_comment red\n\
+            [dark]\n\
+            fish_color_command green\n"),
+    );
+    let lines: Vec<usize> = warnings.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, [2, 3, 4]);
+    assert_eq!(
+        theme.colors(ThemeVariant::Dark),
+        vec![
+            (L!("fish_color_normal").to_owned(), strs(&["normal"])),
+            (L!("fish_color_command").to_owned(), strs(&["green"])),
+        ]
+    );
+}
+
+#[test]
+fn test_theme_variant_for_background() {
+    let variant = |r, g, b| ThemeVariant::for_background(Color24 { r, g, b });
+    assert_eq!(variant(0xff, 0xff, 0xff), ThemeVariant::Light);
+    assert_eq!(variant(0xfd, 0xf6, 0xe3), ThemeVariant::Light);
+    assert_eq!(variant(0x00, 0x2b, 0x36), ThemeVariant::Dark);
+    assert_eq!(variant(0x00, 0x00, 0x00), ThemeVariant::Dark);
+}
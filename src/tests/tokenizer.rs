@@ -1,7 +1,7 @@
 use crate::redirection::RedirectionMode;
 use crate::tokenizer::{
     MoveWordStateMachine, MoveWordStyle, PipeOrRedir, TokFlags, TokenType, Tokenizer,
-    TokenizerError,
+    TokenizerError, TOK_AMPERSAND_ENDS_TOKEN,
 };
 use crate::wchar::prelude::*;
 use libc::{STDERR_FILENO, STDOUT_FILENO};
@@ -56,6 +56,16 @@ fn test_tokenizer() {
         assert_eq!(&actual_types[..], types);
     }
 
+    // "&" can be made to end a token, like before the ampersand-nobg-in-token feature.
+    {
+        let t = Tokenizer::new(L!("echo foo&bar"), TOK_AMPERSAND_ENDS_TOKEN);
+        let actual_types: Vec<_> = t.map(|token| token.type_).collect();
+        assert_eq!(
+            actual_types,
+            [tt::string, tt::string, tt::background, tt::string]
+        );
+    }
+
     // Test some errors.
 
     {
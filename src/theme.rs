@@ -0,0 +1,339 @@
+//! Color themes.
+//!
+//! A theme file sets the `fish_color_*` and `fish_pager_color_*` variables, one per line, like
+//! `fish_color_command blue --bold`. Lines starting with `#` are comments. A theme may have
+//! different colors for light and dark terminal backgrounds: lines after a `[light]` or `[dark]`
+//! header only apply to that variant, while lines before the first header apply to both.
+
+use crate::color::{Color24, RgbColor};
+use crate::common::{str2wcstring, unescape_string, wcs2osstring, UnescapeStringStyle};
+use crate::env::{EnvMode, EnvStack, EnvVar, EnvVarFlags, Environment};
+use crate::event::{self, Event};
+use crate::highlight::{get_fallback, get_highlight_var_name, HighlightRole};
+use crate::output::{parse_color, Outputter};
+use crate::terminal::{terminal_info, ProbeState};
+use crate::tokenizer::{Tokenizer, TOK_ACCEPT_UNFINISHED};
+use crate::wchar::prelude::*;
+use crate::wutil::dir_iter::DirIter;
+use std::sync::Mutex;
+
+/// Whether a theme is meant for a light or a dark background.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ThemeVariant {
+    Light,
+    Dark,
+}
+
+impl ThemeVariant {
+    pub const ALL: [ThemeVariant; 2] = [ThemeVariant::Light, ThemeVariant::Dark];
+
+    pub fn name(self) -> &'static wstr {
+        match self {
+            ThemeVariant::Light => L!("light"),
+            ThemeVariant::Dark => L!("dark"),
+        }
+    }
+
+    pub fn from_name(name: &wstr) -> Option<Self> {
+        Self::ALL.into_iter().find(|variant| variant.name() == name)
+    }
+
+    /// Return the variant that suits text on the given background color.
+    pub fn for_background(color: Color24) -> Self {
+        // Perceived brightness, as in ITU-R BT.601.
+        let brightness =
+            299 * u32::from(color.r) + 587 * u32::from(color.g) + 114 * u32::from(color.b);
+        if brightness > 1000 * 255 / 2 {
+            ThemeVariant::Light
+        } else {
+            ThemeVariant::Dark
+        }
+    }
+
+    /// Return the variant that suits the background the terminal reported, if it did.
+    pub fn detect() -> Option<Self> {
+        terminal_info().background.map(Self::for_background)
+    }
+}
+
+/// A variable set by a theme, with its value.
+pub type ThemeColor = (WString, Vec<WString>);
+
+/// A parsed theme file.
+#[derive(Clone, Debug, Default)]
+pub struct Theme {
+    pub name: WString,
+    /// The colors for both variants.
+    common: Vec<ThemeColor>,
+    light: Vec<ThemeColor>,
+    dark: Vec<ThemeColor>,
+}
+
+/// Return whether a theme may set the variable `name`.
+pub fn is_theme_variable(name: &wstr) -> bool {
+    name.starts_with("fish_color_") || name.starts_with("fish_pager_color_")
+}
+
+/// Set `name` to `value` in `colors`, replacing an earlier value.
+fn set_theme_color(colors: &mut Vec<ThemeColor>, name: WString, value: Vec<WString>) {
+    match colors.iter_mut().find(|(n, _)| *n == name) {
+        Some(color) => color.1 = value,
+        None => colors.push((name, value)),
+    }
+}
+
+impl Theme {
+    /// Parse the contents of a theme file. Lines that are not understood are skipped; return the
+    /// number of each such line and what is wrong with it, along with the theme.
+    pub fn parse(name: WString, contents: &wstr) -> (Theme, Vec<(usize, WString)>) {
+        let mut theme = Theme {
+            name,
+            ..Default::default()
+        };
+        let mut warnings = vec![];
+        let mut section = None;
+        let mut in_unknown_section = false;
+        for (i, line) in contents.split('\n').enumerate() {
+            let trimmed = line.trim_matches(' ');
+            if trimmed.starts_with("[") && trimmed.ends_with("]") {
+                let header = trimmed.slice_to(trimmed.len() - 1).slice_from(1);
+                let Some(variant) = ThemeVariant::from_name(header) else {
+                    warnings.push((i + 1, wgettext_fmt!("unknown section '%ls'", trimmed)));
+                    in_unknown_section = true;
+                    continue;
+                };
+                section = Some(variant);
+                in_unknown_section = false;
+                continue;
+            }
+            if in_unknown_section {
+                continue;
+            }
+
+            let mut tok = Tokenizer::new(line, TOK_ACCEPT_UNFINISHED);
+            let mut tokens = vec![];
+            while let Some(t) = tok.next() {
+                let text = tok.text_of(&t);
+                tokens.push(
+                    unescape_string(text, UnescapeStringStyle::default())
+                        .unwrap_or_else(|| text.to_owned()),
+                );
+            }
+            if tokens.is_empty() {
+                continue;
+            }
+            let name = tokens.remove(0);
+            if !is_theme_variable(&name) {
+                warnings.push((i + 1, wgettext_fmt!("'%ls' is not a color variable", name)));
+                continue;
+            }
+            if let Some(bad) = tokens
+                .iter()
+                .find(|t| !t.starts_with("-") && RgbColor::from_wstr(t).is_none())
+            {
+                warnings.push((i + 1, wgettext_fmt!("unknown color '%ls'", bad)));
+                continue;
+            }
+            let colors = match section {
+                None => &mut theme.common,
+                Some(ThemeVariant::Light) => &mut theme.light,
+                Some(ThemeVariant::Dark) => &mut theme.dark,
+            };
+            set_theme_color(colors, name, tokens);
+        }
+        (theme, warnings)
+    }
+
+    /// Return whether the theme has separate light and dark colors.
+    pub fn has_variants(&self) -> bool {
+        !self.light.is_empty() || !self.dark.is_empty()
+    }
+
+    /// Return the colors of the given variant.
+    pub fn colors(&self, variant: ThemeVariant) -> Vec<ThemeColor> {
+        let mut colors = self.common.clone();
+        let specific = match variant {
+            ThemeVariant::Light => &self.light,
+            ThemeVariant::Dark => &self.dark,
+        };
+        for (name, value) in specific {
+            set_theme_color(&mut colors, name.clone(), value.clone());
+        }
+        colors
+    }
+}
+
+/// Return the directories that contain theme files, in order of precedence.
+pub fn theme_dirs(vars: &dyn Environment) -> Vec<WString> {
+    let mut dirs = vec![];
+    if let Some(config_dir) = vars.get_unless_empty(L!("__fish_config_dir")) {
+        dirs.push(config_dir.as_string() + L!("/themes"));
+    }
+    if let Some(data_dir) = vars.get_unless_empty(L!("__fish_data_dir")) {
+        dirs.push(data_dir.as_string() + L!("/tools/web_config/themes"));
+    }
+    dirs
+}
+
+/// Return the names of all available themes, sorted.
+pub fn theme_names(vars: &dyn Environment) -> Vec<WString> {
+    let mut names = vec![];
+    for dir in theme_dirs(vars) {
+        let Ok(mut iter) = DirIter::new(&dir) else {
+            continue;
+        };
+        while let Some(Ok(entry)) = iter.next() {
+            if entry.name.ends_with(".theme") {
+                let name = entry.name.slice_to(entry.name.len() - ".theme".len());
+                names.push(name.to_owned());
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Find the theme with the given name and parse it. Return it along with warnings about the lines
+/// that were skipped.
+pub fn theme_load(vars: &dyn Environment, name: &wstr) -> Result<(Theme, Vec<WString>), WString> {
+    if name.is_empty() || name.contains('/') {
+        return Err(wgettext_fmt!("%ls: no such theme", name));
+    }
+    for dir in theme_dirs(vars) {
+        let path = sprintf!("%ls/%ls.theme", dir, name);
+        let Ok(contents) = std::fs::read(wcs2osstring(&path)) else {
+            continue;
+        };
+        let (theme, warnings) = Theme::parse(name.to_owned(), &str2wcstring(&contents));
+        let warnings = warnings
+            .into_iter()
+            .map(|(line, warning)| sprintf!("%ls:%lu: %ls", path, line, warning))
+            .collect();
+        return Ok((theme, warnings));
+    }
+    Err(wgettext_fmt!("%ls: no such theme", name))
+}
+
+/// Color variables that fish's prompts use, which are cleared by themes that don't set them.
+const PROMPT_COLOR_VARIABLES: &[&wstr] = &[
+    L!("fish_color_cwd"),
+    L!("fish_color_user"),
+    L!("fish_color_host"),
+    L!("fish_color_host_remote"),
+    L!("fish_color_cancel"),
+];
+
+/// Set the colors of the given variant of `theme` in the scope given by `mode`, and clear the
+/// known color variables that it does not set, so that nothing of the previous theme remains.
+/// Return the names of all variables that were changed.
+pub fn theme_apply(
+    vars: &EnvStack,
+    theme: &Theme,
+    variant: ThemeVariant,
+    mode: EnvMode,
+) -> Vec<WString> {
+    let colors = theme.colors(variant);
+    let unset = HighlightRole::ALL
+        .into_iter()
+        .map(get_highlight_var_name)
+        .chain(PROMPT_COLOR_VARIABLES.iter().copied())
+        .filter(|&name| colors.iter().all(|(n, _)| &**n != name))
+        .map(|name| (name.to_owned(), vec![]));
+    let mut changed = vec![];
+    for (name, value) in colors.iter().cloned().chain(unset) {
+        if mode.contains(EnvMode::UNIVERSAL) {
+            // Erase any shadowing global, so the change is visible right away.
+            vars.remove(&name, EnvMode::GLOBAL);
+        }
+        vars.set(&name, mode, value);
+        changed.push(name);
+    }
+    changed
+}
+
+/// A theme that was chosen before the terminal reported its background, so its variant had to be
+/// guessed.
+static PENDING_THEME: Mutex<Option<(Theme, EnvMode)>> = Mutex::new(None);
+
+/// Apply `theme`, using the given variant or else the one that suits the terminal's background. If
+/// the terminal has yet to report its background, use the dark variant for now, and apply the
+/// right one once it does. Return the names of all variables that were changed.
+pub fn theme_choose(
+    vars: &EnvStack,
+    theme: &Theme,
+    variant: Option<ThemeVariant>,
+    mode: EnvMode,
+) -> Vec<WString> {
+    let detected = ThemeVariant::detect();
+    let is_guess = variant.is_none()
+        && detected.is_none()
+        && theme.has_variants()
+        && terminal_info().probe != ProbeState::Done;
+    *PENDING_THEME.lock().unwrap() = is_guess.then(|| (theme.clone(), mode));
+    let variant = variant.or(detected).unwrap_or(ThemeVariant::Dark);
+    theme_apply(vars, theme, variant, mode)
+}
+
+/// Called when the terminal has told us what it supports, to fix up the variant of a theme chosen
+/// before that.
+pub fn theme_apply_pending(vars: &EnvStack) {
+    let Some((theme, mode)) = PENDING_THEME.lock().unwrap().take() else {
+        return;
+    };
+    if ThemeVariant::detect() == Some(ThemeVariant::Light) {
+        // There is no parser at hand, so queue the events, again only once all variables are set.
+        for name in theme_apply(vars, &theme, ThemeVariant::Light, mode) {
+            event::enqueue(Event::variable_set(name));
+        }
+    }
+}
+
+/// Return a sample command line. If `colorize` is set, it is highlighted with the colors that
+/// `lookup` returns for a variable name.
+pub fn theme_demo(lookup: &dyn Fn(&wstr) -> Option<Vec<WString>>, colorize: bool) -> WString {
+    const DEMO: &[(HighlightRole, &wstr)] = &[
+        (HighlightRole::command, L!("/bright/vixens")),
+        (HighlightRole::normal, L!(" ")),
+        (HighlightRole::param, L!("jump")),
+        (HighlightRole::normal, L!(" ")),
+        (HighlightRole::redirection, L!("|")),
+        (HighlightRole::normal, L!(" ")),
+        (HighlightRole::quote, L!("\"fowl\"")),
+        (HighlightRole::normal, L!(" ")),
+        (HighlightRole::redirection, L!("> quack")),
+        (HighlightRole::normal, L!(" ")),
+        (HighlightRole::statement_terminator, L!("&")),
+        (HighlightRole::comment, L!(" # This is a comment")),
+        (HighlightRole::normal, L!("\n")),
+        (HighlightRole::command, L!("echo")),
+        (HighlightRole::normal, L!(" ")),
+        (HighlightRole::error, L!("'")),
+        (
+            HighlightRole::quote,
+            L!("Errors are the portal to discovery"),
+        ),
+        (HighlightRole::normal, L!("\n")),
+        (HighlightRole::command, L!("Th")),
+        (HighlightRole::autosuggestion, L!("is an autosuggestion")),
+        (HighlightRole::normal, L!("\n")),
+    ];
+    let color_of = |role: HighlightRole| {
+        [role, get_fallback(role), HighlightRole::normal]
+            .into_iter()
+            .find_map(|role| lookup(get_highlight_var_name(role)).filter(|v| !v.is_empty()))
+            .map(|value| parse_color(&EnvVar::new_vec(value, EnvVarFlags::empty()), false))
+            .unwrap_or(RgbColor::NORMAL)
+    };
+    let mut outp = Outputter::new_buffering();
+    for &(role, text) in DEMO {
+        if colorize {
+            outp.set_color(color_of(role), RgbColor::NORMAL);
+        }
+        outp.write_wstr(text);
+    }
+    if colorize {
+        outp.set_color(RgbColor::NORMAL, RgbColor::NORMAL);
+    }
+    str2wcstring(outp.contents())
+}
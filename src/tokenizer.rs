@@ -144,6 +144,10 @@ pub const TOK_SHOW_BLANK_LINES: TokFlags = TokFlags(4);
 /// Make an effort to continue after an error.
 pub const TOK_CONTINUE_AFTER_ERROR: TokFlags = TokFlags(8);
 
+/// Let "&" end a token even if it is followed by a string character, as it did before the
+/// ampersand-nobg-in-token feature.
+pub const TOK_AMPERSAND_ENDS_TOKEN: TokFlags = TokFlags(16);
+
 impl From<TokenizerError> for &'static wstr {
     fn from(err: TokenizerError) -> Self {
         match err {
@@ -262,6 +266,8 @@ pub struct Tokenizer<'c> {
     continue_after_error: bool,
     /// Whether to continue the previous line after the comment.
     continue_line_after_comment: bool,
+    /// Whether "&" always ends a token.
+    ampersand_ends_token: bool,
     /// Called on every quote change.
     on_quote_toggle: Option<&'c mut dyn FnMut(usize)>,
 }
@@ -298,6 +304,7 @@ impl<'c> Tokenizer<'c> {
             show_blank_lines: flags & TOK_SHOW_BLANK_LINES,
             continue_after_error: flags & TOK_CONTINUE_AFTER_ERROR,
             continue_line_after_comment: false,
+            ampersand_ends_token: flags & TOK_AMPERSAND_ENDS_TOKEN,
             on_quote_toggle,
         }
     }
@@ -555,6 +562,15 @@ impl<'c> Tokenizer<'c> {
 }
 
 impl<'c> Tokenizer<'c> {
+    /// Return whether `c`, followed by `next`, is part of a string token.
+    fn is_string_character(&self, c: char, next: Option<char>) -> bool {
+        tok_is_string_character_impl(
+            c,
+            next,
+            !self.ampersand_ends_token && feature_test(FeatureFlag::ampersand_nobg_in_token),
+        )
+    }
+
     /// Read the next token as a string.
     fn read_string(&mut self) -> Tok {
         let mut mode = TOK_MODE_REGULAR_TEXT;
@@ -713,7 +729,7 @@ impl<'c> Tokenizer<'c> {
                     break;
                 }
             } else if mode == TOK_MODE_REGULAR_TEXT
-                && !tok_is_string_character(
+                && !self.is_string_character(
                     c,
                     self.start
                         .as_char_slice()
@@ -729,7 +745,7 @@ impl<'c> Tokenizer<'c> {
                 .as_char_slice()
                 .get(self.token_cursor + 1)
                 .copied();
-            is_token_begin = is_token_delimiter(c, next);
+            is_token_begin = c == '(' || !self.is_string_character(c, next);
             self.token_cursor += 1;
         }
 
@@ -816,14 +832,18 @@ pub fn comment_end(s: &wstr, mut pos: usize) -> usize {
 
 /// Tests if this character can be a part of a string. Hash (#) starts a comment if it's the first
 /// character in a token; otherwise it is considered a string character. See issue #953.
-fn tok_is_string_character(c: char, next: Option<char>) -> bool {
+pub fn tok_is_string_character(c: char, next: Option<char>) -> bool {
+    tok_is_string_character_impl(c, next, feature_test(FeatureFlag::ampersand_nobg_in_token))
+}
+
+fn tok_is_string_character_impl(c: char, next: Option<char>, ampersand_in_token: bool) -> bool {
     match c {
         // Unconditional separators.
         '\0' | ' ' | '\n' | '|' | '\t' | ';' | '\r' | '<' | '>' => false,
         '&' => {
-            if feature_test(FeatureFlag::ampersand_nobg_in_token) {
+            if ampersand_in_token {
                 // Unlike in other shells, '&' is not special if followed by a string character.
-                next.map(|nc| tok_is_string_character(nc, None))
+                next.map(|nc| tok_is_string_character_impl(nc, None, true))
                     .unwrap_or(false)
             } else {
                 false
@@ -1,5 +1,6 @@
 use crate::common::wcs2osstring;
 use crate::env_universal_common::default_vars_path;
+use crate::kill::KILL_RING_FILE_NAME;
 use crate::universal_notifier::UniversalNotifier;
 use crate::wchar::prelude::*;
 use crate::wutil::{wbasename, wdirname};
@@ -11,14 +12,17 @@ use std::os::fd::{AsRawFd, RawFd};
 pub struct InotifyNotifier {
     // The inotify instance.
     inotify: Inotify,
-    // The basename of the file to watch.
-    basename: OsString,
+    // The basenames of the files to watch.
+    basenames: Vec<OsString>,
 }
 
 impl InotifyNotifier {
     /// Create a notifier at the default fish_variables path.
+    /// This also reports changes to the shared killring, which lives next to it.
     pub fn new() -> Option<Self> {
-        Self::new_at(&default_vars_path())
+        let mut notifier = Self::new_at(&default_vars_path())?;
+        notifier.basenames.push(wcs2osstring(KILL_RING_FILE_NAME));
+        Some(notifier)
     }
 
     /// Create a notifier at a given path.
@@ -38,7 +42,7 @@ impl InotifyNotifier {
             .ok()?;
         Some(InotifyNotifier {
             inotify,
-            basename: wcs2osstring(basename),
+            basenames: vec![wcs2osstring(basename)],
         })
     }
 }
@@ -60,8 +64,11 @@ impl UniversalNotifier for InotifyNotifier {
         let Ok(evts) = self.inotify.read_events() else {
             return false;
         };
-        evts.iter()
-            .any(|evt| evt.name.as_ref() == Some(&self.basename))
+        evts.iter().any(|evt| {
+            evt.name
+                .as_ref()
+                .is_some_and(|name| self.basenames.contains(name))
+        })
     }
 }
 
@@ -148,6 +148,49 @@ bind \r
 bind \n 2>&1
 # CHECK: bind: No binding found for key 'ctrl-j'
 
+# Per-binding sequence timeouts.
+bind -M insert --timeout 200 --on-timeout insert j,k 'set fish_bind_mode default'
+bind -M insert j,k
+# CHECK: bind -M insert --timeout 200 --on-timeout insert j,k 'set fish_bind_mode default'
+bind --timeout 500 ctrl-x,ctrl-t 'echo chord'
+bind ctrl-x,ctrl-t
+# CHECK: bind --timeout 500 ctrl-x,ctrl-t 'echo chord'
+bind --on-timeout insert ctrl-x,ctrl-t true
+# CHECKERR: bind: invalid option combination, --on-timeout requires --timeout
+bind --timeout -1 ctrl-x,ctrl-t true
+# CHECKERR: bind: -1: invalid integer
+bind --timeout 10 --on-timeout nothing ctrl-x,ctrl-t true
+# CHECKERR: bind: Invalid timeout action 'nothing'
+
+# Keyboard macros.
+bind --macro=z f6
+# CHECKERR: bind: No macro has been recorded
+bind --macro=ab f6
+# CHECKERR: bind: Invalid macro register 'ab'
+
+# Per-mode sequence timeouts, for bindings without their own.
+bind -M chords --timeout 300 --on-timeout insert
+bind -M chords ctrl-x,ctrl-t 'echo chord'
+bind -M chords --timeout 100 j,k true
+bind -M chords
+# CHECK: bind -M chords --timeout 300 --on-timeout insert
+# CHECK: bind -M chords ctrl-x,ctrl-t 'echo chord'
+# CHECK: bind -M chords --timeout 100 j,k true
+bind -M chords --timeout 400
+bind -M chords
+# CHECK: bind -M chords --timeout 400
+# CHECK: bind -M chords ctrl-x,ctrl-t 'echo chord'
+# CHECK: bind -M chords --timeout 100 j,k true
+bind --preset --user -M chords --timeout 400
+# CHECKERR: bind: --preset --user: options cannot be used together
+bind --erase --all -M chords
+bind -M chords
+
+# Descriptions for sequence hints.
+bind --description 'Edit in $EDITOR' ctrl-x,ctrl-e edit_command_buffer
+bind ctrl-x,ctrl-e
+# CHECK: bind --description 'Edit in $EDITOR' ctrl-x,ctrl-e edit_command_buffer
+
 bind _\cx_\ci_\ei_\\_\'_ 'echo foo'
 # CHECKERR: bind: cannot parse key '_\cx_\t_\ei_\\_'_'
 
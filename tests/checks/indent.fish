@@ -525,3 +525,183 @@ end
 # CHECK: {{^}}"
 # CHECK: {{^}}    end
 # CHECK: {{^}})
+
+echo 'echo aaaa bbbb cccc dddd eeee' | $fish_indent --max-width=15
+# CHECK: {{^}}echo aaaa \
+# CHECK: {{^}}    bbbb cccc \
+# CHECK: {{^}}    dddd eeee
+
+echo 'echo aaaa | string upper | string lower' | $fish_indent --max-width=20
+# CHECK: {{^}}echo aaaa |
+# CHECK: {{^}}    string upper |
+# CHECK: {{^}}    string lower
+
+echo 'true && echo aaaa; or echo bbbb' | $fish_indent --max-width=15
+# CHECK: {{^}}true &&
+# CHECK: {{^}}    echo aaaa
+# CHECK: {{^}}or echo bbbb
+
+printf '%s\n' 'echo aaaa bbbb cccc dddd' 'echo eeee | string upper' | $fish_indent --max-width=15
+# CHECK: {{^}}echo aaaa \
+# CHECK: {{^}}    bbbb cccc \
+# CHECK: {{^}}    dddd
+# CHECK: {{^}}echo eeee |
+# CHECK: {{^}}    string upper
+
+# Tokens that are too long on their own stay as they are.
+echo 'echo aaaaaaaaaaaaaaaaaaaa # a comment' | $fish_indent --max-width=10
+# CHECK: {{^}}echo \
+# CHECK: {{^}}    aaaaaaaaaaaaaaaaaaaa # a comment
+
+set -l tmpdir (mktemp -d)
+echo 'max-width = 15 # a comment' >$tmpdir/.fish_indent
+echo 'echo aaaa bbbb cccc' >$tmpdir/file.fish
+$fish_indent $tmpdir/file.fish
+# CHECK: {{^}}echo aaaa \
+# CHECK: {{^}}    bbbb cccc
+$fish_indent --max-width=0 $tmpdir/file.fish
+# CHECK: {{^}}echo aaaa bbbb cccc
+
+echo 'width = 15' >$tmpdir/.fish_indent
+$fish_indent $tmpdir/file.fish
+# CHECKERR: {{.*}}/.fish_indent:1: warning: ignoring unknown setting 'width'
+# CHECK: {{^}}echo aaaa bbbb cccc
+echo $status
+# CHECK: 0
+
+echo 'max-width = wide' >$tmpdir/.fish_indent
+$fish_indent $tmpdir/file.fish
+# CHECKERR: {{.*}}/.fish_indent:1: invalid setting 'max-width = wide'
+echo $status
+# CHECK: 1
+# Standard input uses the config of the current directory, unless told not to.
+pushd $tmpdir
+echo 'echo aaaa bbbb cccc' | $fish_indent
+echo $status
+# CHECKERR: {{.*}}/.fish_indent:1: invalid setting 'max-width = wide'
+# CHECK: 1
+echo 'echo aaaa bbbb cccc' | $fish_indent --no-config
+echo $status
+# CHECK: {{^}}echo aaaa bbbb cccc
+# CHECK: 0
+popd
+$fish_indent --no-config $tmpdir/file.fish
+# CHECK: {{^}}echo aaaa bbbb cccc
+rm $tmpdir/.fish_indent
+
+echo 'echo  foo
+if true
+echo bar
+end' >$tmpdir/diff.fish
+echo 'echo foo' >$tmpdir/good.fish
+$fish_indent --diff $tmpdir/diff.fish
+# CHECK: --- {{.*}}/diff.fish	(original)
+# CHECK: +++ {{.*}}/diff.fish	(formatted)
+# CHECK: @@ -1,4 +1,4 @@
+# CHECK: -echo  foo
+# CHECK: +echo foo
+# CHECK: {{^}} if true
+# CHECK: -echo bar
+# CHECK: +    echo bar
+# CHECK: {{^}} end
+echo $status
+# CHECK: 1
+
+$fish_indent --diff $tmpdir/good.fish
+echo $status
+# CHECK: 0
+
+printf 'echo foo\necho bar' | $fish_indent --diff
+# CHECK: --- -	(original)
+# CHECK: +++ -	(formatted)
+# CHECK: @@ -1,2 +1,2 @@
+# CHECK: {{^}} echo foo
+# CHECK: -echo bar
+# CHECK: \ No newline at end of file
+# CHECK: +echo bar
+
+$fish_indent --diff $tmpdir/good.fish $tmpdir/diff.fish >/dev/null
+# CHECKERR: 1 of 2 files would be reformatted
+
+# A large file where every third line is re-indented still diffs quickly.
+printf 'if true\necho %s\nend\n' (seq 5000) >$tmpdir/big.fish
+$fish_indent --diff $tmpdir/big.fish | string match -rv '^ ' | count
+# CHECK: 10003
+rm -r $tmpdir
+
+echo 'echo hi # greeting' | $fish_indent --dump-ast=json
+# CHECK: {"file":null,"ast":{"type":"job_list","category":"list","start":{"offset":0,"line":1,"column":1},{{.*}},{"type":"argument","category":"leaf","start":{"offset":5,"line":1,"column":6},"end":{"offset":7,"line":1,"column":8},"text":"hi","children":[]}{{.*}}"comments":[{"start":{"offset":8,"line":1,"column":9},"end":{"offset":18,"line":1,"column":19}}],"errors":[]}
+
+echo 'echo
+end' | $fish_indent --dump-ast=json
+# CHECK: {{.*}}"errors":[{"code":"unbalancing_end","message":{{.*}},"start":{"offset":5,"line":2,"column":1},{{.*}}}]}
+
+$fish_indent --dump-ast=xml </dev/null
+# CHECKERR: Invalid AST format 'xml', expected 'json'
+
+echo 'echo %self a%self \'%self\'' | $fish_indent --fix=remove-percent-self
+# CHECK: echo $fish_pid a%self '%self'
+
+echo 'cat foo ^/dev/null ^^log' | $fish_indent --fix=stderr-nocaret
+# CHECKERR: Warning: feature 'stderr-nocaret' is already enabled, fixing its syntax may change what scripts do
+# CHECK: cat foo 2>/dev/null 2>>log
+
+# Only unambiguous redirections are fixed. Regexes stay, other arguments are reported.
+printf '%s\n' 'cat foo ^&1 ^&- ^^log' 'grep ^foo file ^/dev/null' 'echo ^out' | $fish_indent --fix=stderr-nocaret
+# CHECKERR: Warning: feature 'stderr-nocaret' is already enabled, fixing its syntax may change what scripts do
+# CHECKERR: -:3: warning: not fixing '^out', use '2>' instead if it redirects stderr
+# CHECK: cat foo 2>&1 2>&- 2>>log
+# CHECK: grep ^foo file 2>/dev/null
+# CHECK: echo ^out
+
+echo 'echo foo? "bar?" qux\?' | $fish_indent --fix=qmark-noglob
+# CHECKERR: Warning: feature 'qmark-noglob' is already enabled, fixing its syntax may change what scripts do
+# CHECK: echo foo\? "bar?" qux\?
+
+printf '%s\n' "string replace -r a '\\\\\\\\' x" "string replace a '\\\\\\\\' x" "string replace -a -r -- a '\\\\n' \$x" | $fish_indent --fix=regex-easyesc
+# CHECKERR: Warning: feature 'regex-easyesc' is already enabled, fixing its syntax may change what scripts do
+# CHECK: string replace -r a '\\' x
+# CHECK: string replace a '\\\\' x
+# CHECK: string replace -a -r -- a \n $x
+
+echo 'echo foo&bar; echo baz &' | $fish_indent --fix=ampersand-nobg-in-token
+# CHECKERR: Warning: feature 'ampersand-nobg-in-token' is already enabled, fixing its syntax may change what scripts do
+# CHECK: echo foo & bar
+# CHECK: echo baz &
+
+echo 'test $foo; test -n $foo; [ -z $bar ]; test -n "$foo"; test foo; test $a = $b' | $fish_indent --fix=test-require-arg
+# CHECK: test -n "$foo"
+# CHECK: test -n "$foo"
+# CHECK: [ -z "$bar" ]
+# CHECK: test -n "$foo"
+# CHECK: test -n foo
+# CHECK: test $a = $b
+
+echo 'echo %self' | $fish_indent --fix=all --check
+echo $status
+# CHECK: 1
+
+$fish_indent --fix=foo </dev/null
+# CHECKERR: Unknown feature 'foo'
+
+# "all" only covers the features that are not enabled yet.
+echo 'string match -r ^foo bar; echo %self' | $fish_indent --fix=all
+# CHECK: string match -r ^foo bar
+# CHECK: echo $fish_pid
+
+echo 'echo "a?$(echo b?)" (echo c?)' | $fish_indent --fix=qmark-noglob
+# CHECKERR: Warning: feature 'qmark-noglob' is already enabled, fixing its syntax may change what scripts do
+# CHECK: echo "a?$(echo b\?)" (echo c\?)
+
+echo 'echo (echo foo&bar)' | $fish_indent --fix=ampersand-nobg-in-token
+# CHECKERR: Warning: feature 'ampersand-nobg-in-token' is already enabled, fixing its syntax may change what scripts do
+# CHECK: echo (echo foo& bar)
+
+echo 'test (foo); test -n (string match -q x); [ -z $(bar) ]; echo (test $x)' | $fish_indent --fix=test-require-arg
+# CHECK: test -n "$(foo)"
+# CHECK: test -n "$(string match -q x)"
+# CHECK: [ -z "$(bar)" ]
+# CHECK: echo (test -n "$x")
+
+echo 'echo %self; end' | $fish_indent --fix=remove-percent-self >/dev/null
+# CHECKERR: -: not fixing deprecated syntax because of syntax errors
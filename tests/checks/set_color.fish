@@ -0,0 +1,30 @@
+#RUN: %fish %s
+
+set_color --hyperlink https://fishshell.com | string escape
+# CHECK: \e\]8\;\;https://fishshell.com\e\\
+
+set_color --hyperlink "" | string escape
+# CHECK: \e\]8\;\;\e\\
+
+set_color --hyperlink \e
+# CHECKERR: set_color: Invalid hyperlink '{{.*}}'
+echo $status
+# CHECK: 2
+
+# Until the terminal says that it supports underline styles and colors, only underline plainly.
+set_color --underline=curly | string escape
+# CHECK: \e\[4m
+
+set_color --underline-color red | count
+# CHECK: 0
+
+set_color --strikethrough --overline | string escape
+# CHECK: \e\[9m\e\[53m
+
+set_color --underline=wavy
+# CHECKERR: set_color: Unknown underline style 'wavy'
+echo $status
+# CHECK: 2
+
+set_color --underline-color normal
+# CHECKERR: set_color: Unknown color 'normal'
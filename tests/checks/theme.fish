@@ -0,0 +1,68 @@
+#RUN: %fish %s
+
+set -l tmpdir (mktemp -d)
+set -g __fish_config_dir $tmpdir
+mkdir $tmpdir/themes
+
+echo "# name: 'Test'
+fish_color_normal normal
+fish_color_command blue '--bold'
+
+[light]
+fish_color_command 005
+
+[dark]
+fish_color_comment brblack -i" >$tmpdir/themes/test.theme
+echo "fish_color_command red
+PATH /bin" >$tmpdir/themes/broken.theme
+
+theme list | string match -e -- test
+# CHECK: test
+
+theme list | string match -q -- "fish default"
+and echo has default
+# CHECK: has default
+
+set -g fish_color_cwd green
+theme choose --variant=light test
+set -S fish_color_command
+# CHECK: $fish_color_command: set in global scope, unexported, with 1 elements
+# CHECK: $fish_color_command[1]: |005|
+set -S fish_color_cwd
+# CHECK: $fish_color_cwd: set in global scope, unexported, with 0 elements
+
+theme choose --variant=dark test
+echo $fish_color_command $fish_color_comment
+# CHECK: blue --bold brblack -i
+
+function on_command_color --on-variable fish_color_command
+    echo command color is now $fish_color_command
+end
+theme choose --variant=light test
+# CHECK: command color is now 005
+functions -e on_command_color
+
+theme show --variant=dark test
+# CHECK: test (dark)
+# CHECK: /bright/vixens jump | "fowl" > quack & # This is a comment
+# CHECK: echo 'Errors are the portal to discovery
+# CHECK: This is an autosuggestion
+# CHECK:
+
+theme choose broken
+# CHECKERR: theme: {{.*}}/themes/broken.theme:2: 'PATH' is not a color variable
+echo $status
+# CHECK: 0
+echo $fish_color_command
+# CHECK: red
+
+theme choose nonexistent
+# CHECKERR: theme: nonexistent: no such theme
+
+theme choose --variant=sepia test
+# CHECKERR: theme: sepia: invalid variant, expected 'light' or 'dark'
+
+theme list --universal
+# CHECKERR: theme: invalid option combination, --universal can only be used with choose
+
+rm -r $tmpdir
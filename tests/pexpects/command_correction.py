@@ -0,0 +1,27 @@
+#!/usr/bin/env python3
+from pexpect_helper import SpawnedProc
+
+sp = SpawnedProc()
+send, sendline, expect_prompt, expect_str = (
+    sp.send,
+    sp.sendline,
+    sp.expect_prompt,
+    sp.expect_str,
+)
+expect_prompt()
+
+sendline("function corr_test_func; echo ran $argv; end")
+expect_prompt()
+
+# Running an unknown command suggests the likely correction.
+sendline("corr_test_fucn")
+expect_str("fish: Did you mean 'corr_test_func'?")
+expect_prompt()
+
+# While typing, the correction is offered after the command line, and alt-x accepts it.
+send("corr_test_fucn foo")
+expect_str("Did you mean 'corr_test_func'?")
+send("\x1bx")
+sendline("")
+expect_str("ran foo")
+expect_prompt()
@@ -59,6 +59,10 @@ expect_prompt()
 sendline("stty -a | string match -q '*ixon ixoff*'; echo $status")
 expect_prompt("0")
 
+# This terminal never answers our probe, so we stop waiting and treat everything as unsupported.
+sendline("status terminal | string match 'kitty-keyboard*'")
+expect_prompt("kitty-keyboard +no")
+
 # TODO
 import sys
 sys.exit(0)